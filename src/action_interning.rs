@@ -0,0 +1,200 @@
+// Defines an interner that gives every distinct action a compact identifier.
+// Sequences of identifiers can be hashed and compared much faster than the JSON representations of the actions.
+
+use crate::action_records::{Argument, BasicAction};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+pub type ActionId = u32;
+
+fn hash_argument(argument: &Argument, hasher: &mut DefaultHasher) {
+    match argument {
+        Argument::StringArgument(text) => {
+            0u8.hash(hasher);
+            text.hash(hasher);
+        }
+        Argument::IntArgument(value) => {
+            1u8.hash(hasher);
+            value.hash(hasher);
+        }
+        Argument::BoolArgument(value) => {
+            2u8.hash(hasher);
+            value.hash(hasher);
+        }
+        Argument::FloatArgument(value) => {
+            3u8.hash(hasher);
+            value.to_bits().hash(hasher);
+        }
+        Argument::CaptureArgument(capture) => {
+            4u8.hash(hasher);
+            capture.get_name().hash(hasher);
+            capture.get_instance().hash(hasher);
+        }
+        Argument::ListArgument(elements) => {
+            5u8.hash(hasher);
+            elements.len().hash(hasher);
+            for element in elements {
+                hash_argument(element, hasher);
+            }
+        }
+        Argument::DictArgument(entries) => {
+            6u8.hash(hasher);
+            entries.len().hash(hasher);
+            for (key, value) in entries {
+                key.hash(hasher);
+                hash_argument(value, hasher);
+            }
+        }
+        Argument::NoneArgument => 7u8.hash(hasher),
+    }
+}
+
+fn compute_action_hash(action: &BasicAction) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    action.get_name().hash(&mut hasher);
+    action.get_arguments().len().hash(&mut hasher);
+    for argument in action.get_arguments() {
+        hash_argument(argument, &mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Arguments are identical when their JSON representations are,
+/// so captures are compared without their postfixes and floating point numbers are compared by their bits.
+fn are_arguments_identical(first: &Argument, second: &Argument) -> bool {
+    match (first, second) {
+        (Argument::FloatArgument(first), Argument::FloatArgument(second)) => {
+            first.to_bits() == second.to_bits()
+        }
+        (Argument::CaptureArgument(first), Argument::CaptureArgument(second)) => {
+            first.get_name() == second.get_name() && first.get_instance() == second.get_instance()
+        }
+        (Argument::ListArgument(first), Argument::ListArgument(second)) => {
+            first.len() == second.len()
+                && first
+                    .iter()
+                    .zip(second)
+                    .all(|(first, second)| are_arguments_identical(first, second))
+        }
+        (Argument::DictArgument(first), Argument::DictArgument(second)) => {
+            first.len() == second.len()
+                && first.iter().zip(second).all(
+                    |((first_key, first_value), (second_key, second_value))| {
+                        first_key == second_key
+                            && are_arguments_identical(first_value, second_value)
+                    },
+                )
+        }
+        (first, second) => first == second,
+    }
+}
+
+fn are_actions_identical(first: &BasicAction, second: &BasicAction) -> bool {
+    first.get_name() == second.get_name()
+        && first.get_arguments().len() == second.get_arguments().len()
+        && first
+            .get_arguments()
+            .iter()
+            .zip(second.get_arguments())
+            .all(|(first, second)| are_arguments_identical(first, second))
+}
+
+/// Gives every distinct action an identifier, counting up from 0 in the order the actions are first seen.
+#[derive(Clone, Debug, Default)]
+pub struct ActionInterner {
+    actions: Vec<BasicAction>,
+    /// The identifiers of the actions with each hash.
+    identifiers_by_hash: HashMap<u64, Vec<ActionId>>,
+}
+
+impl ActionInterner {
+    pub fn new() -> Self {
+        ActionInterner::default()
+    }
+
+    fn find_with_hash(&self, action: &BasicAction, hash: u64) -> Option<ActionId> {
+        self.identifiers_by_hash
+            .get(&hash)?
+            .iter()
+            .find(|identifier| are_actions_identical(&self.actions[**identifier as usize], action))
+            .copied()
+    }
+
+    pub fn intern(&mut self, action: &BasicAction) -> ActionId {
+        let hash = compute_action_hash(action);
+        if let Some(identifier) = self.find_with_hash(action, hash) {
+            return identifier;
+        }
+        let identifier = self.actions.len() as ActionId;
+        self.actions.push(action.clone());
+        self.identifiers_by_hash
+            .entry(hash)
+            .or_default()
+            .push(identifier);
+        identifier
+    }
+
+    pub fn intern_actions(&mut self, actions: &[BasicAction]) -> Vec<ActionId> {
+        actions.iter().map(|action| self.intern(action)).collect()
+    }
+
+    /// Returns the identifier of the action without interning it, or None if it was never interned.
+    pub fn find(&self, action: &BasicAction) -> Option<ActionId> {
+        self.find_with_hash(action, compute_action_hash(action))
+    }
+
+    /// Returns None if any of the actions was never interned.
+    pub fn find_actions(&self, actions: &[BasicAction]) -> Option<Vec<ActionId>> {
+        actions.iter().map(|action| self.find(action)).collect()
+    }
+
+    pub fn get_action(&self, identifier: ActionId) -> &BasicAction {
+        &self.actions[identifier as usize]
+    }
+
+    pub fn get_actions(&self, identifiers: &[ActionId]) -> Vec<BasicAction> {
+        identifiers
+            .iter()
+            .map(|identifier| self.get_action(*identifier).clone())
+            .collect()
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.actions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_records::TalonCapture;
+    use crate::action_utilities::create_insert_action;
+
+    #[test]
+    fn test_actions_with_the_same_json_get_the_same_identifier() {
+        let mut interner = ActionInterner::new();
+        let hello = interner.intern(&create_insert_action("hello"));
+        let world = interner.intern(&create_insert_action("world"));
+        assert_ne!(hello, world);
+        assert_eq!(interner.intern(&create_insert_action("hello")), hello);
+        let mut capture = TalonCapture::new("number_small", 1);
+        let plain_repeat =
+            BasicAction::new("repeat", vec![Argument::CaptureArgument(capture.clone())]);
+        capture.set_postfix(" - 1");
+        let repeat = interner.intern(&BasicAction::new(
+            "repeat",
+            vec![Argument::CaptureArgument(capture)],
+        ));
+        assert_eq!(interner.find(&plain_repeat), Some(repeat));
+        let positive_zero = BasicAction::new("wait", vec![Argument::FloatArgument(0.0)]);
+        let negative_zero = BasicAction::new("wait", vec![Argument::FloatArgument(-0.0)]);
+        assert_ne!(
+            interner.intern(&positive_zero),
+            interner.intern(&negative_zero)
+        );
+        assert_eq!(interner.find(&create_insert_action("unknown")), None);
+        assert_eq!(interner.get_size(), 5);
+        assert_eq!(interner.get_action(world), &create_insert_action("world"));
+    }
+}
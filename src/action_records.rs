@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use crate::json::{JsonValue, escape_json_string, parse_json};
+use crate::record_diagnostics::{DiagnosticKind, ParseDiagnostics};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{self, BufRead};
 
+/// Captures with this name refer to a Talon list generated along with the recommendations.
+pub const GENERATED_LIST_CAPTURE_NAME: &str = "user.generated_list";
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TalonCapture {
     name: String,
@@ -18,6 +23,18 @@ impl TalonCapture {
         }
     }
 
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_instance(&self) -> i32 {
+        self.instance
+    }
+
+    pub fn get_postfix(&self) -> &str {
+        &self.postfix
+    }
+
     pub fn set_postfix(&mut self, postfix: &str) {
         self.postfix = String::from(postfix);
     }
@@ -26,14 +43,24 @@ impl TalonCapture {
         format!("{}_{}{}", self.name, self.instance, self.postfix)
     }
 
+    pub fn is_generated_list(&self) -> bool {
+        self.name == GENERATED_LIST_CAPTURE_NAME
+    }
+
+    /// Lists are referred to with braces in the spoken form of a command instead of angle brackets.
     pub fn compute_command_component(&self) -> String {
-        format!("<{}_{}>", self.name, self.instance)
+        if self.is_generated_list() {
+            format!("{{{}_{}}}", self.name, self.instance)
+        } else {
+            format!("<{}_{}>", self.name, self.instance)
+        }
     }
 
     pub fn to_json(&self) -> String {
         format!(
-            "{{\"name\": \"{}\", \"instance\": {}}}",
-            self.name, self.instance
+            "{{\"name\": {}, \"instance\": {}}}",
+            escape_json_string(&self.name),
+            self.instance
         )
     }
 
@@ -53,6 +80,34 @@ pub enum Argument {
     BoolArgument(bool),
     FloatArgument(f64),
     CaptureArgument(TalonCapture),
+    ListArgument(Vec<Argument>),
+    /// Dictionary keys are kept sorted so that dictionaries with the same contents have the same representation.
+    DictArgument(BTreeMap<String, Argument>),
+    NoneArgument,
+}
+
+impl Argument {
+    /// Talon script has no syntax for list, dictionary, or None values.
+    pub fn is_representable_in_talon_script(&self) -> bool {
+        !matches!(
+            self,
+            Argument::ListArgument(_) | Argument::DictArgument(_) | Argument::NoneArgument
+        )
+    }
+
+    /// JSON has no syntax for infinite or NaN numbers.
+    pub fn is_representable_in_json(&self) -> bool {
+        match self {
+            Argument::FloatArgument(value) => value.is_finite(),
+            Argument::ListArgument(elements) => {
+                elements.iter().all(Argument::is_representable_in_json)
+            }
+            Argument::DictArgument(entries) => entries
+                .iter()
+                .all(|(_, value)| value.is_representable_in_json()),
+            _ => true,
+        }
+    }
 }
 
 impl PartialEq for Argument {
@@ -63,6 +118,9 @@ impl PartialEq for Argument {
             (Argument::BoolArgument(a), Argument::BoolArgument(b)) => a == b,
             (Argument::FloatArgument(a), Argument::FloatArgument(b)) => a == b,
             (Argument::CaptureArgument(a), Argument::CaptureArgument(b)) => a == b,
+            (Argument::ListArgument(a), Argument::ListArgument(b)) => a == b,
+            (Argument::DictArgument(a), Argument::DictArgument(b)) => a == b,
+            (Argument::NoneArgument, Argument::NoneArgument) => true,
             _ => false,
         }
     }
@@ -90,6 +148,12 @@ impl BasicAction {
         &self.arguments
     }
 
+    pub fn is_representable_in_talon_script(&self) -> bool {
+        self.arguments
+            .iter()
+            .all(|argument| argument.is_representable_in_talon_script())
+    }
+
     pub fn compute_talon_script(&self) -> String {
         let arguments_text = self
             .compute_arguments_converted_to_talon_script_string()
@@ -118,18 +182,73 @@ impl BasicAction {
             Argument::BoolArgument(arg) => arg.to_string(),
             Argument::FloatArgument(arg) => arg.to_string(),
             Argument::CaptureArgument(arg) => arg.compute_string_representation(),
+            Argument::ListArgument(elements) => {
+                let elements_text: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.compute_string_argument(element))
+                    .collect();
+                format!("[{}]", elements_text.join(", "))
+            }
+            Argument::DictArgument(entries) => {
+                let entries_text: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let key_argument = Argument::StringArgument(key.clone());
+                        format!(
+                            "{}: {}",
+                            self.compute_string_argument(&key_argument),
+                            self.compute_string_argument(value)
+                        )
+                    })
+                    .collect();
+                format!("{{{}}}", entries_text.join(", "))
+            }
+            Argument::NoneArgument => String::from("None"),
         }
     }
 
     pub fn compute_argument_json(&self, argument: &Argument) -> String {
         match argument {
+            Argument::StringArgument(arg) => escape_json_string(arg),
             Argument::CaptureArgument(arg) => arg.to_json(),
+            Argument::ListArgument(elements) => {
+                let elements_json: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.compute_argument_json(element))
+                    .collect();
+                format!("[{}]", elements_json.join(", "))
+            }
+            Argument::DictArgument(entries) => {
+                let entries_json: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{}: {}",
+                            escape_json_string(key),
+                            self.compute_argument_json(value)
+                        )
+                    })
+                    .collect();
+                format!("{{{}}}", entries_json.join(", "))
+            }
+            Argument::NoneArgument => String::from("null"),
+            // The debug format always includes a decimal point or exponent, so the float is not loaded back as an integer.
+            Argument::FloatArgument(arg) => format!("{:?}", arg),
             other => self.compute_string_argument(other),
         }
     }
 
+    pub fn is_representable_in_json(&self) -> bool {
+        self.arguments
+            .iter()
+            .all(Argument::is_representable_in_json)
+    }
+
     pub fn to_json(&self) -> String {
-        let mut result = format!("{{\"name\": \"{}\", \"arguments\": [", self.name);
+        let mut result = format!(
+            "{{\"name\": {}, \"arguments\": [",
+            escape_json_string(&self.name)
+        );
         let mut pushed_first = false;
         for argument in &self.arguments {
             if pushed_first {
@@ -151,11 +270,70 @@ impl BasicAction {
     }
 }
 
-#[derive(Clone)]
+/// Where a command was spoken. Parts that the record does not include are left empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CommandContext {
+    pub application: Option<String>,
+    pub window_title: Option<String>,
+    /// Sorted without duplicates.
+    pub modes: Vec<String>,
+    /// Sorted without duplicates.
+    pub tags: Vec<String>,
+}
+
+fn compute_shared_value(first: &Option<String>, second: &Option<String>) -> Option<String> {
+    if first == second { first.clone() } else { None }
+}
+
+fn compute_shared_names(first: &[String], second: &[String]) -> Vec<String> {
+    first
+        .iter()
+        .filter(|name| second.contains(name))
+        .cloned()
+        .collect()
+}
+
+impl CommandContext {
+    pub fn is_empty(&self) -> bool {
+        self.application.is_none()
+            && self.window_title.is_none()
+            && self.modes.is_empty()
+            && self.tags.is_empty()
+    }
+
+    /// Keeps only the parts of the context shared with the other context, which is what a command spanning both would need.
+    pub fn keep_shared_context(&mut self, other: &CommandContext) {
+        self.application = compute_shared_value(&self.application, &other.application);
+        self.window_title = compute_shared_value(&self.window_title, &other.window_title);
+        self.modes = compute_shared_names(&self.modes, &other.modes);
+        self.tags = compute_shared_names(&self.tags, &other.tags);
+    }
+
+    pub fn compute_description(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(application) = &self.application {
+            parts.push(format!("application {}", application));
+        }
+        if let Some(window_title) = &self.window_title {
+            parts.push(format!("title {}", window_title));
+        }
+        if !self.modes.is_empty() {
+            parts.push(format!("modes {}", self.modes.join(" ")));
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("tags {}", self.tags.join(" ")));
+        }
+        parts.join(", ")
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Command {
     name: String,
     actions: Vec<BasicAction>,
     seconds_since_last_action: Option<u32>,
+    timestamp: Option<u64>,
+    context: CommandContext,
 }
 
 impl Command {
@@ -168,9 +346,19 @@ impl Command {
             name: String::from(name),
             actions,
             seconds_since_last_action: seconds_since_last_action,
+            timestamp: None,
+            context: CommandContext::default(),
         }
     }
 
+    /// Creates a command with the same timing and context but a different name and actions.
+    pub fn create_modified_copy(&self, name: &str, actions: Vec<BasicAction>) -> Command {
+        let mut command = Command::new(name, actions, self.seconds_since_last_action);
+        command.timestamp = self.timestamp;
+        command.context = self.context.clone();
+        command
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -183,6 +371,23 @@ impl Command {
         self.seconds_since_last_action
     }
 
+    /// The unix time in seconds when the command was spoken, if the record includes it.
+    pub fn get_timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: Option<u64>) {
+        self.timestamp = timestamp;
+    }
+
+    pub fn get_context(&self) -> &CommandContext {
+        &self.context
+    }
+
+    pub fn set_context(&mut self, context: CommandContext) {
+        self.context = context;
+    }
+
     pub fn to_string(&self) -> String {
         let actions_text: Vec<String> =
             self.actions.iter().map(|action| action.to_json()).collect();
@@ -264,266 +469,224 @@ impl CommandChain {
         self.chain_size
     }
 
+    /// The chain keeps only the context shared by all of its commands.
     pub fn append_command(&mut self, command: Command) {
+        if self.chain_size == 0 {
+            self.command.set_context(command.get_context().clone());
+        } else {
+            self.command
+                .context
+                .keep_shared_context(command.get_context());
+        }
         self.command.append(&command);
         self.chain_size += 1;
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub enum Entry {
-    RecordingStart,
+    /// Starts a recording session, with the unix time in seconds it started at if known.
+    RecordingStart(Option<u64>),
     Command(Command),
 }
 
-const COMMAND_NAME_PREFIX: &str = "Command: ";
-const RECORDING_START_MESSAGE: &str = "START";
-const TIME_DIFFERENCE_PREFIX: &str = "T";
-
-fn parse_basic_action_json_argument_element(
-    text: &str,
-    is_string: bool,
-) -> Result<Argument, String> {
-    if is_string {
-        return Ok(Argument::StringArgument(String::from(text)));
-    }
-    let trimmed_text = text.trim();
-    if let Ok(i32) = trimmed_text.parse::<i32>() {
-        Ok(Argument::IntArgument(i32))
-    } else if let Ok(f64) = trimmed_text.parse::<f64>() {
-        Ok(Argument::FloatArgument(f64))
-    } else if trimmed_text == "true" {
-        Ok(Argument::BoolArgument(true))
-    } else if trimmed_text == "false" {
-        Ok(Argument::BoolArgument(false))
+pub const COMMAND_NAME_PREFIX: &str = "Command: ";
+pub const RECORDING_START_MESSAGE: &str = "START";
+pub const TIME_DIFFERENCE_PREFIX: &str = "T";
+pub const ABSOLUTE_TIME_PREFIX: &str = "@";
+pub const APPLICATION_PREFIX: &str = "App:";
+pub const WINDOW_TITLE_PREFIX: &str = "Title:";
+pub const MODES_PREFIX: &str = "Modes:";
+pub const TAGS_PREFIX: &str = "Tags:";
+const CONTEXT_PREFIXES: [&str; 4] = [
+    APPLICATION_PREFIX,
+    WINDOW_TITLE_PREFIX,
+    MODES_PREFIX,
+    TAGS_PREFIX,
+];
+
+fn convert_json_number_to_argument(text: &str) -> Result<Argument, String> {
+    if let Ok(integer) = text.parse::<i32>() {
+        Ok(Argument::IntArgument(integer))
+    } else if let Ok(float) = text.parse::<f64>() {
+        // JSON cannot express infinite numbers, so a float too large to represent could not be written back out.
+        if float.is_finite() {
+            Ok(Argument::FloatArgument(float))
+        } else {
+            Err(format!("Number too large: {}", text))
+        }
     } else {
-        let message = format!(
-            "Invalid JSON element: {}, is string: {}",
-            trimmed_text, is_string
-        );
-        Err(String::from(message))
+        Err(format!("Invalid number: {}", text))
     }
 }
 
-#[derive(Debug)]
-enum JsonElement {
-    Argument(Argument),
-    String(String),
-    Container(JsonContainer),
+fn find_json_field<'a>(fields: &'a [(String, JsonValue)], key: &str) -> Option<&'a JsonValue> {
+    fields
+        .iter()
+        .find(|(field_key, _)| field_key == key)
+        .map(|(_, value)| value)
 }
 
-#[derive(Debug)]
-enum JsonContainer {
-    Arguments(Vec<Argument>),
-    HashMap(HashMap<String, JsonElement>),
+/// Captures are written as an object with exactly a string name and an integer instance,
+/// so any other object, even one with name and instance keys, is a dictionary argument.
+fn is_json_capture(fields: &[(String, JsonValue)]) -> bool {
+    fields.len() == 2
+        && matches!(find_json_field(fields, "name"), Some(JsonValue::String(_)))
+        && matches!(
+            find_json_field(fields, "instance"),
+            Some(JsonValue::Number(text)) if text.parse::<i32>().is_ok()
+        )
 }
 
-fn add_current_item(
-    arguments: &mut Vec<Argument>,
-    name: &mut String,
-    key: &mut String,
-    value_text: &mut String,
-    is_current_value_string: &mut bool,
-    is_inside_list: bool,
-    capture_name: &mut String,
-    capture_instance: &mut i32,
-    number_of_unclosed_braces: &mut i32,
-) -> Result<(), String> {
-    if value_text.is_empty() {
-        let message = format!(
-            "called add_current_item with key: |{}|, value_text: |{}|, is_current_value_string: {}\n",
-            key, value_text, is_current_value_string
-        );
-        return Err(String::from(message));
+fn convert_json_to_capture(fields: &[(String, JsonValue)]) -> Result<Argument, String> {
+    let name = match find_json_field(fields, "name") {
+        Some(JsonValue::String(name)) => name,
+        _ => return Err(String::from("Capture name must be a string")),
+    };
+    let instance = match find_json_field(fields, "instance") {
+        Some(JsonValue::Number(text)) => match text.parse::<i32>() {
+            Ok(instance) => instance,
+            Err(_) => return Err(format!("Invalid instance value: {}", text)),
+        },
+        _ => return Err(String::from("Capture instance must be a number")),
+    };
+    if instance < 0 {
+        return Err(String::from("Instance value cannot be negative"));
+    }
+    Ok(Argument::CaptureArgument(TalonCapture::new(name, instance)))
+}
+
+fn convert_json_to_argument(value: &JsonValue) -> Result<Argument, String> {
+    match value {
+        JsonValue::String(text) => Ok(Argument::StringArgument(text.clone())),
+        JsonValue::Number(text) => convert_json_number_to_argument(text),
+        JsonValue::Bool(value) => Ok(Argument::BoolArgument(*value)),
+        JsonValue::Object(fields) if is_json_capture(fields) => convert_json_to_capture(fields),
+        JsonValue::Object(fields) => convert_json_to_dictionary(fields),
+        JsonValue::Array(values) => Ok(Argument::ListArgument(
+            values
+                .iter()
+                .map(convert_json_to_argument)
+                .collect::<Result<Vec<Argument>, String>>()?,
+        )),
+        JsonValue::Null => Ok(Argument::NoneArgument),
     }
+}
 
-    if key == "name" {
-        if *number_of_unclosed_braces == 2 {
-            *capture_name = value_text.clone();
-        } else {
-            if !name.is_empty() {
-                return Err(String::from("JSON string has multiple name fields"));
-            }
-            *name = value_text.clone();
+fn convert_json_to_dictionary(fields: &[(String, JsonValue)]) -> Result<Argument, String> {
+    let mut entries = BTreeMap::new();
+    for (key, value) in fields {
+        if entries
+            .insert(key.clone(), convert_json_to_argument(value)?)
+            .is_some()
+        {
+            return Err(format!("Dictionary argument has multiple {} keys", key));
         }
-        key.clear();
-    } else if key == "instance" {
-        if *capture_instance != -1 {
-            return Err(String::from("JSON string has multiple instance fields"));
-        }
-        if let Ok(instance) = value_text.parse::<i32>() {
-            *capture_instance = instance;
-        } else {
-            return Err(format!("Invalid instance value: {}", value_text));
-        }
-        if *capture_instance < 0 {
-            return Err(String::from("Instance value cannot be negative"));
-        }
-        key.clear();
-    } else if is_inside_list {
-        let argument =
-            parse_basic_action_json_argument_element(value_text, *is_current_value_string)?;
-        arguments.push(argument);
-    } else {
-        return Err(format!("JSON string has a key '{}' without a list", key));
     }
+    Ok(Argument::DictArgument(entries))
+}
 
-    value_text.clear();
-    *is_current_value_string = false;
-    Ok(())
+fn find_unique_json_field<'a>(
+    fields: &'a [(String, JsonValue)],
+    key: &str,
+) -> Result<Option<&'a JsonValue>, String> {
+    let mut matching_values = fields
+        .iter()
+        .filter(|(field_key, _)| field_key == key)
+        .map(|(_, value)| value);
+    let value = matching_values.next();
+    if matching_values.next().is_some() {
+        return Err(format!("JSON string has multiple {} fields", key));
+    }
+    Ok(value)
 }
 
 pub fn load_basic_action_from_json(json: &str) -> Result<BasicAction, String> {
-    let text = json.trim();
-    let mut name = String::new();
-    let mut arguments: Vec<Argument> = Vec::new();
-    let mut key = String::new();
-    let mut is_inside_string = false;
-    let mut is_inside_list = false;
-    let mut is_current_value_string = false;
-    let mut current_text = String::new();
-    let mut escape_next_character = false;
-    let mut string_boundary = '"';
-    let mut unclosed_opening_braces = 0;
-    let mut capture_name = String::new();
-    let mut capture_instance = -1;
-    for char in text.chars() {
-        if is_inside_string {
-            if char == '\\' {
-                if escape_next_character {
-                    current_text.push(char);
-                    escape_next_character = false;
-                } else {
-                    escape_next_character = true;
-                }
-            } else if char == string_boundary {
-                if escape_next_character {
-                    current_text.push(char);
-                    escape_next_character = false;
-                } else {
-                    is_inside_string = false;
-                }
-            } else {
-                current_text.push(char);
-            }
-        } else if char == '{' {
-            unclosed_opening_braces += 1;
-        } else if char == '[' {
-            if is_inside_list {
-                return Err(String::from(
-                    "JSON string has nested lists, which is not permitted",
-                ));
-            } else if key != "arguments" {
-                return Err(format!(
-                    "JSON string has a list without a key 'arguments', found: {}",
-                    key
-                ));
-            }
-            is_inside_list = true;
-            if key.is_empty() {
-                return Err(String::from("List encountered without a key"));
-            }
-            if unclosed_opening_braces != 1 {
-                return Err(String::from("List encountered without containing map"));
-            }
-        } else if char == '}' {
-            if unclosed_opening_braces == 0 {
-                return Err(String::from("JSON string has extraneous closing brace"));
-            }
-            unclosed_opening_braces -= 1;
-            if !key.is_empty() || !current_text.is_empty() || is_current_value_string {
-                add_current_item(
-                    &mut arguments,
-                    &mut name,
-                    &mut key,
-                    &mut current_text,
-                    &mut is_current_value_string,
-                    is_inside_list,
-                    &mut capture_name,
-                    &mut capture_instance,
-                    &mut unclosed_opening_braces,
-                )?;
-            }
+    let fields = match parse_json(json.trim())? {
+        JsonValue::Object(fields) => fields,
+        other => {
+            return Err(format!(
+                "Action must be a dictionary but found a {}",
+                other.compute_type_name()
+            ));
+        }
+    };
+    let name = match find_unique_json_field(&fields, "name")? {
+        Some(JsonValue::String(name)) => name,
+        Some(other) => {
+            return Err(format!(
+                "Action name must be a string but found a {}",
+                other.compute_type_name()
+            ));
+        }
+        None => return Err(String::from("Action has no name")),
+    };
+    let arguments = match find_unique_json_field(&fields, "arguments")? {
+        Some(JsonValue::Array(values)) => values
+            .iter()
+            .map(convert_json_to_argument)
+            .collect::<Result<Vec<Argument>, String>>()?,
+        Some(other) => {
+            return Err(format!(
+                "Action arguments must be a list but found a {}",
+                other.compute_type_name()
+            ));
+        }
+        None => Vec::new(),
+    };
+    Ok(BasicAction::new(name, arguments))
+}
 
-            if is_inside_list && unclosed_opening_braces == 1 {
-                arguments.push(Argument::CaptureArgument(TalonCapture::new(
-                    &capture_name,
-                    capture_instance,
-                )));
-                capture_name.clear();
-                capture_instance = -1;
+/// Splits text made of JSON objects written one after another into the text of each object.
+fn split_concatenated_json_objects(text: &str) -> Result<Vec<&str>, String> {
+    let mut objects = Vec::new();
+    let mut depth = 0;
+    let mut object_start = 0;
+    let mut is_in_string = false;
+    let mut is_escaped = false;
+    for (index, character) in text.char_indices() {
+        if is_in_string {
+            if is_escaped {
+                is_escaped = false;
+            } else if character == '\\' {
+                is_escaped = true;
+            } else if character == '"' {
+                is_in_string = false;
             }
-        } else if char == ']' {
-            if !is_inside_list {
-                return Err(String::from(
-                    "JSON string has a closing bracket without an opening bracket",
-                ));
+            continue;
+        }
+        match character {
+            '"' => is_in_string = true,
+            '{' | '[' => {
+                if depth == 0 {
+                    object_start = index;
+                }
+                depth += 1;
             }
-
-            if !current_text.is_empty() {
-                add_current_item(
-                    &mut arguments,
-                    &mut name,
-                    &mut key,
-                    &mut current_text,
-                    &mut is_current_value_string,
-                    is_inside_list,
-                    &mut capture_name,
-                    &mut capture_instance,
-                    &mut unclosed_opening_braces,
-                )?;
+            '}' | ']' => {
+                if depth == 0 {
+                    return Err(format!("Unexpected {} at position {}", character, index));
+                }
+                depth -= 1;
+                if depth == 0 {
+                    objects.push(&text[object_start..=index]);
+                }
             }
+            _ => {}
+        }
+    }
+    if depth != 0 || is_in_string {
+        return Err(String::from("The last action is incomplete"));
+    }
+    Ok(objects)
+}
 
-            is_inside_list = false;
-            key.clear();
-        } else if char == ':' {
-            if !key.is_empty() && !is_inside_list {
-                return Err(format!(
-                    "JSON string has a colon with a predefined key: {}",
-                    key
-                ));
-            }
-            if unclosed_opening_braces == 0 {
-                return Err(String::from(
-                    "JSON string has a colon without an opening brace",
-                ));
-            }
-            key = String::from(current_text.clone());
-            current_text.clear();
-            is_current_value_string = false;
-        } else if char == ',' {
-            add_current_item(
-                &mut arguments,
-                &mut name,
-                &mut key,
-                &mut current_text,
-                &mut is_current_value_string,
-                is_inside_list,
-                &mut capture_name,
-                &mut capture_instance,
-                &mut unclosed_opening_braces,
-            )?;
-        } else if char == '"' || char == '\'' {
-            is_inside_string = true;
-            string_boundary = char;
-            is_current_value_string = true;
-        } else if is_inside_list && (!current_text.is_empty() || char != ' ') {
-            current_text.push(char);
-        }
-    }
-
-    if is_inside_string {
-        return Err(String::from("JSON string ends with an unclosed string"));
-    } else if unclosed_opening_braces > 0 {
-        return Err(String::from("JSON string ends with unclosed braces"));
-    } else if is_inside_list {
-        return Err(String::from("JSON string ends with an unclosed list"));
-    } else if !key.is_empty() || !current_text.is_empty() || is_current_value_string {
-        return Err(String::from(
-            "JSON string ends with an incomplete key-value pair",
-        ));
-    }
-    Ok(BasicAction::new(&name, arguments))
+/// Loads the actions from the JSON of each action written one after another, which is how sequences of actions are saved.
+pub fn load_basic_actions_from_concatenated_json(json: &str) -> Result<Vec<BasicAction>, String> {
+    split_concatenated_json_objects(json)?
+        .into_iter()
+        .map(load_basic_action_from_json)
+        .collect()
 }
 
 fn compute_command_name_without_prefix(name: &str) -> Result<String, String> {
@@ -561,6 +724,38 @@ fn compute_seconds_since_last_action(time_record: &str) -> Result<u32, String> {
     }
 }
 
+fn compute_absolute_time(time_record: &str) -> Result<u64, String> {
+    match time_record.strip_prefix(ABSOLUTE_TIME_PREFIX) {
+        Some(time_text) => match time_text.parse::<u64>() {
+            Ok(seconds) => Ok(seconds),
+            Err(_) => Err(format!("Invalid absolute time format: {}", time_text)),
+        },
+        None => Err(format!(
+            "Absolute time record does not start with '{}': {}",
+            ABSOLUTE_TIME_PREFIX, time_record
+        )),
+    }
+}
+
+/// Context lines must be recognized before time differences because Title: and Tags: start with the time difference prefix.
+fn is_line_context(line: &str) -> bool {
+    CONTEXT_PREFIXES
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Splits a list of mode or tag names into sorted names without duplicates.
+fn compute_context_names(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = text.split_whitespace().map(String::from).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn is_line_absolute_time(line: &str) -> bool {
+    line.starts_with(ABSOLUTE_TIME_PREFIX)
+}
+
 fn is_line_time_difference(line: &str) -> bool {
     line.starts_with(TIME_DIFFERENCE_PREFIX)
 }
@@ -581,25 +776,58 @@ fn is_line_action(line: &str) -> bool {
     line.starts_with("{")
 }
 
+fn compute_diagnostic_kind(line: &str) -> DiagnosticKind {
+    if is_line_action(line) {
+        DiagnosticKind::InvalidAction
+    } else if is_line_command_start(line) {
+        DiagnosticKind::InvalidCommandName
+    } else if is_line_context(line) {
+        DiagnosticKind::InvalidContext
+    } else if is_line_time_difference(line) {
+        DiagnosticKind::InvalidTimeDifference
+    } else if is_line_absolute_time(line) {
+        DiagnosticKind::InvalidAbsoluteTime
+    } else {
+        DiagnosticKind::InvalidLine
+    }
+}
+
+/// Strict parsing stops at the first problem while lenient parsing skips the command containing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseMode {
+    Strict,
+    Lenient,
+}
+
 struct RecordParser<'a> {
     record: &'a mut Vec<Entry>,
+    mode: ParseMode,
+    diagnostics: ParseDiagnostics,
+    is_skipping_command: bool,
     current_command_name: String,
     current_command_actions: Vec<BasicAction>,
     seconds_since_last_action: Option<u32>,
     seconds_since_last_action_for_next_command: Option<u32>,
     time_information_found_after_command: bool,
+    current_command_timestamp: Option<u64>,
+    current_command_context: CommandContext,
     line_number: usize,
 }
 
 impl<'a> RecordParser<'a> {
-    pub fn new(record: &'a mut Vec<Entry>) -> Self {
+    pub fn new(record: &'a mut Vec<Entry>, mode: ParseMode) -> Self {
         RecordParser {
             record: record,
+            mode,
+            diagnostics: ParseDiagnostics::new(),
+            is_skipping_command: false,
             current_command_name: String::new(),
             current_command_actions: Vec::new(),
             seconds_since_last_action: None,
             seconds_since_last_action_for_next_command: None,
             time_information_found_after_command: false,
+            current_command_timestamp: None,
+            current_command_context: CommandContext::default(),
             line_number: 0,
         }
     }
@@ -624,11 +852,13 @@ impl<'a> RecordParser<'a> {
         }
 
         let seconds_since_last_action = self.compute_seconds_since_last_command();
-        let command = Command::new(
+        let mut command = Command::new(
             &self.current_command_name,
             self.current_command_actions.clone(),
             seconds_since_last_action,
         );
+        command.set_timestamp(self.current_command_timestamp);
+        command.set_context(self.current_command_context.clone());
         self.record.push(Entry::Command(command));
         Ok(())
     }
@@ -664,9 +894,43 @@ impl<'a> RecordParser<'a> {
         Ok(())
     }
 
+    /// An absolute time before the first command of a session is the time the session started.
+    fn process_absolute_time(&mut self, line: &str) -> Result<(), String> {
+        let timestamp = compute_absolute_time(line)?;
+        if !self.current_command_name.is_empty() {
+            self.current_command_timestamp = Some(timestamp);
+        } else if let Some(Entry::RecordingStart(start_timestamp)) = self.record.last_mut() {
+            *start_timestamp = Some(timestamp);
+        } else {
+            self.record.push(Entry::RecordingStart(Some(timestamp)));
+        }
+        Ok(())
+    }
+
+    fn process_context(&mut self, line: &str) -> Result<(), String> {
+        if self.current_command_name.is_empty() {
+            return Err(format!("Context must follow a command name: {}", line));
+        }
+        let context = &mut self.current_command_context;
+        if let Some(application) = line.strip_prefix(APPLICATION_PREFIX) {
+            let application = application.trim();
+            if application.is_empty() {
+                return Err(String::from("Application name is empty"));
+            }
+            context.application = Some(application.to_string());
+        } else if let Some(window_title) = line.strip_prefix(WINDOW_TITLE_PREFIX) {
+            context.window_title = Some(window_title.trim().to_string());
+        } else if let Some(modes) = line.strip_prefix(MODES_PREFIX) {
+            context.modes = compute_context_names(modes);
+        } else if let Some(tags) = line.strip_prefix(TAGS_PREFIX) {
+            context.tags = compute_context_names(tags);
+        }
+        Ok(())
+    }
+
     fn process_recording_start(&mut self) -> Result<(), String> {
         self.add_current_command_if_available()?;
-        self.record.push(Entry::RecordingStart);
+        self.record.push(Entry::RecordingStart(None));
         self.current_command_name.clear();
         Ok(())
     }
@@ -676,13 +940,25 @@ impl<'a> RecordParser<'a> {
         self.seconds_since_last_action = None;
         self.seconds_since_last_action_for_next_command = None;
         self.time_information_found_after_command = false;
+        self.current_command_timestamp = None;
+        self.current_command_context = CommandContext::default();
     }
 
     fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        if self.is_skipping_command {
+            if !is_line_command_ending(line) {
+                return Ok(());
+            }
+            self.is_skipping_command = false;
+        }
         if is_line_action(line) {
             self.add_action_based_on_line(line)?;
         } else if is_line_command_start(line) {
             self.process_command_start(line)?;
+        } else if is_line_context(line) {
+            self.process_context(line)?;
+        } else if is_line_absolute_time(line) {
+            self.process_absolute_time(line)?;
         } else if is_line_time_difference(line) {
             self.process_time_difference(line)?;
         } else if is_line_recording_start(line) {
@@ -694,18 +970,66 @@ impl<'a> RecordParser<'a> {
         Ok(())
     }
 
-    fn parse_file_lines(&mut self, file: io::BufReader<File>) -> Result<(), String> {
-        for line in file.lines().map_while(Result::ok) {
-            self.line_number += 1;
-            if let Err(message) = self.parse_line(line.trim()) {
-                return Err(format!("Error parsing line ({})\n	{}", line, message));
+    fn skip_current_command(&mut self) {
+        self.current_command_name.clear();
+        self.reset_command_information_except_name();
+        self.is_skipping_command = true;
+    }
+
+    fn decode_line(&mut self, bytes: &[u8]) -> Result<String, String> {
+        match std::str::from_utf8(bytes) {
+            Ok(line) => Ok(line.to_string()),
+            Err(e) if self.mode == ParseMode::Strict => {
+                Err(format!("Line is not valid UTF-8: {}", e))
+            }
+            Err(e) => {
+                self.diagnostics.add(
+                    self.line_number,
+                    DiagnosticKind::InvalidUtf8,
+                    &format!("Replaced invalid bytes: {}", e),
+                );
+                Ok(String::from_utf8_lossy(bytes).into_owned())
+            }
+        }
+    }
+
+    fn parse_line_based_on_mode(&mut self, line: &str) -> Result<(), String> {
+        let trimmed_line = line.trim();
+        if let Err(message) = self.parse_line(trimmed_line) {
+            match self.mode {
+                ParseMode::Strict => {
+                    return Err(format!("Error parsing line ({})\n\t{}", line, message));
+                }
+                ParseMode::Lenient => {
+                    let kind = compute_diagnostic_kind(trimmed_line);
+                    self.diagnostics.add(self.line_number, kind, &message);
+                    self.skip_current_command();
+                }
             }
         }
         Ok(())
     }
 
-    pub fn parse_file(&mut self, file: io::BufReader<File>) -> Result<(), String> {
-        if let Err(message) = self.parse_file_lines(file) {
+    fn parse_file_lines<R: BufRead>(&mut self, mut reader: R) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        loop {
+            bytes.clear();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => return Ok(()),
+                Ok(_) => self.line_number += 1,
+                Err(e) => return Err(format!("Error reading line: {}", e)),
+            }
+            let line_without_ending = bytes
+                .strip_suffix(b"\n")
+                .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                .unwrap_or(&bytes);
+            let line = self.decode_line(line_without_ending)?;
+            self.parse_line_based_on_mode(&line)?;
+        }
+    }
+
+    pub fn parse_file<R: BufRead>(&mut self, reader: R) -> Result<(), String> {
+        if let Err(message) = self.parse_file_lines(reader) {
             return Err(format!(
                 "Error parsing file at line {}: {}",
                 self.line_number, message
@@ -718,17 +1042,35 @@ impl<'a> RecordParser<'a> {
     }
 }
 
-pub fn read_file_record(file: File) -> Result<Vec<Entry>, String> {
-    let reader = io::BufReader::new(file);
+/// A parsed record along with the problems skipped while parsing it.
+pub struct ParsedRecord {
+    pub record: Vec<Entry>,
+    pub diagnostics: ParseDiagnostics,
+}
+
+/// Parses a record from any buffered source, such as a file, standard input, or an in-memory buffer.
+pub fn read_record_with_mode<R: BufRead>(
+    reader: R,
+    mode: ParseMode,
+) -> Result<ParsedRecord, String> {
     let mut record: Vec<Entry> = Vec::new();
-    let mut parser = RecordParser::new(&mut record);
+    let mut parser = RecordParser::new(&mut record, mode);
     parser.parse_file(reader)?;
-    Ok(record)
+    let diagnostics = parser.diagnostics;
+    Ok(ParsedRecord {
+        record,
+        diagnostics,
+    })
+}
+
+pub fn read_file_record(file: File, mode: ParseMode) -> Result<ParsedRecord, String> {
+    read_record_with_mode(io::BufReader::new(file), mode)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utilities::compute_names;
 
     #[test]
     fn test_basic_action_string() {
@@ -825,4 +1167,463 @@ mod tests {
         let json = r#"{"name": "mouse_scroll", "arguments": [0, 1, true]}"#;
         assert_action_matches_expected_from_string(&name, &arguments, json);
     }
+
+    fn assert_action_fails_to_load(text: &str) {
+        assert!(
+            load_basic_action_from_json(text).is_err(),
+            "Expected an error for: {}",
+            text
+        );
+    }
+
+    fn create_string_arguments(texts: &[&str]) -> Vec<Argument> {
+        texts
+            .iter()
+            .map(|text| Argument::StringArgument(String::from(*text)))
+            .collect()
+    }
+
+    // The following actions come from real histories that the original parser could not handle.
+
+    #[test]
+    fn test_insert_with_escaped_newline_and_tab() {
+        let json = r#"{"name": "insert", "arguments": ["fn main() {\n\tprintln!(\"hi\");\n}"]}"#;
+        let arguments = create_string_arguments(&["fn main() {\n\tprintln!(\"hi\");\n}"]);
+        assert_action_matches_expected_from_string("insert", &arguments, json);
+    }
+
+    #[test]
+    fn test_insert_with_unicode_escapes() {
+        let json = r#"{"name": "insert", "arguments": ["caf\u00e9 \ud83d\ude00"]}"#;
+        let arguments = create_string_arguments(&["café 😀"]);
+        assert_action_matches_expected_from_string("insert", &arguments, json);
+    }
+
+    #[test]
+    fn test_insert_with_backslashes() {
+        let json = r#"{"name": "insert", "arguments": ["C:\\Users\\me\\"]}"#;
+        let arguments = create_string_arguments(&["C:\\Users\\me\\"]);
+        assert_action_matches_expected_from_string("insert", &arguments, json);
+    }
+
+    #[test]
+    fn test_insert_with_brackets_and_braces() {
+        let json = r#"{"name": "insert", "arguments": ["[]{}: ,"]}"#;
+        let arguments = create_string_arguments(&["[]{}: ,"]);
+        assert_action_matches_expected_from_string("insert", &arguments, json);
+    }
+
+    #[test]
+    fn test_key_with_symbols() {
+        let json = r#"{"name": "key", "arguments": ["ctrl-shift-["]}"#;
+        let arguments = create_string_arguments(&["ctrl-shift-["]);
+        assert_action_matches_expected_from_string("key", &arguments, json);
+    }
+
+    #[test]
+    fn test_action_without_whitespace_and_reordered_fields() {
+        let json = r#"{"arguments":["enter"],"name":"key"}"#;
+        let arguments = create_string_arguments(&["enter"]);
+        assert_action_matches_expected_from_string("key", &arguments, json);
+    }
+
+    #[test]
+    fn test_action_without_arguments() {
+        let json = r#"{"name": "edit.undo", "arguments": []}"#;
+        assert_action_matches_expected_from_string("edit.undo", &vec![], json);
+    }
+
+    #[test]
+    fn test_negative_and_exponent_numbers() {
+        let json = r#"{"name": "mouse_scroll", "arguments": [-3, 1.5e2, -0.25]}"#;
+        let arguments = vec![
+            Argument::IntArgument(-3),
+            Argument::FloatArgument(150.0),
+            Argument::FloatArgument(-0.25),
+        ];
+        assert_action_matches_expected_from_string("mouse_scroll", &arguments, json);
+    }
+
+    #[test]
+    fn test_rejects_numbers_too_large_for_float_argument() {
+        assert_action_fails_to_load(r#"{"name": "user.sleep", "arguments": [1e999]}"#);
+        assert_action_fails_to_load(r#"{"name": "user.sleep", "arguments": [[-1e999]]}"#);
+    }
+
+    #[test]
+    fn test_integer_too_large_for_int_argument_becomes_float() {
+        let json = r#"{"name": "user.sleep", "arguments": [3000000000]}"#;
+        let arguments = vec![Argument::FloatArgument(3000000000.0)];
+        assert_action_matches_expected_from_string("user.sleep", &arguments, json);
+    }
+
+    fn create_dictionary_argument(entries: Vec<(&str, Argument)>) -> Argument {
+        Argument::DictArgument(
+            entries
+                .into_iter()
+                .map(|(key, value)| (String::from(key), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_dictionaries_that_only_look_like_captures_stay_dictionaries() {
+        let json = r#"{"name": "user.open", "arguments": [{"name": "x", "instance": "abc"}, {"name": "x", "instance": 1.5}, {"name": "x", "instance": 1, "path": "y"}]}"#;
+        let arguments = vec![
+            create_dictionary_argument(vec![
+                ("name", Argument::StringArgument(String::from("x"))),
+                ("instance", Argument::StringArgument(String::from("abc"))),
+            ]),
+            create_dictionary_argument(vec![
+                ("name", Argument::StringArgument(String::from("x"))),
+                ("instance", Argument::FloatArgument(1.5)),
+            ]),
+            create_dictionary_argument(vec![
+                ("name", Argument::StringArgument(String::from("x"))),
+                ("instance", Argument::IntArgument(1)),
+                ("path", Argument::StringArgument(String::from("y"))),
+            ]),
+        ];
+        assert_action_matches_expected_from_string("user.open", &arguments, json);
+    }
+
+    #[test]
+    fn test_nested_list_argument() {
+        let json = r#"{"name": "user.paste_all", "arguments": [["a", ["b", 2]]]}"#;
+        let arguments = vec![Argument::ListArgument(vec![
+            Argument::StringArgument(String::from("a")),
+            Argument::ListArgument(vec![
+                Argument::StringArgument(String::from("b")),
+                Argument::IntArgument(2),
+            ]),
+        ])];
+        assert_action_matches_expected_from_string("user.paste_all", &arguments, json);
+    }
+
+    #[test]
+    fn test_dictionary_argument() {
+        let json = r#"{"name": "user.settings", "arguments": [{"key": "value", "size": [1.5]}]}"#;
+        let arguments = vec![create_dictionary_argument(vec![
+            ("key", Argument::StringArgument(String::from("value"))),
+            (
+                "size",
+                Argument::ListArgument(vec![Argument::FloatArgument(1.5)]),
+            ),
+        ])];
+        assert_action_matches_expected_from_string("user.settings", &arguments, json);
+    }
+
+    #[test]
+    fn test_none_argument() {
+        let json = r#"{"name": "user.clear", "arguments": [null]}"#;
+        let arguments = vec![Argument::NoneArgument];
+        assert_action_matches_expected_from_string("user.clear", &arguments, json);
+    }
+
+    #[test]
+    fn test_dictionaries_with_reordered_keys_are_equal() {
+        let first =
+            load_basic_action_from_json(r#"{"name": "a", "arguments": [{"x": 1, "y": null}]}"#);
+        let second =
+            load_basic_action_from_json(r#"{"name": "a", "arguments": [{"y": null, "x": 1}]}"#);
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first, second);
+        assert_eq!(first.to_json(), second.to_json());
+    }
+
+    #[test]
+    fn test_list_is_not_equal_to_its_elements() {
+        let element = Argument::IntArgument(1);
+        let list = Argument::ListArgument(vec![element.clone()]);
+        assert_ne!(list, element);
+        assert_ne!(Argument::NoneArgument, Argument::ListArgument(vec![]));
+    }
+
+    #[test]
+    fn test_container_arguments_talon_script() {
+        let action = BasicAction::new(
+            "user.configure",
+            vec![
+                Argument::ListArgument(vec![
+                    Argument::StringArgument(String::from("say \"hi\"")),
+                    Argument::BoolArgument(true),
+                ]),
+                create_dictionary_argument(vec![
+                    ("b", Argument::NoneArgument),
+                    ("a", Argument::IntArgument(1)),
+                ]),
+                Argument::NoneArgument,
+            ],
+        );
+        assert_eq!(
+            action.compute_talon_script(),
+            "user.configure([\"say \\\"hi\\\"\", true], {\"a\": 1, \"b\": None}, None)"
+        );
+        assert!(!action.is_representable_in_talon_script());
+        assert!(
+            BasicAction::new("key", create_string_arguments(&["a"]))
+                .is_representable_in_talon_script()
+        );
+    }
+
+    #[test]
+    fn test_rejects_dictionary_argument_with_repeated_key() {
+        assert_action_fails_to_load(r#"{"name": "a", "arguments": [{"k": 1, "k": 2}]}"#);
+    }
+
+    #[test]
+    fn test_rejects_invalid_actions() {
+        assert_action_fails_to_load(r#"{'name': 'insert', 'arguments': ['text']}"#);
+        assert_action_fails_to_load(r#"{"name": "insert", "arguments": ["text"]"#);
+        assert_action_fails_to_load(r#"{"name": "insert", "name": "key", "arguments": []}"#);
+        assert_action_fails_to_load(r#"{"arguments": []}"#);
+        assert_action_fails_to_load(r#"{"name": "insert", "arguments": "text"}"#);
+        assert_action_fails_to_load(r#"["insert", "text"]"#);
+        assert_action_fails_to_load(
+            r#"{"name": "insert", "arguments": [{"name": "capture", "instance": -1}]}"#,
+        );
+    }
+
+    #[test]
+    fn test_action_json_loads_back_to_same_action() {
+        let action = BasicAction::new(
+            "user.\"quoted\"",
+            vec![
+                Argument::StringArgument(String::from("line\nbreak \\ \"quote\" \u{01}")),
+                Argument::IntArgument(-7),
+                Argument::BoolArgument(false),
+                Argument::FloatArgument(2.5),
+                Argument::CaptureArgument(TalonCapture::new("user.text", 2)),
+                Argument::ListArgument(vec![Argument::NoneArgument, Argument::IntArgument(3)]),
+                create_dictionary_argument(vec![("line\nkey", Argument::BoolArgument(true))]),
+            ],
+        );
+        let loaded = load_basic_action_from_json(&action.to_json()).unwrap();
+        assert_eq!(loaded, action);
+    }
+
+    #[test]
+    fn test_concatenated_action_json_loads_back_to_same_actions() {
+        let actions = vec![
+            BasicAction::new(
+                "insert",
+                vec![Argument::StringArgument(String::from("}{ \\\" ]"))],
+            ),
+            BasicAction::new(
+                "repeat",
+                vec![Argument::CaptureArgument(TalonCapture::new(
+                    "number_small",
+                    1,
+                ))],
+            ),
+            BasicAction::new("key", vec![]),
+        ];
+        let json: String = actions.iter().map(|action| action.to_json()).collect();
+        assert_eq!(
+            load_basic_actions_from_concatenated_json(&json).unwrap(),
+            actions
+        );
+        assert!(load_basic_actions_from_concatenated_json(&json[..json.len() - 1]).is_err());
+    }
+
+    fn read_record(text: &[u8]) -> Result<Vec<Entry>, String> {
+        read_record_with_mode(text, ParseMode::Strict).map(|parsed_record| parsed_record.record)
+    }
+
+    fn read_record_from_text(text: &str) -> Vec<Entry> {
+        match read_record(text.as_bytes()) {
+            Ok(record) => record,
+            Err(message) => panic!("Error parsing record:\n    {}", message),
+        }
+    }
+
+    fn get_command(entry: &Entry) -> &Command {
+        match entry {
+            Entry::Command(command) => command,
+            Entry::RecordingStart(_) => panic!("Expected a command but found a recording start"),
+        }
+    }
+
+    #[test]
+    fn test_reads_record_from_memory() {
+        let text = "START\nCommand: word hello\n{\"name\": \"insert\", \"arguments\": [\"hello\"]}\nT3\nCommand: press enter\n{\"name\": \"key\", \"arguments\": [\"enter\"]}\n";
+        let record = read_record_from_text(text);
+        assert_eq!(record.len(), 3);
+        assert!(matches!(record[0], Entry::RecordingStart(None)));
+        let first = get_command(&record[1]);
+        assert_eq!(first.get_name(), "word hello");
+        assert_eq!(first.get_seconds_since_last_action(), Some(3));
+        assert_actions_match(
+            &first.get_actions()[0],
+            "insert",
+            &vec![Argument::StringArgument(String::from("hello"))],
+        );
+        let second = get_command(&record[2]);
+        assert_eq!(second.get_name(), "press enter");
+        assert_eq!(second.get_seconds_since_last_action(), None);
+    }
+
+    #[test]
+    fn test_reads_empty_record_from_memory() {
+        let record = read_record_from_text("");
+        assert!(record.is_empty());
+    }
+
+    #[test]
+    fn test_reports_line_number_of_invalid_record_from_memory() {
+        let text = "START\nCommand: word hello\n{\"name\": \"insert\", \"arguments\": [\"hello\"\n";
+        let result = read_record(text.as_bytes());
+        match result {
+            Ok(_) => panic!("Expected an error for an unclosed action"),
+            Err(message) => assert!(message.contains("at line 3"), "{}", message),
+        }
+    }
+
+    fn read_lenient_record(text: &[u8]) -> ParsedRecord {
+        match read_record_with_mode(text, ParseMode::Lenient) {
+            Ok(parsed_record) => parsed_record,
+            Err(message) => panic!("Error parsing record leniently:\n    {}", message),
+        }
+    }
+
+    const RECORD_WITH_BROKEN_ACTION: &str = "START\nCommand: first\n{\"name\": \"key\", \"arguments\": [\"a\"]}\nCommand: broken\n{\"name\": \"insert\", \"arguments\": [\"unclosed\"\n{\"name\": \"key\", \"arguments\": [\"b\"]}\nT4\nCommand: last\n{\"name\": \"key\", \"arguments\": [\"c\"]}\n";
+
+    #[test]
+    fn test_lenient_parsing_skips_broken_command() {
+        let parsed_record = read_lenient_record(RECORD_WITH_BROKEN_ACTION.as_bytes());
+        assert_eq!(
+            compute_names(&parsed_record.record),
+            vec!["START", "first", "last"]
+        );
+        let counts = parsed_record.diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidAction), Some(&1));
+        let summary = parsed_record.diagnostics.compute_summary("memory");
+        assert!(summary.contains("Line 5 (invalid action)"), "{}", summary);
+    }
+
+    #[test]
+    fn test_strict_parsing_stops_at_broken_command() {
+        let result = read_record(RECORD_WITH_BROKEN_ACTION.as_bytes());
+        match result {
+            Ok(_) => panic!("Expected strict parsing to fail"),
+            Err(message) => assert!(message.contains("at line 5"), "{}", message),
+        }
+    }
+
+    #[test]
+    fn test_lenient_parsing_reports_invalid_time_difference() {
+        let text = "Command: timed\n{\"name\": \"key\", \"arguments\": [\"b\"]}\nTsoon\nCommand: kept\n{\"name\": \"key\", \"arguments\": [\"c\"]}\n";
+        let parsed_record = read_lenient_record(text.as_bytes());
+        assert_eq!(compute_names(&parsed_record.record), vec!["kept"]);
+        let counts = parsed_record.diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidTimeDifference), Some(&1));
+    }
+
+    fn create_record_with_invalid_utf8() -> Vec<u8> {
+        let mut text = b"Command: caf".to_vec();
+        text.push(0xff);
+        text.extend_from_slice(b"\n{\"name\": \"key\", \"arguments\": [\"a\"]}\nCommand: after\n{\"name\": \"key\", \"arguments\": [\"b\"]}\n");
+        text
+    }
+
+    #[test]
+    fn test_lenient_parsing_decodes_invalid_utf8_lossily() {
+        let parsed_record = read_lenient_record(&create_record_with_invalid_utf8());
+        assert_eq!(
+            compute_names(&parsed_record.record),
+            vec!["caf\u{fffd}", "after"]
+        );
+        let counts = parsed_record.diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidUtf8), Some(&1));
+    }
+
+    #[test]
+    fn test_strict_parsing_reports_invalid_utf8() {
+        match read_record(&create_record_with_invalid_utf8()) {
+            Ok(_) => panic!("Expected invalid UTF-8 to be reported"),
+            Err(message) => assert!(message.contains("not valid UTF-8"), "{}", message),
+        }
+    }
+
+    #[test]
+    fn test_reads_absolute_times() {
+        let text = "START\n@1700000000\nCommand: first\n{\"name\": \"key\", \"arguments\": [\"a\"]}\nT2\nCommand: second\n@1700000005\n{\"name\": \"key\", \"arguments\": [\"b\"]}\n";
+        let record = read_record_from_text(text);
+        assert!(matches!(record[0], Entry::RecordingStart(Some(1700000000))));
+        assert_eq!(get_command(&record[1]).get_timestamp(), None);
+        assert_eq!(
+            get_command(&record[1]).get_seconds_since_last_action(),
+            Some(2)
+        );
+        assert_eq!(get_command(&record[2]).get_timestamp(), Some(1700000005));
+    }
+
+    #[test]
+    fn test_absolute_time_before_any_recording_start_starts_a_session() {
+        let text = "@1700000000\nCommand: first\n{\"name\": \"key\", \"arguments\": [\"a\"]}\n";
+        let record = read_record_from_text(text);
+        assert_eq!(record.len(), 2);
+        assert!(matches!(record[0], Entry::RecordingStart(Some(1700000000))));
+    }
+
+    #[test]
+    fn test_lenient_parsing_reports_invalid_absolute_time() {
+        let text = "Command: first\n@yesterday\n{\"name\": \"key\", \"arguments\": [\"a\"]}\n";
+        let parsed_record = read_lenient_record(text.as_bytes());
+        assert!(parsed_record.record.is_empty());
+        let counts = parsed_record.diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidAbsoluteTime), Some(&1));
+    }
+
+    const RECORD_WITH_CONTEXT: &str = "START\nCommand: first\nApp: Firefox\nTitle: Tests - Inbox\nModes: command dictation\nTags: user.tabs browser\n{\"name\": \"key\", \"arguments\": [\"a\"]}\nCommand: second\nApp: Firefox\nTags: browser\nT3\n{\"name\": \"key\", \"arguments\": [\"b\"]}\n";
+
+    #[test]
+    fn test_reads_command_context() {
+        let record = read_record_from_text(RECORD_WITH_CONTEXT);
+        let first = get_command(&record[1]);
+        assert_eq!(
+            first.get_context(),
+            &CommandContext {
+                application: Some(String::from("Firefox")),
+                window_title: Some(String::from("Tests - Inbox")),
+                modes: vec![String::from("command"), String::from("dictation")],
+                tags: vec![String::from("browser"), String::from("user.tabs")],
+            }
+        );
+        assert_eq!(first.get_seconds_since_last_action(), None);
+        let second = get_command(&record[2]);
+        assert_eq!(second.get_seconds_since_last_action(), Some(3));
+        assert!(second.get_context().modes.is_empty());
+    }
+
+    #[test]
+    fn test_command_chain_keeps_shared_context() {
+        let record = read_record_from_text(RECORD_WITH_CONTEXT);
+        let mut chain = CommandChain::empty(1);
+        chain.append_command(get_command(&record[1]).clone());
+        chain.append_command(get_command(&record[2]).clone());
+        assert_eq!(
+            chain.get_command().get_context(),
+            &CommandContext {
+                application: Some(String::from("Firefox")),
+                window_title: None,
+                modes: Vec::new(),
+                tags: vec![String::from("browser")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_context_outside_command_is_invalid() {
+        let text =
+            "START\nApp: Firefox\nCommand: kept\n{\"name\": \"key\", \"arguments\": [\"a\"]}\n";
+        match read_record(text.as_bytes()) {
+            Ok(_) => panic!("Expected a context line outside a command to be reported"),
+            Err(message) => assert!(message.contains("at line 2"), "{}", message),
+        }
+        let parsed_record = read_lenient_record(text.as_bytes());
+        assert_eq!(compute_names(&parsed_record.record), vec!["START", "kept"]);
+        let counts = parsed_record.diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidContext), Some(&1));
+    }
 }
@@ -0,0 +1,535 @@
+// Defines code for saving the command counts between runs so that only the newly appended part of a history is analyzed.
+// Counts are saved as they were just before the first chain start whose chains could reach appended entries.
+// The next run resumes counting from there, which always gives the same counts as analyzing the whole record again.
+// When that is not possible, because the record no longer starts with the analyzed entries
+// or the appended entries repeat a pattern that makes longer chains worth processing before that point,
+// the checkpoint is ignored and the whole record is analyzed.
+// The record is the one analyzed after trimming it to the time range and removing corrections,
+// so a time range relative to now, like --since 30d, starts the record somewhere else every run and keeps the checkpoint from being reused.
+
+use crate::action_records::Entry;
+use crate::analysis_settings::AnalysisSettings;
+use crate::binary_encoding::{BinaryDecoder, BinaryEncoder, StableHasher, compute_stable_hash};
+use crate::data_output::compute_data_directory;
+use crate::paths::{
+    create_directory_if_nonexistent, remove_files_in_directory, write_file_atomically,
+};
+use crate::recommendation_generation::{
+    CommandCounts, CommandStatistics, compute_chain_sizes,
+    compute_first_chain_start_affected_by_appending, compute_recommendations_from_record,
+    count_command_chains, create_commands_from_counts, create_gapped_commands,
+};
+use crate::record_cache::encode_entry;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const CHECKPOINT_DIRECTORY_NAME: &str = "analysis_checkpoints";
+const CHECKPOINT_FILE_EXTENSION: &str = "bin";
+const CHECKPOINT_FILE_MAGIC: &[u8; 4] = b"CGAC";
+/// Increase this whenever the encoding or the way chains are counted changes so that old checkpoints are ignored.
+const CHECKPOINT_FORMAT_VERSION: u32 = 18;
+
+/// The settings used to analyze the record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisParameters {
+    /// The most commands to merge into a single potential command, or None to not limit it.
+    pub max_chain_size: Option<usize>,
+    pub settings: AnalysisSettings,
+}
+
+impl AnalysisParameters {
+    /// Counts can be reused as long as the settings that change how chains are counted are the same.
+    fn can_reuse_counts_from(&self, other: &AnalysisParameters) -> bool {
+        self.max_chain_size == other.max_chain_size
+            && self.settings.has_same_counting_settings(&other.settings)
+    }
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_optional_u64(self.max_chain_size.map(|size| size as u64));
+        self.settings.encode(encoder);
+    }
+
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        Ok(AnalysisParameters {
+            max_chain_size: decoder.read_optional_u64()?.map(|size| size as usize),
+            settings: AnalysisSettings::decode(decoder)?,
+        })
+    }
+}
+
+/// Describes the analysis saved in a checkpoint without the counts themselves.
+struct CheckpointHeader {
+    parameters: AnalysisParameters,
+    record_length: usize,
+    record_hash: u64,
+    next_chain_start: usize,
+    /// How many chains starting before the next chain start were counted.
+    number_of_counted_chains: usize,
+}
+
+impl CheckpointHeader {
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        for byte in CHECKPOINT_FILE_MAGIC {
+            encoder.write_u8(*byte);
+        }
+        encoder.write_u32(CHECKPOINT_FORMAT_VERSION);
+        self.parameters.encode(encoder);
+        encoder.write_u64(self.record_length as u64);
+        encoder.write_u64(self.record_hash);
+        encoder.write_u64(self.next_chain_start as u64);
+        encoder.write_u64(self.number_of_counted_chains as u64);
+    }
+
+    /// Returns None for checkpoints written by a different version of the program.
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Option<Self>, String> {
+        for expected_byte in CHECKPOINT_FILE_MAGIC {
+            if decoder.read_u8()? != *expected_byte {
+                return Err(String::from("The file is not an analysis checkpoint"));
+            }
+        }
+        if decoder.read_u32()? != CHECKPOINT_FORMAT_VERSION {
+            return Ok(None);
+        }
+        Ok(Some(CheckpointHeader {
+            parameters: AnalysisParameters::decode(decoder)?,
+            record_length: decoder.read_u64()? as usize,
+            record_hash: decoder.read_u64()?,
+            next_chain_start: decoder.read_u64()? as usize,
+            number_of_counted_chains: decoder.read_u64()? as usize,
+        }))
+    }
+}
+
+/// Hashes the first prefix_length entries and the whole record in a single pass.
+fn compute_record_hashes(record: &[Entry], prefix_length: usize) -> (u64, u64) {
+    let mut hasher = StableHasher::new();
+    let mut prefix_hash = hasher.finish();
+    for (index, entry) in record.iter().enumerate() {
+        if index == prefix_length {
+            prefix_hash = hasher.finish();
+        }
+        let mut encoder = BinaryEncoder::new();
+        encode_entry(entry, &mut encoder);
+        hasher.update(&encoder.into_bytes());
+    }
+    if prefix_length >= record.len() {
+        prefix_hash = hasher.finish();
+    }
+    (prefix_hash, hasher.finish())
+}
+
+fn count_chains_before(chain_sizes: &[usize], chain_start: usize) -> usize {
+    chain_sizes.iter().take(chain_start).sum()
+}
+
+/// Returns the counts in the checkpoint and where to resume counting if the checkpoint can be used for the record.
+/// The chain sizes are those computed for the whole record.
+fn read_checkpoint(
+    bytes: &[u8],
+    record: &[Entry],
+    chain_sizes: &[usize],
+    parameters: &AnalysisParameters,
+) -> Result<Option<(CommandCounts, usize)>, String> {
+    let mut decoder = BinaryDecoder::new(bytes);
+    let Some(header) = CheckpointHeader::decode(&mut decoder)? else {
+        println!(
+            "The analysis checkpoint is from a different version, so the record is analyzed from the start."
+        );
+        return Ok(None);
+    };
+    if !parameters.can_reuse_counts_from(&header.parameters) {
+        println!("The analysis parameters changed, so the record is analyzed from the start.");
+        return Ok(None);
+    }
+    let (prefix_hash, _) = compute_record_hashes(record, header.record_length);
+    if header.record_length > record.len() || prefix_hash != header.record_hash {
+        println!(
+            "The record does not start with the previously analyzed entries, so it is analyzed from the start."
+        );
+        return Ok(None);
+    }
+    // Appending only ever adds chains, so the same number of chains means the same chains were counted.
+    if count_chains_before(chain_sizes, header.next_chain_start) != header.number_of_counted_chains
+    {
+        println!(
+            "The new entries repeat earlier patterns that were not counted before, so the record is analyzed from the start."
+        );
+        return Ok(None);
+    }
+    let counts = CommandCounts::decode(&mut decoder)?;
+    if !decoder.is_finished() {
+        return Err(String::from("Unexpected data after the counts"));
+    }
+    println!(
+        "Resuming the analysis from a checkpoint covering {} of {} entries.",
+        header.record_length,
+        record.len()
+    );
+    Ok(Some((counts, header.next_chain_start)))
+}
+
+fn load_checkpoint(
+    checkpoint_path: &Path,
+    record: &[Entry],
+    chain_sizes: &[usize],
+    parameters: &AnalysisParameters,
+) -> Option<(CommandCounts, usize)> {
+    let bytes = fs::read(checkpoint_path).ok()?;
+    match read_checkpoint(&bytes, record, chain_sizes, parameters) {
+        Ok(checkpoint) => checkpoint,
+        Err(message) => {
+            println!(
+                "Ignoring unreadable analysis checkpoint {}: {}",
+                checkpoint_path.display(),
+                message
+            );
+            None
+        }
+    }
+}
+
+fn create_checkpoint_bytes(
+    counts: &CommandCounts,
+    parameters: &AnalysisParameters,
+    record: &[Entry],
+    chain_sizes: &[usize],
+    next_chain_start: usize,
+) -> Vec<u8> {
+    let (_, record_hash) = compute_record_hashes(record, record.len());
+    let header = CheckpointHeader {
+        parameters: parameters.clone(),
+        record_length: record.len(),
+        record_hash,
+        next_chain_start,
+        number_of_counted_chains: count_chains_before(chain_sizes, next_chain_start),
+    };
+    let mut encoder = BinaryEncoder::new();
+    header.encode(&mut encoder);
+    counts.encode(&mut encoder);
+    encoder.into_bytes()
+}
+
+fn compute_checkpoint_directory() -> io::Result<PathBuf> {
+    let mut directory = compute_data_directory()?;
+    directory.push(CHECKPOINT_DIRECTORY_NAME);
+    Ok(directory)
+}
+
+/// Histories read from different sources get separate checkpoints so that switching between them keeps both.
+fn compute_checkpoint_path(record_description: &str) -> io::Result<PathBuf> {
+    let directory = compute_checkpoint_directory()?;
+    create_directory_if_nonexistent(&directory)?;
+    Ok(directory.join(format!(
+        "{:016x}.{}",
+        compute_stable_hash(record_description.as_bytes()),
+        CHECKPOINT_FILE_EXTENSION
+    )))
+}
+
+/// Counts the command chains in the record, resuming from the checkpoint file if one matches the record and parameters.
+/// The checkpoint file is updated for the next run. Without a checkpoint file, the whole record is analyzed.
+fn count_command_chains_with_checkpoint_file(
+    record: Vec<Entry>,
+    parameters: &AnalysisParameters,
+    checkpoint_path: Option<&Path>,
+) -> CommandCounts {
+    let record = Arc::new(record);
+    let chain_break_gap_seconds = parameters.settings.chain_break_gap_seconds;
+    let chain_sizes =
+        compute_chain_sizes(&record, parameters.max_chain_size, chain_break_gap_seconds);
+    let (mut counts, first_chain_start) = checkpoint_path
+        .and_then(|path| load_checkpoint(path, &record, &chain_sizes, parameters))
+        .unwrap_or_else(|| (CommandCounts::new(), 0));
+    let next_chain_start = compute_first_chain_start_affected_by_appending(
+        &record,
+        parameters.max_chain_size,
+        chain_break_gap_seconds,
+    )
+    .max(first_chain_start);
+    count_command_chains(
+        &record,
+        first_chain_start..next_chain_start,
+        &chain_sizes,
+        &parameters.settings,
+        &mut counts,
+    );
+    let checkpoint_bytes = checkpoint_path.map(|_| {
+        create_checkpoint_bytes(&counts, parameters, &record, &chain_sizes, next_chain_start)
+    });
+    count_command_chains(
+        &record,
+        next_chain_start..record.len(),
+        &chain_sizes,
+        &parameters.settings,
+        &mut counts,
+    );
+    if let (Some(path), Some(bytes)) = (checkpoint_path, checkpoint_bytes)
+        && let Err(e) = write_file_atomically(path, &bytes)
+    {
+        println!("Could not save the analysis checkpoint: {}", e);
+    }
+    counts
+}
+
+/// Computes recommendations for the record, only analyzing the part appended since the last run on the same history when possible.
+pub fn compute_recommendations_with_checkpoint(
+    record: Vec<Entry>,
+    parameters: &AnalysisParameters,
+    record_description: &str,
+    should_use_checkpoint: bool,
+) -> Vec<CommandStatistics> {
+    if !should_use_checkpoint {
+        return compute_recommendations_from_record(
+            record,
+            parameters.max_chain_size,
+            &parameters.settings,
+        );
+    }
+    let checkpoint_path = match compute_checkpoint_path(record_description) {
+        Ok(path) => Some(path),
+        Err(e) => {
+            println!("Could not create the analysis checkpoint directory: {}", e);
+            None
+        }
+    };
+    let gapped_commands =
+        create_gapped_commands(&record, parameters.max_chain_size, &parameters.settings);
+    let counts =
+        count_command_chains_with_checkpoint_file(record, parameters, checkpoint_path.as_deref());
+    let mut commands = create_commands_from_counts(counts, &parameters.settings);
+    commands.extend(gapped_commands);
+    commands
+}
+
+/// Deletes every analysis checkpoint and returns how many were deleted.
+pub fn clear_analysis_checkpoints() -> io::Result<usize> {
+    remove_files_in_directory(&compute_checkpoint_directory()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_records::BasicAction;
+    use crate::action_utilities::create_insert_action;
+    use crate::recommendation_generation::compute_string_representation_of_actions;
+    use crate::test_utilities::create_command;
+    use std::env;
+
+    /// Commands inserting different text also call different actions so that they cannot become the same abstract command.
+    fn create_text_command(name: &str, text: &str) -> Entry {
+        let actions = vec![
+            create_insert_action(text),
+            BasicAction::new(&format!("user.{}", text.replace(' ', "_")), vec![]),
+        ];
+        create_command(name, actions)
+    }
+
+    fn create_record(number_of_repetitions: usize) -> Vec<Entry> {
+        let mut record = vec![Entry::RecordingStart(None)];
+        for repetition in 0..number_of_repetitions {
+            record.push(create_text_command("say hello there", "hello there"));
+            record.push(create_text_command("word okay", "okay"));
+            if repetition % 3 == 0 {
+                record.push(Entry::RecordingStart(None));
+            }
+            record.push(create_text_command(
+                &format!("say other {}", repetition % 4),
+                &format!("other {}", repetition % 4),
+            ));
+        }
+        record
+    }
+
+    /// Appends a session whose patterns never occur earlier, so resuming does not need longer chains before it.
+    fn create_record_with_new_session(number_of_repetitions: usize) -> Vec<Entry> {
+        let mut record = create_record(number_of_repetitions);
+        record.push(Entry::RecordingStart(None));
+        for _ in 0..2 {
+            record.push(create_text_command("say alpha", "alpha"));
+            record.push(create_text_command("say beta", "beta"));
+        }
+        record
+    }
+
+    fn compute_summary(counts: CommandCounts) -> Vec<(String, usize, u32)> {
+        let mut summary: Vec<(String, usize, u32)> =
+            create_commands_from_counts(counts, &AnalysisSettings::default())
+                .iter()
+                .map(|statistics| {
+                    (
+                        compute_string_representation_of_actions(&statistics.actions),
+                        statistics.number_of_times_used,
+                        statistics.number_of_words_saved,
+                    )
+                })
+                .collect();
+        summary.sort();
+        summary
+    }
+
+    fn create_parameters(max_chain_size: usize) -> AnalysisParameters {
+        AnalysisParameters {
+            max_chain_size: Some(max_chain_size),
+            settings: AnalysisSettings::default(),
+        }
+    }
+
+    fn read_checkpoint_for_record(
+        bytes: &[u8],
+        record: &[Entry],
+        parameters: &AnalysisParameters,
+    ) -> Result<Option<(CommandCounts, usize)>, String> {
+        let chain_sizes = compute_chain_sizes(
+            record,
+            parameters.max_chain_size,
+            parameters.settings.chain_break_gap_seconds,
+        );
+        read_checkpoint(bytes, record, &chain_sizes, parameters)
+    }
+
+    fn create_checkpoint_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "command_generator_{}_{}.bin",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_resuming_gives_same_counts_as_full_analysis() {
+        let parameters = create_parameters(4);
+        let checkpoint_path = create_checkpoint_path("resume");
+        let full_counts =
+            count_command_chains_with_checkpoint_file(create_record(12), &parameters, None);
+        let expected_summary = compute_summary(full_counts);
+        assert!(!expected_summary.is_empty());
+        for initial_repetitions in [0, 1, 5, 11, 12] {
+            let _ = fs::remove_file(&checkpoint_path);
+            count_command_chains_with_checkpoint_file(
+                create_record(initial_repetitions),
+                &parameters,
+                Some(&checkpoint_path),
+            );
+            let record = create_record(12);
+            let resumed_counts = count_command_chains_with_checkpoint_file(
+                record,
+                &parameters,
+                Some(&checkpoint_path),
+            );
+            assert_eq!(compute_summary(resumed_counts), expected_summary);
+        }
+        fs::remove_file(&checkpoint_path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_is_used_unless_new_entries_repeat_earlier_patterns() {
+        for parameters in [
+            create_parameters(4),
+            AnalysisParameters {
+                max_chain_size: None,
+                settings: AnalysisSettings::default(),
+            },
+        ] {
+            let checkpoint_path = create_checkpoint_path("patterns");
+            count_command_chains_with_checkpoint_file(
+                create_record(12),
+                &parameters,
+                Some(&checkpoint_path),
+            );
+            let bytes = fs::read(&checkpoint_path).unwrap();
+            let record = create_record_with_new_session(12);
+            assert!(
+                read_checkpoint_for_record(&bytes, &record, &parameters)
+                    .unwrap()
+                    .is_some()
+            );
+            let expected_summary = compute_summary(count_command_chains_with_checkpoint_file(
+                record.clone(),
+                &parameters,
+                None,
+            ));
+            let resumed_counts = count_command_chains_with_checkpoint_file(
+                record,
+                &parameters,
+                Some(&checkpoint_path),
+            );
+            assert_eq!(compute_summary(resumed_counts), expected_summary);
+            // The appended repetition makes chains starting in earlier sessions occur twice.
+            assert!(
+                read_checkpoint_for_record(&bytes, &create_record(13), &parameters)
+                    .unwrap()
+                    .is_none()
+            );
+            fs::remove_file(&checkpoint_path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_is_not_used_after_changes() {
+        let parameters = create_parameters(4);
+        let checkpoint_path = create_checkpoint_path("changes");
+        count_command_chains_with_checkpoint_file(
+            create_record(5),
+            &parameters,
+            Some(&checkpoint_path),
+        );
+        let bytes = fs::read(&checkpoint_path).unwrap();
+        let changed_parameters = create_parameters(5);
+        assert!(
+            read_checkpoint_for_record(
+                &bytes,
+                &create_record_with_new_session(5),
+                &changed_parameters
+            )
+            .unwrap()
+            .is_none()
+        );
+        let mut changed_parameters = create_parameters(4);
+        changed_parameters.settings.max_prose_size = 3;
+        assert!(
+            read_checkpoint_for_record(
+                &bytes,
+                &create_record_with_new_session(5),
+                &changed_parameters
+            )
+            .unwrap()
+            .is_none()
+        );
+        let mut filtering_parameters = create_parameters(4);
+        filtering_parameters.settings.minimum_times_used = 5;
+        assert!(
+            read_checkpoint_for_record(
+                &bytes,
+                &create_record_with_new_session(5),
+                &filtering_parameters
+            )
+            .unwrap()
+            .is_some()
+        );
+        let mut changed_record = create_record_with_new_session(5);
+        changed_record[1] = create_text_command("say goodbye", "goodbye");
+        assert!(
+            read_checkpoint_for_record(&bytes, &changed_record, &parameters)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            read_checkpoint_for_record(&bytes, &create_record(4), &parameters)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            read_checkpoint_for_record(
+                &bytes[..bytes.len() - 1],
+                &create_record_with_new_session(5),
+                &parameters
+            )
+            .is_err()
+        );
+        fs::remove_file(&checkpoint_path).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+// Defines the settings that control how potential commands are found, filtered, and searched through.
+// Settings are given as key = value lines in a file in the configuration directory and can be overridden from the command line.
+
+use crate::binary_encoding::{BinaryDecoder, BinaryEncoder};
+use crate::recommendation_generation::FIVE_MINUTES_IN_SECONDS;
+use std::str::FromStr;
+
+const COMMENT_PREFIX: char = '#';
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalysisSettings {
+    /// Commands spoken more than this many seconds after the previous command are never merged with the commands before them.
+    pub chain_break_gap_seconds: u32,
+    /// The most words of dictation to consider replacing with a prose capture.
+    pub max_prose_size: usize,
+    /// The most prose captures an abstract command can have, each replacing prose in a different insert.
+    pub max_prose_captures: usize,
+    pub minimum_times_used: usize,
+    pub minimum_words_saved: u32,
+    /// Commands with more actions per dictated word than this are only recommended if they are used often enough to make up for it.
+    pub maximum_actions_per_word: f32,
+    pub abstract_minimum_average_words_dictated: f32,
+    /// The number of different concrete commands an abstract command must stand in for.
+    pub abstract_minimum_instantiations: usize,
+    /// The most different texts an abstract command inserting an item from a generated list can choose from.
+    pub abstract_maximum_list_size: usize,
+    /// The most commands that can be skipped between the commands of a gapped potential command. 0 turns off gapped mining.
+    pub gapped_maximum_skipped_commands: usize,
+    /// Every command of a gapped potential command must be spoken within this many seconds of its first command.
+    pub gapped_window_seconds: u32,
+    /// Commands undone or deleted within this many seconds are removed along with their corrections. 0 keeps every command.
+    pub correction_window_seconds: u32,
+    /// Controls how much the tree search favors exploring less visited recommendations over the best ones found so far.
+    pub search_exploration_constant: f64,
+    pub search_rollouts_per_exploration: usize,
+    pub search_rollouts_per_child_expansion: usize,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        AnalysisSettings {
+            chain_break_gap_seconds: FIVE_MINUTES_IN_SECONDS,
+            max_prose_size: 10,
+            max_prose_captures: 2,
+            minimum_times_used: 2,
+            minimum_words_saved: 1,
+            maximum_actions_per_word: 2.0,
+            abstract_minimum_average_words_dictated: 2.0,
+            abstract_minimum_instantiations: 3,
+            abstract_maximum_list_size: 10,
+            gapped_maximum_skipped_commands: 0,
+            gapped_window_seconds: 60,
+            correction_window_seconds: 30,
+            search_exploration_constant: 0.000001,
+            search_rollouts_per_exploration: 10,
+            search_rollouts_per_child_expansion: 1,
+        }
+    }
+}
+
+const SETTING_NAMES: [&str; 15] = [
+    "chain_break_gap_seconds",
+    "max_prose_size",
+    "max_prose_captures",
+    "minimum_times_used",
+    "minimum_words_saved",
+    "maximum_actions_per_word",
+    "abstract_minimum_average_words_dictated",
+    "abstract_minimum_instantiations",
+    "abstract_maximum_list_size",
+    "gapped_maximum_skipped_commands",
+    "gapped_window_seconds",
+    "correction_window_seconds",
+    "search_exploration_constant",
+    "search_rollouts_per_exploration",
+    "search_rollouts_per_child_expansion",
+];
+
+fn parse_value<T: FromStr>(value: &str, description: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Expected {} but found '{}'", description, value))
+}
+
+fn parse_positive_integer(value: &str) -> Result<usize, String> {
+    match parse_value(value, "a positive integer")? {
+        0 => Err(String::from("Expected a positive integer but found 0")),
+        number => Ok(number),
+    }
+}
+
+fn parse_nonnegative_number<T: FromStr + Into<f64> + Copy>(value: &str) -> Result<T, String> {
+    let number: T = parse_value(value, "a non-negative number")?;
+    let converted_number: f64 = number.into();
+    if converted_number.is_finite() && converted_number >= 0.0 {
+        Ok(number)
+    } else {
+        Err(format!(
+            "Expected a non-negative number but found '{}'",
+            value
+        ))
+    }
+}
+
+impl AnalysisSettings {
+    /// Changes the setting with the given name, reporting unknown names and invalid values as errors.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match name.trim() {
+            "chain_break_gap_seconds" => {
+                self.chain_break_gap_seconds = parse_value(value, "a non-negative integer")?
+            }
+            "max_prose_size" => self.max_prose_size = parse_positive_integer(value)?,
+            "max_prose_captures" => self.max_prose_captures = parse_positive_integer(value)?,
+            "minimum_times_used" => {
+                self.minimum_times_used = parse_value(value, "a non-negative integer")?
+            }
+            "minimum_words_saved" => {
+                self.minimum_words_saved = parse_value(value, "a non-negative integer")?
+            }
+            "maximum_actions_per_word" => {
+                self.maximum_actions_per_word = parse_nonnegative_number(value)?
+            }
+            "abstract_minimum_average_words_dictated" => {
+                self.abstract_minimum_average_words_dictated = parse_nonnegative_number(value)?
+            }
+            "abstract_minimum_instantiations" => {
+                self.abstract_minimum_instantiations = parse_value(value, "a non-negative integer")?
+            }
+            "abstract_maximum_list_size" => {
+                self.abstract_maximum_list_size = parse_value(value, "a non-negative integer")?
+            }
+            "gapped_maximum_skipped_commands" => {
+                self.gapped_maximum_skipped_commands = parse_value(value, "a non-negative integer")?
+            }
+            "gapped_window_seconds" => {
+                self.gapped_window_seconds = parse_value(value, "a non-negative integer")?
+            }
+            "correction_window_seconds" => {
+                self.correction_window_seconds = parse_value(value, "a non-negative integer")?
+            }
+            "search_exploration_constant" => {
+                self.search_exploration_constant = parse_nonnegative_number(value)?
+            }
+            "search_rollouts_per_exploration" => {
+                self.search_rollouts_per_exploration = parse_positive_integer(value)?
+            }
+            "search_rollouts_per_child_expansion" => {
+                self.search_rollouts_per_child_expansion = parse_positive_integer(value)?
+            }
+            unknown_name => {
+                return Err(format!(
+                    "Unknown setting '{}'. The settings are: {}",
+                    unknown_name,
+                    SETTING_NAMES.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every key = value line in the text. Empty lines and lines starting with # are ignored.
+    /// Every problem is reported with its line number instead of stopping at the first one.
+    pub fn apply_settings_text(&mut self, text: &str) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMMENT_PREFIX) {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((name, value)) => self.set(name, value),
+                None => Err(format!(
+                    "Expected a line like key = value but found '{}'",
+                    line
+                )),
+            };
+            if let Err(message) = result {
+                errors.push(format!("Line {}: {}", index + 1, message));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Applies an override given on the command line like max_prose_size=5.
+    pub fn apply_override(&mut self, setting_override: &str) -> Result<(), String> {
+        let result = match setting_override.split_once('=') {
+            Some((name, value)) => self.set(name, value),
+            None => Err(String::from("Expected an override like key=value")),
+        };
+        result.map_err(|message| format!("--set {}: {}", setting_override, message))
+    }
+
+    /// Lists every setting with its value in the format the settings file uses.
+    pub fn compute_settings_text(&self) -> String {
+        let values = [
+            self.chain_break_gap_seconds.to_string(),
+            self.max_prose_size.to_string(),
+            self.max_prose_captures.to_string(),
+            self.minimum_times_used.to_string(),
+            self.minimum_words_saved.to_string(),
+            self.maximum_actions_per_word.to_string(),
+            self.abstract_minimum_average_words_dictated.to_string(),
+            self.abstract_minimum_instantiations.to_string(),
+            self.abstract_maximum_list_size.to_string(),
+            self.gapped_maximum_skipped_commands.to_string(),
+            self.gapped_window_seconds.to_string(),
+            self.correction_window_seconds.to_string(),
+            self.search_exploration_constant.to_string(),
+            self.search_rollouts_per_exploration.to_string(),
+            self.search_rollouts_per_child_expansion.to_string(),
+        ];
+        SETTING_NAMES
+            .iter()
+            .zip(values)
+            .map(|(name, value)| format!("{} = {}\n", name, value))
+            .collect()
+    }
+
+    /// Whether counts of potential commands made with the other settings are the same as ones made with these settings.
+    /// The other settings only affect what is done with the counts or, like the gapped settings, analysis that is never saved.
+    pub fn has_same_counting_settings(&self, other: &AnalysisSettings) -> bool {
+        self.chain_break_gap_seconds == other.chain_break_gap_seconds
+            && self.max_prose_size == other.max_prose_size
+            && self.max_prose_captures == other.max_prose_captures
+    }
+
+    pub fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_u32(self.chain_break_gap_seconds);
+        encoder.write_u64(self.max_prose_size as u64);
+        encoder.write_u64(self.max_prose_captures as u64);
+        encoder.write_u64(self.minimum_times_used as u64);
+        encoder.write_u32(self.minimum_words_saved);
+        encoder.write_f64(self.maximum_actions_per_word as f64);
+        encoder.write_f64(self.abstract_minimum_average_words_dictated as f64);
+        encoder.write_u64(self.abstract_minimum_instantiations as u64);
+        encoder.write_u64(self.abstract_maximum_list_size as u64);
+        encoder.write_u64(self.gapped_maximum_skipped_commands as u64);
+        encoder.write_u32(self.gapped_window_seconds);
+        encoder.write_u32(self.correction_window_seconds);
+        encoder.write_f64(self.search_exploration_constant);
+        encoder.write_u64(self.search_rollouts_per_exploration as u64);
+        encoder.write_u64(self.search_rollouts_per_child_expansion as u64);
+    }
+
+    pub fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        Ok(AnalysisSettings {
+            chain_break_gap_seconds: decoder.read_u32()?,
+            max_prose_size: decoder.read_u64()? as usize,
+            max_prose_captures: decoder.read_u64()? as usize,
+            minimum_times_used: decoder.read_u64()? as usize,
+            minimum_words_saved: decoder.read_u32()?,
+            maximum_actions_per_word: decoder.read_f64()? as f32,
+            abstract_minimum_average_words_dictated: decoder.read_f64()? as f32,
+            abstract_minimum_instantiations: decoder.read_u64()? as usize,
+            abstract_maximum_list_size: decoder.read_u64()? as usize,
+            gapped_maximum_skipped_commands: decoder.read_u64()? as usize,
+            gapped_window_seconds: decoder.read_u32()?,
+            correction_window_seconds: decoder.read_u32()?,
+            search_exploration_constant: decoder.read_f64()?,
+            search_rollouts_per_exploration: decoder.read_u64()? as usize,
+            search_rollouts_per_child_expansion: decoder.read_u64()? as usize,
+        })
+    }
+}
+
+/// The text of a new settings file, which lists the default settings as comments.
+pub fn compute_default_settings_file_text() -> String {
+    let mut text = String::from(
+        "# Remove the # from a line and change its value to change a setting.\n# Settings can also be changed for a single run with --set key=value.\n",
+    );
+    for line in AnalysisSettings::default().compute_settings_text().lines() {
+        text.push_str(&format!("{} {}\n", COMMENT_PREFIX, line));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_text_is_applied() {
+        let mut settings = AnalysisSettings::default();
+        let text = "# A comment\n\nmax_prose_size = 4\n  chain_break_gap_seconds=60  \nmaximum_actions_per_word = 1.5\n";
+        assert_eq!(settings.apply_settings_text(text), Ok(()));
+        assert_eq!(settings.max_prose_size, 4);
+        assert_eq!(settings.chain_break_gap_seconds, 60);
+        assert_eq!(settings.maximum_actions_per_word, 1.5);
+        assert_eq!(settings.minimum_times_used, 2);
+    }
+
+    #[test]
+    fn test_problems_are_reported_with_line_numbers() {
+        let mut settings = AnalysisSettings::default();
+        let text = "max_prose_size = 4\nmaximum_chain = 3\nminimum_times_used = many\nmax_prose_size\nsearch_rollouts_per_exploration = 0\n";
+        let message = settings.apply_settings_text(text).unwrap_err();
+        let lines: Vec<&str> = message.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", message);
+        assert!(
+            lines[0].starts_with("Line 2: Unknown setting 'maximum_chain'"),
+            "{}",
+            message
+        );
+        assert!(lines[1].starts_with("Line 3: Expected"), "{}", message);
+        assert!(lines[2].starts_with("Line 4: Expected"), "{}", message);
+        assert!(lines[3].starts_with("Line 5: Expected"), "{}", message);
+    }
+
+    #[test]
+    fn test_overrides_are_applied() {
+        let mut settings = AnalysisSettings::default();
+        assert_eq!(settings.apply_override("minimum_words_saved=3"), Ok(()));
+        assert_eq!(settings.minimum_words_saved, 3);
+        assert!(settings.apply_override("minimum_words_saved").is_err());
+        assert!(
+            settings
+                .apply_override("maximum_actions_per_word=-1")
+                .unwrap_err()
+                .starts_with("--set maximum_actions_per_word=-1: ")
+        );
+    }
+
+    #[test]
+    fn test_default_settings_file_gives_default_settings() {
+        let settings = AnalysisSettings {
+            max_prose_size: 3,
+            ..AnalysisSettings::default()
+        };
+        let text = settings.compute_settings_text();
+        let mut loaded_settings = AnalysisSettings::default();
+        assert_eq!(loaded_settings.apply_settings_text(&text), Ok(()));
+        assert_eq!(loaded_settings, settings);
+        let mut default_settings = settings;
+        assert_eq!(
+            default_settings.apply_settings_text(&compute_default_settings_file_text()),
+            Ok(())
+        );
+        assert_eq!(default_settings, settings);
+    }
+}
@@ -0,0 +1,271 @@
+// Defines a compact binary format for saving data that is expensive to recompute.
+// Numbers are little endian and variable length data is preceded by its length.
+
+pub struct BinaryEncoder {
+    bytes: Vec<u8>,
+}
+
+impl BinaryEncoder {
+    pub fn new() -> Self {
+        BinaryEncoder { bytes: Vec::new() }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_length(&mut self, length: usize) {
+        self.write_u64(length as u64);
+    }
+
+    pub fn write_string(&mut self, text: &str) {
+        self.write_length(text.len());
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    pub fn write_optional_u32(&mut self, value: Option<u32>) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            self.write_u32(value);
+        }
+    }
+
+    pub fn write_optional_u64(&mut self, value: Option<u64>) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            self.write_u64(value);
+        }
+    }
+
+    pub fn write_optional_string(&mut self, text: Option<&str>) {
+        self.write_bool(text.is_some());
+        if let Some(text) = text {
+            self.write_string(text);
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values in the order they were written by a BinaryEncoder.
+/// Running out of bytes or finding invalid values is reported as an error instead of panicking,
+/// so that corrupted files can be detected and ignored.
+pub struct BinaryDecoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryDecoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        BinaryDecoder { bytes, position: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| {
+                format!(
+                    "Expected {} more bytes at position {} but only {} remain",
+                    length,
+                    self.position,
+                    self.bytes.len() - self.position
+                )
+            })?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(format!("Invalid boolean value {}", value)),
+        }
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Lengths larger than the remaining bytes are rejected so that corrupted lengths do not cause huge allocations.
+    pub fn read_length(&mut self) -> Result<usize, String> {
+        let length = self.read_u64()?;
+        let remaining = (self.bytes.len() - self.position) as u64;
+        if length > remaining {
+            return Err(format!(
+                "Length {} at position {} is larger than the {} remaining bytes",
+                length, self.position, remaining
+            ));
+        }
+        Ok(length as usize)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, String> {
+        let length = self.read_length()?;
+        let bytes = self.read_bytes(length)?;
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Ok(text.to_string()),
+            Err(e) => Err(format!("Invalid text: {}", e)),
+        }
+    }
+
+    pub fn read_optional_string(&mut self) -> Result<Option<String>, String> {
+        if self.read_bool()? {
+            Ok(Some(self.read_string()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn read_optional_u32(&mut self) -> Result<Option<u32>, String> {
+        if self.read_bool()? {
+            Ok(Some(self.read_u32()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn read_optional_u64(&mut self) -> Result<Option<u64>, String> {
+        if self.read_bool()? {
+            Ok(Some(self.read_u64()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Computes the 64 bit FNV-1a hash, which unlike the standard library hasher is the same across program versions.
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash = (self.hash ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+pub fn compute_stable_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_survive_round_trip() {
+        let mut encoder = BinaryEncoder::new();
+        encoder.write_u8(7);
+        encoder.write_bool(true);
+        encoder.write_i32(-5);
+        encoder.write_u64(u64::MAX);
+        encoder.write_f64(-1.5);
+        encoder.write_string("héllo");
+        encoder.write_optional_u32(None);
+        encoder.write_optional_u64(Some(3));
+        let bytes = encoder.into_bytes();
+        let mut decoder = BinaryDecoder::new(&bytes);
+        assert_eq!(decoder.read_u8(), Ok(7));
+        assert_eq!(decoder.read_bool(), Ok(true));
+        assert_eq!(decoder.read_i32(), Ok(-5));
+        assert_eq!(decoder.read_u64(), Ok(u64::MAX));
+        assert_eq!(decoder.read_f64(), Ok(-1.5));
+        assert_eq!(decoder.read_string(), Ok(String::from("héllo")));
+        assert_eq!(decoder.read_optional_u32(), Ok(None));
+        assert_eq!(decoder.read_optional_u64(), Ok(Some(3)));
+        assert!(decoder.is_finished());
+    }
+
+    #[test]
+    fn test_truncated_and_invalid_data_are_errors() {
+        let mut encoder = BinaryEncoder::new();
+        encoder.write_string("hello");
+        let bytes = encoder.into_bytes();
+        assert!(
+            BinaryDecoder::new(&bytes[..bytes.len() - 1])
+                .read_string()
+                .is_err()
+        );
+        assert!(BinaryDecoder::new(&[2]).read_bool().is_err());
+        assert!(
+            BinaryDecoder::new(&u64::MAX.to_le_bytes())
+                .read_length()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stable_hash_matches_fnv_1a() {
+        assert_eq!(compute_stable_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(compute_stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
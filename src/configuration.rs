@@ -1,6 +1,7 @@
 // Defines functions for accessing and managing configuration files.
 
-use crate::action_records::BasicAction;
+use crate::action_records::{BasicAction, load_basic_actions_from_concatenated_json};
+use crate::analysis_settings::{AnalysisSettings, compute_default_settings_file_text};
 use crate::paths;
 use crate::recommendation_generation::{ActionSet, compute_string_representation_of_actions};
 use std::fs;
@@ -10,6 +11,7 @@ use std::path::PathBuf;
 const CONFIGURATION_DIRECTORY_NAME: &str = "configuration";
 const ACTIONS_TO_REJECT_FILE_NAME: &str = "actions_to_reject.txt";
 const COMMANDS_TO_REJECT_FILENAME: &str = "commands_to_reject.txt";
+const ANALYSIS_SETTINGS_FILE_NAME: &str = "analysis_settings.txt";
 
 fn compute_configuration_directory() -> io::Result<PathBuf> {
     paths::compute_directory_under_current_directory(CONFIGURATION_DIRECTORY_NAME)
@@ -23,6 +25,11 @@ pub fn create_configuration_directory() -> io::Result<()> {
     // create commands to reject file
     let commands_to_reject_path = path.join(COMMANDS_TO_REJECT_FILENAME);
     paths::create_file(&commands_to_reject_path)?;
+    // create analysis settings file listing the default settings
+    let analysis_settings_path = path.join(ANALYSIS_SETTINGS_FILE_NAME);
+    if !analysis_settings_path.exists() {
+        fs::write(analysis_settings_path, compute_default_settings_file_text())?;
+    }
     Ok(())
 }
 
@@ -48,8 +55,19 @@ pub fn load_action_set(file_name: &str) -> ActionSet {
     }
 
     if let Ok(file_content) = fs::read_to_string(file_path) {
-        for line in file_content.lines() {
-            actions.insert_representation(line);
+        for (line_index, line) in file_content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match load_basic_actions_from_concatenated_json(line) {
+                Ok(line_actions) => actions.insert(&line_actions),
+                Err(message) => println!(
+                    "Ignoring line {} of {}: {}",
+                    line_index + 1,
+                    file_name,
+                    message
+                ),
+            }
         }
     } else {
         println!("Error reading {} file", file_name);
@@ -115,3 +133,22 @@ pub fn append_commands_to_reject(commands: &Vec<Vec<BasicAction>>) {
         .collect();
     append_representations(COMMANDS_TO_REJECT_FILENAME, &representations);
 }
+
+/// Loads the analysis settings file and then applies the overrides given on the command line.
+/// A missing settings file leaves the default settings.
+pub fn load_analysis_settings(overrides: &[String]) -> Result<AnalysisSettings, String> {
+    let mut settings = AnalysisSettings::default();
+    let file_path = compute_configuration_filepath(ANALYSIS_SETTINGS_FILE_NAME)
+        .map_err(|e| format!("Error loading {}: {}", ANALYSIS_SETTINGS_FILE_NAME, e))?;
+    if file_path.exists() {
+        let text = fs::read_to_string(&file_path)
+            .map_err(|e| format!("Error reading {}: {}", file_path.display(), e))?;
+        settings.apply_settings_text(&text).map_err(|message| {
+            format!("Invalid settings in {}:\n{}", file_path.display(), message)
+        })?;
+    }
+    for setting_override in overrides {
+        settings.apply_override(setting_override)?;
+    }
+    Ok(settings)
+}
@@ -1,12 +1,20 @@
+use crate::action_records::{Argument, BasicAction, GENERATED_LIST_CAPTURE_NAME, TalonCapture};
 use crate::paths;
 use crate::recommendation_generation::*;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
 
 const DATA_DIRECTORY_NAME: &str = "data";
+const GLOBAL_TALON_FILE_NAME: &str = "global.talon";
+const GENERATED_LISTS_DECLARATION_FILE_NAME: &str = "generated_lists.py";
+const GENERATED_REPEAT_BLOCKS_FILE_NAME: &str = "generated_repeat_blocks.py";
+/// Marks commands that drop the commands spoken between their commands so that reviewers know to check them.
+const GAPPED_COMMAND_NOTE: &str =
+    "Gapped: the commands spoken between the commands of this one when it was found are dropped";
 
-fn compute_data_directory() -> io::Result<PathBuf> {
+pub fn compute_data_directory() -> io::Result<PathBuf> {
     paths::compute_directory_under_current_directory(DATA_DIRECTORY_NAME)
 }
 
@@ -36,6 +44,20 @@ pub fn output_recommendations(
             "#Number of words saved: {}",
             statistics.number_of_words_saved
         )?;
+        for (context, count) in &statistics.contexts {
+            writeln!(
+                buffered_writer,
+                "#Used in context: {} ({} times)",
+                context.compute_description(),
+                count
+            )?;
+        }
+        if let Some(application) = &statistics.application {
+            writeln!(buffered_writer, "#Application: {}", application)?;
+        }
+        if statistics.is_gapped {
+            writeln!(buffered_writer, "#{}", GAPPED_COMMAND_NOTE)?;
+        }
         if let Some(instantiation_set) = &statistics.instantiation_set {
             writeln!(
                 buffered_writer,
@@ -43,7 +65,23 @@ pub fn output_recommendations(
                 instantiation_set.get_size()
             )?;
         }
+        if statistics.uses_generated_list() {
+            writeln!(
+                buffered_writer,
+                "#Generated list items: {}",
+                statistics.compute_generated_list_values().join(", ")
+            )?;
+        }
         let actions = &statistics.actions;
+        if !actions
+            .iter()
+            .all(|action| action.is_representable_in_talon_script())
+        {
+            writeln!(
+                buffered_writer,
+                "#Talon script cannot express list, dictionary, or None arguments, so these actions must be called from Python"
+            )?;
+        }
         actions.iter().for_each(|action| {
             let action_string = action.compute_talon_script();
             writeln!(buffered_writer, "{}", action_string).unwrap();
@@ -54,3 +92,708 @@ pub fn output_recommendations(
 
     Ok(())
 }
+
+/// Different applications can have names that make the same file name, so later ones are given a number to tell them apart.
+fn compute_talon_file_name(application: &Option<String>, number: usize) -> String {
+    let file_name = match application {
+        Some(application) => {
+            let name: String = application
+                .chars()
+                .map(|character| {
+                    if character.is_alphanumeric() || character == '-' || character == '.' {
+                        character
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("app_{}.talon", name)
+        }
+        None => String::from(GLOBAL_TALON_FILE_NAME),
+    };
+    if number > 1 {
+        let stem = file_name.strip_suffix(".talon").unwrap_or(&file_name);
+        format!("{}_{}.talon", stem, number)
+    } else {
+        file_name
+    }
+}
+
+/// The file for the commands of the application, which no other application shares.
+fn find_talon_file_name(
+    application: &Option<String>,
+    file_names: &mut BTreeMap<Option<String>, String>,
+) -> String {
+    if let Some(file_name) = file_names.get(application) {
+        return file_name.clone();
+    }
+    let mut number = 1;
+    let mut file_name = compute_talon_file_name(application, number);
+    while file_names
+        .values()
+        .any(|taken_name| *taken_name == file_name)
+    {
+        number += 1;
+        file_name = compute_talon_file_name(application, number);
+    }
+    file_names.insert(application.clone(), file_name.clone());
+    file_name
+}
+
+/// The names of the captures a string uses through interpolation, like number_1 in "down:{number_1}".
+fn compute_interpolated_capture_names(text: &str) -> Vec<&str> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}'))
+        .map(|(capture, _)| capture)
+        .collect()
+}
+
+/// How a capture used by the actions of a command is written in its .talon file.
+struct TalonCaptureNaming {
+    /// The name the actions use for the capture, like user.letter_1.
+    name: String,
+    /// The capture in the rule of the command, like <user.letter>.
+    rule_component: String,
+    /// The variable Talon gives the body of the command for the capture, like letter_1.
+    variable: String,
+}
+
+/// Talon numbers the variables of captures with the same name in the order they appear in the rule,
+/// so the captures are numbered again in the order the actions first use them.
+/// Generated lists already have a name of their own, like user.generated_list_1.
+fn compute_talon_capture_namings(actions: &[BasicAction]) -> Vec<TalonCaptureNaming> {
+    let mut namings: Vec<TalonCaptureNaming> = Vec::new();
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for name in compute_capture_names(actions) {
+        let Some((capture_name, instance)) = name.rsplit_once('_') else {
+            continue;
+        };
+        let short_name = capture_name.rsplit('.').next().unwrap_or(capture_name);
+        let (rule_component, variable) = if capture_name == GENERATED_LIST_CAPTURE_NAME {
+            (
+                format!("{{{}}}", name),
+                format!("{}_{}", short_name, instance),
+            )
+        } else {
+            let count = counts.entry(capture_name.to_string()).or_insert(0);
+            *count += 1;
+            (
+                format!("<{}>", capture_name),
+                format!("{}_{}", short_name, count),
+            )
+        };
+        namings.push(TalonCaptureNaming {
+            name,
+            rule_component,
+            variable,
+        });
+    }
+    namings
+}
+
+/// The spoken form is a numbered placeholder for the user to replace, followed by the captures the actions use.
+fn compute_spoken_form_placeholder(namings: &[TalonCaptureNaming], number: usize) -> String {
+    let mut spoken_form = format!("recommended command {}", number);
+    for naming in namings {
+        spoken_form.push(' ');
+        spoken_form.push_str(&naming.rule_component);
+    }
+    spoken_form
+}
+
+fn find_talon_variable<'a>(namings: &'a [TalonCaptureNaming], name: &'a str) -> &'a str {
+    namings
+        .iter()
+        .find(|naming| naming.name == name)
+        .map_or(name, |naming| naming.variable.as_str())
+}
+
+/// The Talon script for the action that refers to captures by the variables Talon gives them.
+fn compute_talon_body_script(action: &BasicAction, namings: &[TalonCaptureNaming]) -> String {
+    let arguments: Vec<String> = action
+        .get_arguments()
+        .iter()
+        .map(|argument| match argument {
+            Argument::CaptureArgument(capture) => format!(
+                "{}{}",
+                find_talon_variable(
+                    namings,
+                    &format!("{}_{}", capture.get_name(), capture.get_instance())
+                ),
+                capture.get_postfix()
+            ),
+            Argument::StringArgument(text) => {
+                let mut script = action.compute_string_argument(argument);
+                for name in compute_interpolated_capture_names(text) {
+                    script = script.replace(
+                        &format!("{{{}}}", name),
+                        &format!("{{{}}}", find_talon_variable(namings, name)),
+                    );
+                }
+                script
+            }
+            _ => action.compute_string_argument(argument),
+        })
+        .collect();
+    format!("{}({})", action.get_name(), arguments.join(", "))
+}
+
+fn compute_talon_command_text(statistics: &CommandStatistics, number: usize) -> String {
+    let mut text = format!(
+        "# Number of times used: {}\n# Number of words saved: {}\n",
+        statistics.number_of_times_used, statistics.number_of_words_saved
+    );
+    if statistics.is_gapped {
+        text.push_str(&format!("# {}\n", GAPPED_COMMAND_NOTE));
+    }
+    // Talon cannot load a file containing actions it cannot express, so those commands are left commented out.
+    let prefix = if statistics
+        .actions
+        .iter()
+        .all(|action| action.is_representable_in_talon_script())
+    {
+        ""
+    } else {
+        text.push_str("# Talon script cannot express list, dictionary, or None arguments, so these actions must be called from Python\n");
+        "# "
+    };
+    let namings = compute_talon_capture_namings(&statistics.actions);
+    text.push_str(&format!(
+        "{}{}:\n",
+        prefix,
+        compute_spoken_form_placeholder(&namings, number)
+    ));
+    for action in &statistics.actions {
+        text.push_str(&format!(
+            "{}    {}\n",
+            prefix,
+            compute_talon_body_script(action, &namings)
+        ));
+    }
+    text.push('\n');
+    text
+}
+
+/// Gives the generated list of the command the number of the list so that every command has its own list.
+fn compute_statistics_with_numbered_list(
+    statistics: &CommandStatistics,
+    list_number: i32,
+) -> CommandStatistics {
+    let mut numbered_statistics = statistics.clone();
+    for action in numbered_statistics.actions.iter_mut() {
+        if matches!(
+            action.get_arguments().first(),
+            Some(Argument::CaptureArgument(capture)) if capture.is_generated_list()
+        ) {
+            *action = BasicAction::new(
+                action.get_name(),
+                vec![Argument::CaptureArgument(TalonCapture::new(
+                    GENERATED_LIST_CAPTURE_NAME,
+                    list_number,
+                ))],
+            );
+        }
+    }
+    numbered_statistics
+}
+
+/// Turns inserted text into words that can be said, like feature/login into feature login.
+fn compute_list_item_spoken_form(value: &str) -> String {
+    value
+        .to_lowercase()
+        .split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+const DIGIT_WORDS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// Spells out a number digit by digit, like 12 as one two.
+fn compute_digit_words(number: usize) -> String {
+    number
+        .to_string()
+        .chars()
+        .filter_map(|digit| digit.to_digit(10))
+        .map(|digit| DIGIT_WORDS[digit as usize])
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// A .talon-list file mapping a spoken form of every value to the value.
+/// A value whose spoken form is already taken by an earlier value gets the next free number spelled out after it,
+/// like feature login two, so that every value can still be said.
+fn compute_talon_list_text(list_name: &str, values: &[String]) -> String {
+    let mut text = format!("list: {}\n-\n", list_name);
+    let mut spoken_forms = Vec::new();
+    for value in values {
+        let mut base_spoken_form = compute_list_item_spoken_form(value);
+        if base_spoken_form.is_empty() {
+            base_spoken_form = String::from("item");
+        }
+        let mut spoken_form = base_spoken_form.clone();
+        let mut number = 2;
+        while spoken_forms.contains(&spoken_form) {
+            spoken_form = format!("{} {}", base_spoken_form, compute_digit_words(number));
+            number += 1;
+        }
+        text.push_str(&format!(
+            "{}: \"{}\"\n",
+            spoken_form,
+            value
+                .replace('\\', "\\\\")
+                .replace('\"', "\\\"")
+                .replace('\n', "\\n")
+        ));
+        spoken_forms.push(spoken_form);
+    }
+    text
+}
+
+/// Talon requires lists to be declared in Python before a .talon-list file can give them items.
+fn compute_list_declaration_text(list_numbers: &[i32]) -> String {
+    let mut text = String::from("from talon import Module\n\nmodule = Module()\n");
+    for list_number in list_numbers {
+        text.push_str(&format!(
+            "module.list(\"generated_list_{}\", desc=\"Items for recommended command lists\")\n",
+            list_number
+        ));
+    }
+    text
+}
+
+/// A helper action generated to run the block of actions before a repeat block action again,
+/// along with the captures the block uses, which the command passes to it.
+struct RepeatBlockHelper {
+    name: String,
+    block: Vec<BasicAction>,
+    captures: Vec<String>,
+}
+
+/// Python names cannot contain the dots of capture names like user.letter_1.
+fn compute_python_name(capture: &str) -> String {
+    capture.replace('.', "_")
+}
+
+/// The names of the captures the actions use, like user.text_1, in the order they are first used.
+fn compute_capture_names(actions: &[BasicAction]) -> Vec<String> {
+    let mut captures = Vec::new();
+    for action in actions {
+        for argument in action.get_arguments() {
+            let names = match argument {
+                Argument::CaptureArgument(capture) => {
+                    vec![format!("{}_{}", capture.get_name(), capture.get_instance())]
+                }
+                Argument::StringArgument(text) => compute_interpolated_capture_names(text)
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for name in names {
+                if !captures.contains(&name) {
+                    captures.push(name);
+                }
+            }
+        }
+    }
+    captures
+}
+
+fn compute_python_argument(action: &BasicAction, argument: &Argument) -> String {
+    match argument {
+        Argument::CaptureArgument(capture) => format!(
+            "{}{}",
+            compute_python_name(&format!(
+                "{}_{}",
+                capture.get_name(),
+                capture.get_instance()
+            )),
+            capture.get_postfix()
+        ),
+        Argument::StringArgument(text) if !compute_interpolated_capture_names(text).is_empty() => {
+            let mut python_string = action.compute_string_argument(argument);
+            for capture in compute_interpolated_capture_names(text) {
+                python_string = python_string.replace(
+                    &format!("{{{}}}", capture),
+                    &format!("{{{}}}", compute_python_name(capture)),
+                );
+            }
+            format!("f{}", python_string)
+        }
+        _ => action.compute_string_argument(argument),
+    }
+}
+
+/// The Python lines running the actions, where a repeat action runs the line before it again.
+fn compute_python_lines(actions: &[BasicAction]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for action in actions {
+        let arguments: Vec<String> = action
+            .get_arguments()
+            .iter()
+            .map(|argument| compute_python_argument(action, argument))
+            .collect();
+        if action.get_name() == "repeat"
+            && let Some(previous_line) = lines.pop()
+        {
+            lines.push(format!("for _ in range({}):", arguments.join(", ")));
+            lines.push(format!("    {}", previous_line));
+        } else {
+            lines.push(format!(
+                "actions.{}({})",
+                action.get_name(),
+                arguments.join(", ")
+            ));
+        }
+    }
+    lines
+}
+
+/// Number captures give the helper numbers and every other capture gives it text.
+fn compute_python_parameter(capture: &str) -> String {
+    let type_name = if capture.starts_with("number") {
+        "int"
+    } else {
+        "str"
+    };
+    format!("{}: {}", compute_python_name(capture), type_name)
+}
+
+/// Replaces every repeat block action of the command with a call to a helper action that runs its block again,
+/// passing it the number of repetitions and the captures used by the block.
+fn compute_statistics_with_repeat_block_helpers(
+    statistics: &CommandStatistics,
+    helpers: &mut Vec<RepeatBlockHelper>,
+) -> CommandStatistics {
+    let mut helper_statistics = statistics.clone();
+    for index in 0..helper_statistics.actions.len() {
+        let action = &helper_statistics.actions[index];
+        if action.get_name() != REPEAT_BLOCK_ACTION_NAME {
+            continue;
+        }
+        let [repeat_count, Argument::IntArgument(block_size)] = action.get_arguments().as_slice()
+        else {
+            continue;
+        };
+        let block_size = *block_size as usize;
+        if block_size > index {
+            continue;
+        }
+        let block = helper_statistics.actions[index - block_size..index].to_vec();
+        let captures = compute_capture_names(&block);
+        let name = format!("{}_{}", REPEAT_BLOCK_ACTION_NAME, helpers.len() + 1);
+        let mut arguments = vec![repeat_count.clone()];
+        arguments.extend(captures.iter().filter_map(|capture| {
+            let (name, instance) = capture.rsplit_once('_')?;
+            Some(Argument::CaptureArgument(TalonCapture::new(
+                name,
+                instance.parse().ok()?,
+            )))
+        }));
+        helper_statistics.actions[index] = BasicAction::new(&name, arguments);
+        helpers.push(RepeatBlockHelper {
+            name,
+            block,
+            captures,
+        });
+    }
+    helper_statistics
+}
+
+/// Talon script cannot run earlier lines again, so every repeated block gets a Python action running it.
+fn compute_repeat_block_helpers_text(helpers: &[RepeatBlockHelper]) -> String {
+    let mut text = String::from(
+        "from talon import Module, actions\n\nmodule = Module()\n\n\n@module.action_class\nclass Actions:\n",
+    );
+    for helper in helpers {
+        let mut parameters = vec![String::from("repetitions: int")];
+        parameters.extend(
+            helper
+                .captures
+                .iter()
+                .map(|capture| compute_python_parameter(capture)),
+        );
+        text.push_str(&format!(
+            "    def {}({}):\n        \"\"\"Runs a block of actions of a recommended command again the given number of times\"\"\"\n        for _ in range(repetitions):\n",
+            helper.name.trim_start_matches("user."),
+            parameters.join(", ")
+        ));
+        for line in compute_python_lines(&helper.block) {
+            text.push_str(&format!("            {}\n", line));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Computes the contents of a .talon file for every application with recommendations, keyed by file name.
+/// Commands limited to an application get a file with an app.name header matching it, and the rest go to a global file.
+/// Every command inserting an item from a generated list also gets a .talon-list file for its list,
+/// and the lists are declared in a Python file. Repeated blocks of actions are run by actions defined in another Python file.
+pub fn compute_talon_files(recommendations: &[CommandStatistics]) -> BTreeMap<String, String> {
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    let mut list_numbers = Vec::new();
+    let mut repeat_block_helpers = Vec::new();
+    let mut file_names = BTreeMap::new();
+    for (index, statistics) in recommendations.iter().enumerate() {
+        let statistics =
+            &compute_statistics_with_repeat_block_helpers(statistics, &mut repeat_block_helpers);
+        let file_text = files
+            .entry(find_talon_file_name(
+                &statistics.application,
+                &mut file_names,
+            ))
+            .or_insert_with(|| match &statistics.application {
+                Some(application) => format!("app.name: {}\n-\n\n", application),
+                None => String::new(),
+            });
+        if statistics.uses_generated_list() {
+            let list_number = list_numbers.len() as i32 + 1;
+            list_numbers.push(list_number);
+            let numbered_statistics =
+                compute_statistics_with_numbered_list(statistics, list_number);
+            file_text.push_str(&compute_talon_command_text(&numbered_statistics, index + 1));
+            let list_name = format!("{}_{}", GENERATED_LIST_CAPTURE_NAME, list_number);
+            files.insert(
+                format!("generated_list_{}.talon-list", list_number),
+                compute_talon_list_text(&list_name, &statistics.compute_generated_list_values()),
+            );
+        } else {
+            file_text.push_str(&compute_talon_command_text(statistics, index + 1));
+        }
+    }
+    if !list_numbers.is_empty() {
+        files.insert(
+            String::from(GENERATED_LISTS_DECLARATION_FILE_NAME),
+            compute_list_declaration_text(&list_numbers),
+        );
+    }
+    if !repeat_block_helpers.is_empty() {
+        files.insert(
+            String::from(GENERATED_REPEAT_BLOCKS_FILE_NAME),
+            compute_repeat_block_helpers_text(&repeat_block_helpers),
+        );
+    }
+    files
+}
+
+pub fn output_talon_files(
+    recommendations: &[CommandStatistics],
+    directory_name: &str,
+) -> io::Result<()> {
+    let mut directory = compute_data_directory()?;
+    directory.push(directory_name);
+    paths::create_directory_if_nonexistent(&directory)?;
+    for (file_name, text) in compute_talon_files(recommendations) {
+        fs::write(directory.join(file_name), text)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_statistics(
+        actions: Vec<BasicAction>,
+        application: Option<&str>,
+    ) -> CommandStatistics {
+        let mut statistics = CommandStatistics::new(actions);
+        statistics.number_of_times_used = 3;
+        statistics.number_of_words_saved = 6;
+        statistics.application = application.map(String::from);
+        statistics
+    }
+
+    #[test]
+    fn test_talon_files_are_split_by_application() {
+        let recommendations = vec![
+            create_statistics(
+                vec![
+                    BasicAction::new("edit.save", Vec::new()),
+                    BasicAction::new(
+                        "insert",
+                        vec![Argument::CaptureArgument(TalonCapture::new("user.text", 1))],
+                    ),
+                ],
+                Some("Code"),
+            ),
+            create_statistics(
+                vec![BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from("enter"))],
+                )],
+                None,
+            ),
+            create_statistics(
+                vec![BasicAction::new("user.take", vec![Argument::NoneArgument])],
+                Some("Visual Studio/Code"),
+            ),
+            create_statistics(
+                vec![BasicAction::new("edit.undo", Vec::new())],
+                Some("Visual Studio Code"),
+            ),
+        ];
+        let files = compute_talon_files(&recommendations);
+        assert_eq!(
+            files.keys().collect::<Vec<&String>>(),
+            vec![
+                "app_Code.talon",
+                "app_Visual_Studio_Code.talon",
+                "app_Visual_Studio_Code_2.talon",
+                "global.talon"
+            ]
+        );
+        assert_eq!(
+            files["app_Code.talon"],
+            "app.name: Code\n-\n\n# Number of times used: 3\n# Number of words saved: 6\nrecommended command 1 <user.text>:\n    edit.save()\n    insert(text_1)\n\n"
+        );
+        assert_eq!(
+            files["global.talon"],
+            "# Number of times used: 3\n# Number of words saved: 6\nrecommended command 2:\n    key(\"enter\")\n\n"
+        );
+        assert!(
+            files["app_Visual_Studio_Code.talon"]
+                .contains("# recommended command 3:\n#     user.take(None)\n"),
+            "{}",
+            files["app_Visual_Studio_Code.talon"]
+        );
+        assert!(
+            files["app_Visual_Studio_Code_2.talon"]
+                .starts_with("app.name: Visual Studio Code\n-\n"),
+            "{}",
+            files["app_Visual_Studio_Code_2.talon"]
+        );
+    }
+
+    #[test]
+    fn test_spoken_form_includes_captures_used_inside_strings() {
+        let statistics = create_statistics(
+            vec![
+                BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from(
+                        "down:{number_small_1}",
+                    ))],
+                ),
+                BasicAction::new(
+                    "edit.line_goto",
+                    vec![Argument::CaptureArgument(TalonCapture::new("number", 1))],
+                ),
+            ],
+            None,
+        );
+        let namings = compute_talon_capture_namings(&statistics.actions);
+        assert_eq!(
+            compute_spoken_form_placeholder(&namings, 1),
+            "recommended command 1 <number_small> <number>"
+        );
+        assert_eq!(
+            statistics
+                .actions
+                .iter()
+                .map(|action| compute_talon_body_script(action, &namings))
+                .collect::<Vec<String>>(),
+            vec!["key(\"down:{number_small_1}\")", "edit.line_goto(number_1)"]
+        );
+    }
+
+    #[test]
+    fn test_commands_inserting_list_items_get_talon_lists() {
+        let mut statistics = create_statistics(
+            vec![
+                BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from("ctrl-l"))],
+                ),
+                BasicAction::new(
+                    "insert",
+                    vec![Argument::CaptureArgument(TalonCapture::new(
+                        GENERATED_LIST_CAPTURE_NAME,
+                        1,
+                    ))],
+                ),
+            ],
+            None,
+        );
+        let mut instantiation_set = ActionSet::new();
+        for value in ["POST", "GET", "feature/login"] {
+            instantiation_set.insert(&[
+                BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from("ctrl-l"))],
+                ),
+                BasicAction::new(
+                    "insert",
+                    vec![Argument::StringArgument(String::from(value))],
+                ),
+            ]);
+        }
+        statistics.instantiation_set = Some(instantiation_set);
+        let recommendations = vec![
+            create_statistics(vec![BasicAction::new("edit.save", Vec::new())], None),
+            statistics,
+        ];
+        let files = compute_talon_files(&recommendations);
+        assert_eq!(
+            files["global.talon"],
+            "# Number of times used: 3\n# Number of words saved: 6\nrecommended command 1:\n    edit.save()\n\n# Number of times used: 3\n# Number of words saved: 6\nrecommended command 2 {user.generated_list_1}:\n    key(\"ctrl-l\")\n    insert(generated_list_1)\n\n"
+        );
+        assert_eq!(
+            files["generated_list_1.talon-list"],
+            "list: user.generated_list_1\n-\nget: \"GET\"\npost: \"POST\"\nfeature login: \"feature/login\"\n"
+        );
+        assert!(files["generated_lists.py"].contains("module.list(\"generated_list_1\""));
+    }
+
+    #[test]
+    fn test_list_values_with_the_same_spoken_form_get_numbered() {
+        let values: Vec<String> = ["feature/login", "feature-login", "Feature_Login", "!!"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            compute_talon_list_text("user.generated_list_1", &values),
+            "list: user.generated_list_1\n-\nfeature login: \"feature/login\"\nfeature login two: \"feature-login\"\nfeature login three: \"Feature_Login\"\nitem: \"!!\"\n"
+        );
+    }
+
+    #[test]
+    fn test_repeated_blocks_are_run_by_generated_helper_actions() {
+        let mut repeat_count = TalonCapture::new("number_small", 1);
+        repeat_count.set_postfix(" - 1");
+        let statistics = create_statistics(
+            vec![
+                BasicAction::new("edit.line_start", Vec::new()),
+                BasicAction::new("key", vec![Argument::StringArgument(String::from("down"))]),
+                BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from(
+                        "ctrl-{user.letter_1}",
+                    ))],
+                ),
+                BasicAction::new(
+                    REPEAT_BLOCK_ACTION_NAME,
+                    vec![
+                        Argument::CaptureArgument(repeat_count),
+                        Argument::IntArgument(2),
+                    ],
+                ),
+            ],
+            None,
+        );
+        let files = compute_talon_files(&[statistics]);
+        assert_eq!(
+            files["global.talon"],
+            "# Number of times used: 3\n# Number of words saved: 6\nrecommended command 1 <user.letter> <number_small>:\n    edit.line_start()\n    key(\"down\")\n    key(\"ctrl-{letter_1}\")\n    user.fire_chicken_auto_generated_command_action_repeat_block_1(number_small_1 - 1, letter_1)\n\n"
+        );
+        assert_eq!(
+            files["generated_repeat_blocks.py"],
+            "from talon import Module, actions\n\nmodule = Module()\n\n\n@module.action_class\nclass Actions:\n    def fire_chicken_auto_generated_command_action_repeat_block_1(repetitions: int, user_letter_1: str):\n        \"\"\"Runs a block of actions of a recommended command again the given number of times\"\"\"\n        for _ in range(repetitions):\n            actions.key(\"down\")\n            actions.key(f\"ctrl-{user_letter_1}\")\n\n"
+        );
+    }
+}
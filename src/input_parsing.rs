@@ -1,18 +1,52 @@
+use crate::action_records::ParseMode;
+use crate::current_time::compute_time_in_seconds;
+use crate::record_loading::{
+    RecordSource, STANDARD_INPUT_SPECIFICATION, expand_record_specification,
+};
+use crate::record_timing::{TimeRange, parse_time_specification};
 use std::env;
-use std::fs::File;
 use std::io;
+use std::path::{Path, PathBuf};
 
-fn get_file_from_user() -> File {
+const DEFAULT_NUMBER_OF_RECOMMENDATIONS: usize = 0;
+const STRICT_PARSING_FLAG: &str = "--strict";
+const NO_CACHE_FLAG: &str = "--no-cache";
+const CLEAR_CACHE_FLAG: &str = "--clear-cache";
+const SINCE_OPTION: &str = "--since";
+const UNTIL_OPTION: &str = "--until";
+const OUTPUT_OPTION: &str = "--output";
+const SEED_OPTION: &str = "--seed";
+const JSON_OPTION: &str = "--json";
+const SET_OPTION: &str = "--set";
+const FLAGS: [&str; 3] = [STRICT_PARSING_FLAG, NO_CACHE_FLAG, CLEAR_CACHE_FLAG];
+const OPTIONS_WITH_VALUES: [&str; 6] = [
+    SINCE_OPTION,
+    UNTIL_OPTION,
+    OUTPUT_OPTION,
+    SEED_OPTION,
+    JSON_OPTION,
+    SET_OPTION,
+];
+const CONVERT_SUBCOMMAND: &str = "convert";
+const TRIM_SUBCOMMAND: &str = "trim";
+const ANONYMIZE_SUBCOMMAND: &str = "anonymize";
+const STATS_SUBCOMMAND: &str = "stats";
+
+fn get_record_sources_from_user() -> Vec<RecordSource> {
     return loop {
-        println!("Input the filepath to the command record: ");
+        println!("Input the filepath, directory, or glob pattern for the command record: ");
         let mut input = String::new();
         let _result = io::stdin().read_line(&mut input);
         match _result {
             Ok(_result) => {
-                let _file_result = File::open(input.trim());
-                match _file_result {
-                    Ok(valid_file) => break valid_file,
-                    Err(_) => println!("Please input a valid path."),
+                let specification = input.trim();
+                if specification == STANDARD_INPUT_SPECIFICATION {
+                    println!("Standard input can only be used from the command line.");
+                    continue;
+                }
+                match expand_record_specification(specification) {
+                    Ok(sources) => break sources,
+                    Err(message) => println!("{}\nPlease input a valid path.", message),
                 }
             }
             Err(_) => println!("Error reading input!"),
@@ -41,69 +75,338 @@ fn get_nonnegative_integer_from_user(prompt: &str, default: usize) -> usize {
     }
 }
 
-fn get_max_chain_size_from_user() -> usize {
-    get_nonnegative_integer_from_user(
-        "Input the maximum number of consecutive commands to consider as a single potential command.\nMaking this bigger can allow finding longer patterns but it takes longer. Press enter with no input to take default of 20: ",
-        20,
-    )
+/// A maximum chain size of 0 means that chains are not limited.
+fn compute_max_chain_size(size: usize) -> Option<usize> {
+    if size == 0 { None } else { Some(size) }
+}
+
+fn get_max_chain_size_from_user() -> Option<usize> {
+    compute_max_chain_size(get_nonnegative_integer_from_user(
+        "Input the maximum number of consecutive commands to consider as a single potential command.\nOnly patterns that repeat are extended, so this is rarely needed. Press enter with no input to take default of 0, which means no maximum: ",
+        0,
+    ))
 }
 
 fn get_number_of_recommendations_from_user() -> usize {
     get_nonnegative_integer_from_user(
         "Input the maximum number of command recommendations to output. Press enter with no input to take default of 0: ",
-        0,
+        DEFAULT_NUMBER_OF_RECOMMENDATIONS,
     )
 }
 
+/// What the program does with the record.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Subcommand {
+    /// Recommends new commands based on the record.
+    Recommend,
+    /// Writes the record back out, joining multiple records and applying any time range.
+    Convert,
+    /// Writes out the part of the record within the time range.
+    Trim,
+    /// Writes out the record with spoken words and inserted text replaced by pseudonyms.
+    Anonymize,
+    /// Prints statistics about the record.
+    Stats,
+}
+
 pub struct InputParameters {
-    pub max_chain_size: usize,
+    /// The most consecutive commands to consider as a single potential command, or None to not limit it.
+    pub max_chain_size: Option<usize>,
     pub number_of_recommendations: usize,
+    pub can_prompt_user: bool,
+    pub parse_mode: ParseMode,
+    pub time_range: TimeRange,
+    pub subcommand: Subcommand,
+    pub output_path: Option<PathBuf>,
+    pub anonymization_seed: u64,
+    pub json_output_path: Option<PathBuf>,
+    pub should_use_cache: bool,
+    pub should_clear_cache: bool,
+    /// Analysis settings given with --set like max_prose_size=5, in the order they were given.
+    pub setting_overrides: Vec<String>,
 }
 
-fn get_file(arguments: &Vec<String>) -> File {
-    if arguments.len() < 2 {
-        get_file_from_user()
-    } else if let Ok(file) = File::open(&arguments[1]) {
-        file
+fn is_option_argument(argument: &str) -> bool {
+    argument.starts_with("--")
+}
+
+/// The flags and options with values, like --strict or --since 30d, given on the command line.
+struct CommandLineOptions {
+    flags: Vec<String>,
+    values: Vec<(String, String)>,
+}
+
+impl CommandLineOptions {
+    fn new() -> Self {
+        CommandLineOptions {
+            flags: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|given_flag| given_flag == flag)
+    }
+
+    /// Returns the last value given for the option.
+    fn get_value(&self, option: &str) -> Option<&String> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(name, _)| name == option)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns every value given for an option that can be repeated.
+    fn get_values(&self, option: &str) -> Vec<String> {
+        self.values
+            .iter()
+            .filter(|(name, _)| name == option)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+/// Separates options from the positional arguments, keeping the program name first.
+/// Options that take a value accept it as the next argument or after an equals sign.
+fn separate_options_from_arguments(arguments: &[String]) -> (CommandLineOptions, Vec<String>) {
+    let mut options = CommandLineOptions::new();
+    let mut positional_arguments = Vec::new();
+    let mut arguments_iterator = arguments.iter();
+    if let Some(program_name) = arguments_iterator.next() {
+        positional_arguments.push(program_name.clone());
+    }
+    while let Some(argument) = arguments_iterator.next() {
+        if !is_option_argument(argument) {
+            positional_arguments.push(argument.clone());
+            continue;
+        }
+        let (name, value) = match argument.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (argument.as_str(), None),
+        };
+        if OPTIONS_WITH_VALUES.contains(&name) {
+            match value.or_else(|| arguments_iterator.next().cloned()) {
+                Some(value) => options.values.push((name.to_string(), value)),
+                None => println!("Ignoring option {} because it has no value", name),
+            }
+        } else if FLAGS.contains(&name) && value.is_none() {
+            options.flags.push(name.to_string());
+        } else {
+            println!("Ignoring unknown option {}", argument);
+        }
+    }
+    (options, positional_arguments)
+}
+
+fn compute_parse_mode(options: &CommandLineOptions) -> ParseMode {
+    if options.has_flag(STRICT_PARSING_FLAG) {
+        ParseMode::Strict
     } else {
-        println!("Could not open the record file.");
-        get_file_from_user()
+        ParseMode::Lenient
     }
 }
 
-fn get_max_chain_size(arguments: &Vec<String>) -> usize {
-    if arguments.len() < 3 {
-        get_max_chain_size_from_user()
-    } else if let Ok(size) = arguments[2].parse() {
-        size
+fn compute_time_range_boundary(options: &CommandLineOptions, option: &str) -> Option<u64> {
+    let specification = options.get_value(option)?;
+    let is_end_of_range = option == UNTIL_OPTION;
+    match parse_time_specification(specification, compute_time_in_seconds(), is_end_of_range) {
+        Ok(time) => Some(time),
+        Err(message) => {
+            println!(
+                "Could not understand {} {}: {}\nThe option will be ignored.",
+                option, specification, message
+            );
+            None
+        }
+    }
+}
+
+fn compute_time_range(options: &CommandLineOptions) -> TimeRange {
+    TimeRange {
+        since: compute_time_range_boundary(options, SINCE_OPTION),
+        until: compute_time_range_boundary(options, UNTIL_OPTION),
+    }
+}
+
+/// The subcommand is the first argument after the program name. Without one, recommendations are made.
+fn split_subcommand_from_arguments(mut arguments: Vec<String>) -> (Subcommand, Vec<String>) {
+    let subcommand = match arguments.get(1).map(|argument| argument.as_str()) {
+        Some(CONVERT_SUBCOMMAND) => Subcommand::Convert,
+        Some(TRIM_SUBCOMMAND) => Subcommand::Trim,
+        Some(ANONYMIZE_SUBCOMMAND) => Subcommand::Anonymize,
+        Some(STATS_SUBCOMMAND) => Subcommand::Stats,
+        _ => return (Subcommand::Recommend, arguments),
+    };
+    arguments.remove(1);
+    (subcommand, arguments)
+}
+
+/// Uses the current time as the seed unless one is given.
+fn compute_anonymization_seed(options: &CommandLineOptions) -> u64 {
+    let Some(seed_text) = options.get_value(SEED_OPTION) else {
+        return compute_time_in_seconds();
+    };
+    match seed_text.parse() {
+        Ok(seed) => seed,
+        Err(_) => {
+            println!(
+                "Could not understand {} {}. Using the current time instead.",
+                SEED_OPTION, seed_text
+            );
+            compute_time_in_seconds()
+        }
+    }
+}
+
+fn get_output_path_from_user() -> PathBuf {
+    loop {
+        println!("Input the filepath to write the record to: ");
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(_) if !input.trim().is_empty() => return PathBuf::from(input.trim()),
+            Ok(_) => println!("Please input a filepath."),
+            Err(_) => println!("Error reading input!"),
+        }
+    }
+}
+
+fn get_output_path(
+    options: &CommandLineOptions,
+    subcommand: Subcommand,
+    can_prompt_user: bool,
+) -> Option<PathBuf> {
+    if matches!(subcommand, Subcommand::Recommend | Subcommand::Stats) {
+        None
+    } else if let Some(path) = options.get_value(OUTPUT_OPTION) {
+        Some(PathBuf::from(path))
+    } else if can_prompt_user {
+        Some(get_output_path_from_user())
     } else {
+        None
+    }
+}
+
+/// A number is taken as a record specification instead if a file or directory has that name, like a history named 2024.
+fn is_number_argument(argument: &str) -> bool {
+    argument.parse::<usize>().is_ok() && !Path::new(argument).exists()
+}
+
+/// Record specifications come first and continue until the first number, which starts the numeric parameters.
+fn split_record_specifications_from_numeric_arguments(
+    arguments: &[String],
+) -> (&[String], &[String]) {
+    let arguments_after_program = arguments.get(1..).unwrap_or_default();
+    let number_of_specifications = arguments_after_program
+        .iter()
+        .position(|argument| is_number_argument(argument))
+        .unwrap_or(arguments_after_program.len());
+    arguments_after_program.split_at(number_of_specifications)
+}
+
+fn remove_repeated_sources(sources: Vec<RecordSource>) -> Vec<RecordSource> {
+    let mut unique_sources: Vec<RecordSource> = Vec::with_capacity(sources.len());
+    for source in sources {
+        if !unique_sources.contains(&source) {
+            unique_sources.push(source);
+        }
+    }
+    unique_sources
+}
+
+fn get_record_sources(specifications: &[String]) -> Vec<RecordSource> {
+    if specifications.is_empty() {
+        return get_record_sources_from_user();
+    }
+    let mut sources = Vec::new();
+    for specification in specifications {
+        match expand_record_specification(specification) {
+            Ok(specification_sources) => sources.extend(specification_sources),
+            Err(message) => {
+                println!("Could not open the record file. {}", message);
+                return get_record_sources_from_user();
+            }
+        }
+    }
+    remove_repeated_sources(sources)
+}
+
+fn get_max_chain_size(argument: Option<&String>, can_prompt_user: bool) -> Option<usize> {
+    let Some(argument) = argument else {
+        return if can_prompt_user {
+            get_max_chain_size_from_user()
+        } else {
+            None
+        };
+    };
+    if let Ok(size) = argument.parse() {
+        compute_max_chain_size(size)
+    } else if can_prompt_user {
         println!("Could not parse the maximum chain size.");
         get_max_chain_size_from_user()
+    } else {
+        println!("Could not parse the maximum chain size. Chains will not be limited.");
+        None
     }
 }
 
-fn get_number_of_recommendations(arguments: &Vec<String>) -> usize {
-    if arguments.len() < 4 {
-        get_number_of_recommendations_from_user()
-    } else if let Ok(size) = arguments[3].parse() {
+fn get_number_of_recommendations(argument: Option<&String>, can_prompt_user: bool) -> usize {
+    let Some(argument) = argument else {
+        return if can_prompt_user {
+            get_number_of_recommendations_from_user()
+        } else {
+            DEFAULT_NUMBER_OF_RECOMMENDATIONS
+        };
+    };
+    if let Ok(size) = argument.parse() {
         size
-    } else {
+    } else if can_prompt_user {
         println!("Could not parse the number of recommendations.");
         get_number_of_recommendations_from_user()
+    } else {
+        println!(
+            "Could not parse the number of recommendations. Using the default of {}.",
+            DEFAULT_NUMBER_OF_RECOMMENDATIONS
+        );
+        DEFAULT_NUMBER_OF_RECOMMENDATIONS
     }
 }
 
-pub fn get_input_parameters_from_user() -> (File, InputParameters) {
+pub fn get_input_parameters_from_user() -> (Vec<RecordSource>, InputParameters) {
     let arguments: Vec<String> = env::args().collect();
-    let record_file = get_file(&arguments);
-    let max_chain_size = get_max_chain_size(&arguments);
-    let number_of_recommendations = get_number_of_recommendations(&arguments);
+    let (options, arguments) = separate_options_from_arguments(&arguments);
+    let (subcommand, arguments) = split_subcommand_from_arguments(arguments);
+    // Statistics consider chains, so they take the maximum chain size like recommendations do.
+    let (specifications, numeric_arguments) =
+        if matches!(subcommand, Subcommand::Recommend | Subcommand::Stats) {
+            split_record_specifications_from_numeric_arguments(&arguments)
+        } else {
+            (arguments.get(1..).unwrap_or_default(), &[] as &[String])
+        };
+    let record_sources = get_record_sources(specifications);
+    // The user cannot answer prompts when standard input is being used to provide the record.
+    let can_prompt_user = !record_sources.contains(&RecordSource::StandardInput);
+    let can_prompt_for_numbers = can_prompt_user && subcommand == Subcommand::Recommend;
+    let max_chain_size = get_max_chain_size(numeric_arguments.first(), can_prompt_for_numbers);
+    let number_of_recommendations =
+        get_number_of_recommendations(numeric_arguments.get(1), can_prompt_for_numbers);
+    let output_path = get_output_path(&options, subcommand, can_prompt_user);
 
     (
-        record_file,
+        record_sources,
         InputParameters {
             max_chain_size,
             number_of_recommendations,
+            can_prompt_user,
+            parse_mode: compute_parse_mode(&options),
+            time_range: compute_time_range(&options),
+            subcommand,
+            output_path,
+            anonymization_seed: compute_anonymization_seed(&options),
+            json_output_path: options.get_value(JSON_OPTION).map(PathBuf::from),
+            should_use_cache: !options.has_flag(NO_CACHE_FLAG),
+            should_clear_cache: options.has_flag(CLEAR_CACHE_FLAG),
+            setting_overrides: options.get_values(SET_OPTION),
         },
     )
 }
@@ -0,0 +1,446 @@
+// Defines a standards compliant JSON parser for the values found in command records.
+
+const MAXIMUM_NESTING_DEPTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Keeps the original text so that integers and floating point numbers can be told apart.
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Keeps the fields in the order they appear in the text.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    pub fn compute_type_name(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "boolean",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "list",
+            JsonValue::Object(_) => "dictionary",
+        }
+    }
+}
+
+/// Creates the JSON text for a string, including the surrounding quotes.
+pub fn escape_json_string(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{08}' => result.push_str("\\b"),
+            '\u{0C}' => result.push_str("\\f"),
+            character if (character as u32) < 0x20 => {
+                result.push_str(&format!("\\u{:04x}", character as u32));
+            }
+            character => result.push(character),
+        }
+    }
+    result.push('"');
+    result
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(text: &'a str) -> Self {
+        JsonParser {
+            bytes: text.as_bytes(),
+            position: 0,
+            depth: 0,
+        }
+    }
+
+    fn create_error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.position)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect_byte(&mut self, expected: u8) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.create_error(&format!("Expected '{}'", expected as char)))
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(())
+        } else {
+            Err(self.create_error(&format!("Expected {}", literal)))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b't') => {
+                self.expect_literal("true")?;
+                Ok(JsonValue::Bool(true))
+            }
+            Some(b'f') => {
+                self.expect_literal("false")?;
+                Ok(JsonValue::Bool(false))
+            }
+            Some(b'n') => {
+                self.expect_literal("null")?;
+                Ok(JsonValue::Null)
+            }
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(byte) => {
+                Err(self.create_error(&format!("Unexpected character '{}'", byte as char)))
+            }
+            None => Err(self.create_error("Unexpected end of JSON")),
+        }
+    }
+
+    fn enter_container(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAXIMUM_NESTING_DEPTH {
+            return Err(self.create_error("JSON is nested too deeply"));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.enter_container()?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+        } else {
+            loop {
+                elements.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.position += 1,
+                    Some(b']') => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(self.create_error("Expected ',' or ']' in list")),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonValue::Array(elements))
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.enter_container()?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') {
+                    return Err(self.create_error("Expected a string key in dictionary"));
+                }
+                let key = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect_byte(b':')?;
+                let value = self.parse_value()?;
+                fields.push((key, value));
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => self.position += 1,
+                    Some(b'}') => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(self.create_error("Expected ',' or '}' in dictionary")),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(JsonValue::Object(fields))
+    }
+
+    fn parse_hexadecimal_code_unit(&mut self) -> Result<u32, String> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .ok_or_else(|| self.create_error("Incomplete unicode escape"))?;
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err(self.create_error("Invalid unicode escape"));
+        }
+        let text = std::str::from_utf8(digits).expect("Hexadecimal digits are ASCII");
+        let value = u32::from_str_radix(text, 16).expect("Hexadecimal digits were checked");
+        self.position += 4;
+        Ok(value)
+    }
+
+    /// Assumes the \u has already been consumed. Combines surrogate pairs into a single character.
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let first = self.parse_hexadecimal_code_unit()?;
+        let code_point = if (0xD800..0xDC00).contains(&first) {
+            if !self.bytes[self.position..].starts_with(b"\\u") {
+                return Err(self.create_error("Unpaired surrogate in unicode escape"));
+            }
+            self.position += 2;
+            let second = self.parse_hexadecimal_code_unit()?;
+            if !(0xDC00..0xE000).contains(&second) {
+                return Err(self.create_error("Invalid low surrogate in unicode escape"));
+            }
+            0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+        } else if (0xDC00..0xE000).contains(&first) {
+            return Err(self.create_error("Unpaired surrogate in unicode escape"));
+        } else {
+            first
+        };
+        char::from_u32(code_point).ok_or_else(|| self.create_error("Invalid unicode escape"))
+    }
+
+    fn parse_escape(&mut self, result: &mut Vec<u8>) -> Result<(), String> {
+        let escaped = self
+            .peek()
+            .ok_or_else(|| self.create_error("Unterminated escape sequence"))?;
+        self.position += 1;
+        let character = match escaped {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{08}',
+            b'f' => '\u{0C}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => self.parse_unicode_escape()?,
+            other => {
+                self.position -= 1;
+                return Err(
+                    self.create_error(&format!("Invalid escape character '{}'", other as char))
+                );
+            }
+        };
+        let mut buffer = [0; 4];
+        result.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+        Ok(())
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect_byte(b'"')?;
+        let mut result = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    self.parse_escape(&mut result)?;
+                }
+                Some(byte) if byte < 0x20 => {
+                    return Err(self.create_error("Unescaped control character in string"));
+                }
+                Some(byte) => {
+                    // Multibyte characters are copied one byte at a time, which keeps them intact because none of their bytes are special.
+                    result.push(byte);
+                    self.position += 1;
+                }
+                None => return Err(self.create_error("Unterminated string")),
+            }
+        }
+        Ok(String::from_utf8(result)
+            .expect("Copying complete UTF-8 sequences keeps the string valid"))
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.position;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.position += 1;
+        } else if self.skip_digits() == 0 {
+            return Err(self.create_error("Expected digits in number"));
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if self.skip_digits() == 0 {
+                return Err(self.create_error("Expected digits after decimal point"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.position += 1;
+            }
+            if self.skip_digits() == 0 {
+                return Err(self.create_error("Expected digits in exponent"));
+            }
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position])
+            .expect("Numbers only contain ASCII characters");
+        Ok(JsonValue::Number(text.to_string()))
+    }
+
+    fn parse_document(&mut self) -> Result<JsonValue, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.position < self.bytes.len() {
+            return Err(self.create_error("Unexpected text after JSON value"));
+        }
+        Ok(value)
+    }
+}
+
+/// Parses text containing exactly one JSON value.
+pub fn parse_json(text: &str) -> Result<JsonValue, String> {
+    JsonParser::new(text).parse_document()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_parses_to(text: &str, expected: JsonValue) {
+        match parse_json(text) {
+            Ok(value) => assert_eq!(value, expected),
+            Err(message) => panic!("Error parsing {}: {}", text, message),
+        }
+    }
+
+    fn assert_fails_to_parse(text: &str) {
+        assert!(parse_json(text).is_err(), "Expected an error for: {}", text);
+    }
+
+    fn create_string(text: &str) -> JsonValue {
+        JsonValue::String(String::from(text))
+    }
+
+    #[test]
+    fn test_parses_literals() {
+        assert_parses_to("null", JsonValue::Null);
+        assert_parses_to(" true ", JsonValue::Bool(true));
+        assert_parses_to("false", JsonValue::Bool(false));
+    }
+
+    #[test]
+    fn test_keeps_number_text() {
+        assert_parses_to("-12", JsonValue::Number(String::from("-12")));
+        assert_parses_to("0.5e-3", JsonValue::Number(String::from("0.5e-3")));
+    }
+
+    #[test]
+    fn test_rejects_invalid_numbers() {
+        assert_fails_to_parse("01");
+        assert_fails_to_parse("1.");
+        assert_fails_to_parse("-");
+        assert_fails_to_parse("1e");
+        assert_fails_to_parse(".5");
+    }
+
+    #[test]
+    fn test_parses_escapes() {
+        assert_parses_to(
+            r#""a\"b\\c\/d\n\t\r\b\f""#,
+            create_string("a\"b\\c/d\n\t\r\u{08}\u{0C}"),
+        );
+    }
+
+    #[test]
+    fn test_parses_unicode_escapes() {
+        assert_parses_to(r#""caf\u00e9""#, create_string("café"));
+        assert_parses_to(r#""\ud83d\ude00""#, create_string("😀"));
+    }
+
+    #[test]
+    fn test_rejects_unpaired_surrogate() {
+        assert_fails_to_parse(r#""\ud83d""#);
+        assert_fails_to_parse(r#""\ude00""#);
+    }
+
+    #[test]
+    fn test_keeps_raw_unicode() {
+        assert_parses_to("\"naïve ✓\"", create_string("naïve ✓"));
+    }
+
+    #[test]
+    fn test_rejects_raw_control_characters_in_strings() {
+        assert_fails_to_parse("\"line\nbreak\"");
+    }
+
+    #[test]
+    fn test_parses_nested_containers() {
+        assert_parses_to(
+            r#"{"a": [1, [true, null], {}], "b": {"c": []}}"#,
+            JsonValue::Object(vec![
+                (
+                    String::from("a"),
+                    JsonValue::Array(vec![
+                        JsonValue::Number(String::from("1")),
+                        JsonValue::Array(vec![JsonValue::Bool(true), JsonValue::Null]),
+                        JsonValue::Object(vec![]),
+                    ]),
+                ),
+                (
+                    String::from("b"),
+                    JsonValue::Object(vec![(String::from("c"), JsonValue::Array(vec![]))]),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_containers() {
+        assert_fails_to_parse("[1, 2");
+        assert_fails_to_parse("[1,]");
+        assert_fails_to_parse("{\"a\" 1}");
+        assert_fails_to_parse("{'a': 1}");
+        assert_fails_to_parse("{\"a\": 1} extra");
+    }
+
+    #[test]
+    fn test_rejects_excessive_nesting() {
+        let text = "[".repeat(MAXIMUM_NESTING_DEPTH + 1) + &"]".repeat(MAXIMUM_NESTING_DEPTH + 1);
+        assert_fails_to_parse(&text);
+    }
+
+    #[test]
+    fn test_escaped_string_parses_back_to_original() {
+        let original = "quote \" backslash \\ newline \n tab \t bell \u{07} é";
+        assert_parses_to(&escape_json_string(original), create_string(original));
+    }
+}
@@ -1,9 +1,14 @@
+mod action_interning;
 mod action_records;
 mod action_utilities;
+mod analysis_checkpoint;
+mod analysis_settings;
+mod binary_encoding;
 mod configuration;
 mod current_time;
 mod data_output;
 mod input_parsing;
+mod json;
 mod monte_carlo_tree_search;
 mod paths;
 mod pool;
@@ -11,15 +16,23 @@ mod random;
 mod recommendation_filtering;
 mod recommendation_generation;
 mod recommendation_scoring;
+mod record_anonymization;
+mod record_cache;
+mod record_corrections;
+mod record_diagnostics;
+mod record_loading;
+mod record_statistics;
+mod record_timing;
+mod record_writing;
+#[cfg(test)]
+mod test_utilities;
 mod text_separation;
 mod user_command_parsing;
 
-use action_records::read_file_record;
+use analysis_settings::AnalysisSettings;
 use current_time::compute_timestamp;
-use data_output::{create_data_directory, output_recommendations};
-use recommendation_generation::{
-    ActionSet, compute_recommendations_from_record, create_sorted_info,
-};
+use data_output::{create_data_directory, output_recommendations, output_talon_files};
+use recommendation_generation::{ActionSet, create_sorted_info};
 use std::io;
 use std::time::Instant;
 
@@ -27,6 +40,7 @@ fn find_best(
     recommendations: Vec<recommendation_generation::CommandStatistics>,
     start: &Vec<usize>,
     number_of_recommendations: usize,
+    settings: &AnalysisSettings,
 ) -> Vec<recommendation_generation::CommandStatistics> {
     println!(
         "Finding the best {} recommendations.",
@@ -39,6 +53,7 @@ fn find_best(
         number_of_recommendations as usize,
         false,
         false,
+        settings,
     );
     println!(
         "Time taken to find best recommendations: {:.3?}",
@@ -150,13 +165,19 @@ fn find_best_until_user_satisfied(
     number_of_recommendations: usize,
     to_persistently_reject_containing: &mut Vec<action_records::BasicAction>,
     to_persistently_reject_commands: &mut Vec<Vec<action_records::BasicAction>>,
+    settings: &AnalysisSettings,
 ) -> Vec<recommendation_generation::CommandStatistics> {
     let mut start: Vec<usize> = Vec::new();
     let mut to_keep = ActionSet::new();
     let mut should_keep_everything_else = false;
 
     loop {
-        let best = find_best(recommendations.clone(), &start, number_of_recommendations);
+        let best = find_best(
+            recommendations.clone(),
+            &start,
+            number_of_recommendations,
+            settings,
+        );
         let mut to_remove = ActionSet::new();
         let mut to_remove_containing = ActionSet::new();
         for recommendation in best.iter() {
@@ -260,13 +281,70 @@ fn filter_recommendations(recommendations: &mut Vec<recommendation_generation::C
     }
 }
 
+fn trim_record_to_time_range(
+    record: Vec<action_records::Entry>,
+    time_range: &record_timing::TimeRange,
+) -> Vec<action_records::Entry> {
+    if time_range.is_unbounded() {
+        return record;
+    }
+    let number_of_commands = count_commands(&record);
+    let (record, number_of_commands_with_unknown_time) =
+        record_timing::trim_record_to_time_range(record, time_range);
+    println!(
+        "Kept {} of {} commands within the requested time range",
+        count_commands(&record),
+        number_of_commands
+    );
+    if number_of_commands_with_unknown_time > 0 {
+        println!(
+            "Removed {} commands because the record does not say when they were spoken",
+            number_of_commands_with_unknown_time
+        );
+    }
+    record
+}
+
+fn remove_corrections(
+    record: Vec<action_records::Entry>,
+    settings: &AnalysisSettings,
+) -> Vec<action_records::Entry> {
+    let (record, number_of_corrections) =
+        record_corrections::remove_corrections(record, settings.correction_window_seconds);
+    if number_of_corrections > 0 {
+        println!(
+            "Removed {} corrections along with the commands they corrected",
+            number_of_corrections
+        );
+    }
+    record
+}
+
+fn count_commands(record: &[action_records::Entry]) -> usize {
+    record
+        .iter()
+        .filter(|entry| matches!(entry, action_records::Entry::Command(_)))
+        .count()
+}
+
 fn create_initial_recommendations(
     record: Vec<action_records::Entry>,
     parameters: &input_parsing::InputParameters,
+    settings: &AnalysisSettings,
+    record_description: &str,
     start_time: Instant,
 ) -> Vec<recommendation_generation::CommandStatistics> {
     println!("Generating recommendations");
-    let recommendations = compute_recommendations_from_record(record, parameters.max_chain_size);
+    let analysis_parameters = analysis_checkpoint::AnalysisParameters {
+        max_chain_size: parameters.max_chain_size,
+        settings: *settings,
+    };
+    let recommendations = analysis_checkpoint::compute_recommendations_with_checkpoint(
+        record,
+        &analysis_parameters,
+        record_description,
+        parameters.should_use_cache,
+    );
     let elapsed_time = start_time.elapsed();
     println!(
         "Time taken to compute recommendations: {:.3?}",
@@ -279,6 +357,7 @@ fn create_initial_recommendations(
 fn let_user_run_commands_on_recommendations(
     recommendations: Vec<recommendation_generation::CommandStatistics>,
     parameters: &input_parsing::InputParameters,
+    settings: &AnalysisSettings,
 ) -> Vec<recommendation_generation::CommandStatistics> {
     let mut recommendations =
         recommendation_scoring::filter_out_recommendations_redundant_smaller_commands(
@@ -288,6 +367,15 @@ fn let_user_run_commands_on_recommendations(
         "Narrowed it down to {} recommendations",
         recommendations.len()
     );
+    if !parameters.can_prompt_user {
+        println!("Standard input provided the record, so recommendations cannot be reviewed.");
+        return find_best(
+            recommendations,
+            &Vec::new(),
+            parameters.number_of_recommendations,
+            settings,
+        );
+    }
     let mut to_persistently_reject_containing: Vec<action_records::BasicAction> = Vec::new();
     let mut commands_to_persistently_reject = Vec::new();
     recommendations = find_best_until_user_satisfied(
@@ -295,6 +383,7 @@ fn let_user_run_commands_on_recommendations(
         parameters.number_of_recommendations,
         &mut to_persistently_reject_containing,
         &mut commands_to_persistently_reject,
+        settings,
     );
     configuration::append_actions_to_reject(&to_persistently_reject_containing);
     configuration::append_commands_to_reject(&commands_to_persistently_reject);
@@ -304,24 +393,106 @@ fn let_user_run_commands_on_recommendations(
 fn create_user_recommendations(
     record: Vec<action_records::Entry>,
     parameters: &input_parsing::InputParameters,
+    settings: &AnalysisSettings,
+    record_description: &str,
     start_time: Instant,
 ) {
     if record.is_empty() {
         println!("No actions in the record. Exiting.");
         return;
     }
-    let mut recommendations = create_initial_recommendations(record, parameters, start_time);
+    let mut recommendations = create_initial_recommendations(
+        record,
+        parameters,
+        settings,
+        record_description,
+        start_time,
+    );
     filter_recommendations(&mut recommendations);
 
     if parameters.number_of_recommendations > 0 {
-        recommendations = let_user_run_commands_on_recommendations(recommendations, parameters);
+        recommendations =
+            let_user_run_commands_on_recommendations(recommendations, parameters, settings);
     }
 
     create_sorted_info(&mut recommendations);
-    let file_name = format!("recommendations {}.txt", compute_timestamp());
+    let timestamp = compute_timestamp();
+    let file_name = format!("recommendations {}.txt", timestamp);
     output_recommendations(&recommendations, &file_name)
         .unwrap_or_else(|e| println!("Error writing recommendations to file: {}", e));
     println!("Recommendations written to file.");
+    let directory_name = format!("talon recommendations {}", timestamp);
+    match output_talon_files(&recommendations, &directory_name) {
+        Ok(()) => println!("Talon files written to the {} folder.", directory_name),
+        Err(e) => println!("Error writing talon files: {}", e),
+    }
+}
+
+fn write_converted_record(
+    record: Vec<action_records::Entry>,
+    parameters: &input_parsing::InputParameters,
+) {
+    if parameters.subcommand == input_parsing::Subcommand::Trim
+        && parameters.time_range.is_unbounded()
+    {
+        println!("Trimming requires a time range given with --since or --until.");
+        return;
+    }
+    let Some(output_path) = &parameters.output_path else {
+        println!("An output path must be given with --output when reading from standard input.");
+        return;
+    };
+    let mut record = trim_record_to_time_range(record, &parameters.time_range);
+    if parameters.subcommand == input_parsing::Subcommand::Anonymize {
+        record = record_anonymization::anonymize_record(record, parameters.anonymization_seed);
+    }
+    match record_writing::write_record_to_file(&record, output_path) {
+        Ok(_) => println!(
+            "Wrote {} commands to {}",
+            count_commands(&record),
+            output_path.display()
+        ),
+        Err(e) => println!("Error writing record to {}: {}", output_path.display(), e),
+    }
+}
+
+fn print_record_statistics(
+    record: Vec<action_records::Entry>,
+    parameters: &input_parsing::InputParameters,
+    settings: &AnalysisSettings,
+) {
+    let record = trim_record_to_time_range(record, &parameters.time_range);
+    let statistics = record_statistics::compute_record_statistics(
+        &record,
+        parameters.max_chain_size,
+        settings.chain_break_gap_seconds,
+    );
+    println!("{}", statistics.compute_report());
+    if let Some(json_output_path) = &parameters.json_output_path {
+        match statistics.write_json(json_output_path) {
+            Ok(_) => println!("Wrote statistics to {}", json_output_path.display()),
+            Err(e) => println!(
+                "Error writing statistics to {}: {}",
+                json_output_path.display(),
+                e
+            ),
+        }
+    }
+}
+
+fn clear_caches() {
+    match record_cache::clear_record_cache() {
+        Ok(number_of_files_removed) => {
+            println!("Removed {} cached records", number_of_files_removed)
+        }
+        Err(e) => println!("Error clearing the record cache: {}", e),
+    }
+    match analysis_checkpoint::clear_analysis_checkpoints() {
+        Ok(number_of_files_removed) => {
+            println!("Removed {} analysis checkpoints", number_of_files_removed)
+        }
+        Err(e) => println!("Error clearing the analysis checkpoints: {}", e),
+    }
 }
 
 fn main() {
@@ -333,14 +504,48 @@ fn main() {
         }
     }
 
-    let (record_file, parameters) = input_parsing::get_input_parameters_from_user();
+    let (record_sources, parameters) = input_parsing::get_input_parameters_from_user();
+    let settings = match configuration::load_analysis_settings(&parameters.setting_overrides) {
+        Ok(settings) => settings,
+        Err(message) => {
+            println!("Error loading the analysis settings:\n{}", message);
+            return;
+        }
+    };
+    if parameters.should_clear_cache {
+        clear_caches();
+    }
     let start_time = Instant::now();
     println!("Reading file");
-    let record = read_file_record(record_file);
+    let record = record_loading::load_record(
+        &record_sources,
+        parameters.parse_mode,
+        parameters.should_use_cache,
+    );
     match record {
-        Ok(record) => {
-            create_user_recommendations(record, &parameters, start_time);
-        }
+        Ok(record) => match parameters.subcommand {
+            input_parsing::Subcommand::Recommend => {
+                let record = trim_record_to_time_range(record, &parameters.time_range);
+                let record = remove_corrections(record, &settings);
+                let record_description =
+                    record_loading::compute_sources_description(&record_sources);
+                create_user_recommendations(
+                    record,
+                    &parameters,
+                    &settings,
+                    &record_description,
+                    start_time,
+                );
+            }
+            input_parsing::Subcommand::Convert
+            | input_parsing::Subcommand::Trim
+            | input_parsing::Subcommand::Anonymize => {
+                write_converted_record(record, &parameters);
+            }
+            input_parsing::Subcommand::Stats => {
+                print_record_statistics(record, &parameters, &settings)
+            }
+        },
         Err(e) => println!("Error reading record file:\n	{}", e),
     }
 }
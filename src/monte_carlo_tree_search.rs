@@ -1,3 +1,4 @@
+use crate::analysis_settings::AnalysisSettings;
 use crate::pool::{ThreadPool, compute_parallelism};
 use crate::random::RandomNumberGenerator;
 use crate::recommendation_generation::CommandStatistics;
@@ -443,6 +444,7 @@ impl<'a> MonteCarloTreeSearcher<'a> {
         recommendations: &'a Vec<CommandStatistics>,
         start: Vec<usize>,
         seed: u64,
+        settings: &AnalysisSettings,
     ) -> Self {
         let max_depth = recommendation_limit - start.len() - 1;
         let max_remaining_depth = std::cmp::min(start.len() + max_depth, recommendation_limit);
@@ -451,9 +453,9 @@ impl<'a> MonteCarloTreeSearcher<'a> {
             exploration_data: MonteCarloExplorationData::new(),
             start,
             constants: SearchConstants {
-                c: 0.000001,
-                rollouts_per_exploration: 10,
-                rollouts_per_child_expansion: 1,
+                c: settings.search_exploration_constant,
+                rollouts_per_exploration: settings.search_rollouts_per_exploration,
+                rollouts_per_child_expansion: settings.search_rollouts_per_child_expansion,
                 maximum_depth: max_remaining_depth,
                 recommendation_limit,
             },
@@ -551,9 +553,15 @@ fn perform_worker_monte_carlo_tree_search<'a>(
     recommendation_limit: usize,
     seed: u64,
     number_of_trials: usize,
+    settings: &AnalysisSettings,
 ) -> MonteCarloTreeSearcher<'a> {
-    let mut searcher =
-        MonteCarloTreeSearcher::new(recommendation_limit, recommendations, start.clone(), seed);
+    let mut searcher = MonteCarloTreeSearcher::new(
+        recommendation_limit,
+        recommendations,
+        start.clone(),
+        seed,
+        settings,
+    );
     searcher.explore_solutions(number_of_trials);
     searcher
 }
@@ -578,6 +586,7 @@ fn possibly_perform_parallel_monte_carlo_tree_search(
     recommendation_limit: usize,
     number_of_trials: usize,
     seed: u64,
+    settings: &AnalysisSettings,
 ) -> (f64, Vec<usize>, usize) {
     let num_workers = compute_parallelism();
     let trials_per_worker = if num_workers == 1 {
@@ -593,6 +602,7 @@ fn possibly_perform_parallel_monte_carlo_tree_search(
             recommendation_limit,
             seed,
             trials_per_worker,
+            settings,
         );
         let best_score = searcher.get_best_score();
         let best_recommendation_indexes = searcher.get_best_recommendation_indexes().clone();
@@ -613,6 +623,7 @@ fn possibly_perform_parallel_monte_carlo_tree_search(
             current_seed =
                 current_seed.wrapping_add(local_random_generator.next_in_range(1, 10000) as u64);
             let thread_seed = current_seed;
+            let settings = *settings;
 
             pool.execute(move || {
                 let searcher = perform_worker_monte_carlo_tree_search(
@@ -621,6 +632,7 @@ fn possibly_perform_parallel_monte_carlo_tree_search(
                     recommendation_limit,
                     thread_seed,
                     trials_per_worker,
+                    &settings,
                 );
                 (
                     searcher.get_best_score(),
@@ -692,6 +704,7 @@ pub fn perform_monte_carlo_tree_search(
     given_start: &Vec<usize>,
     recommendation_limit: usize,
     is_verbose: bool,
+    settings: &AnalysisSettings,
 ) -> (Vec<CommandStatistics>, f64) {
     let mut start = Vec::new();
     for i in given_start {
@@ -749,6 +762,7 @@ pub fn perform_monte_carlo_tree_search(
                 recommendation_limit,
                 number_of_trials,
                 seed as u64,
+                settings,
             )
         };
         if is_verbose {
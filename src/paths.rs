@@ -1,7 +1,7 @@
 use std::env::current_dir;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn create_directory_if_nonexistent(directory: &PathBuf) -> io::Result<()> {
     if !directory.exists() {
@@ -32,3 +32,26 @@ pub fn create_file(path: &PathBuf) -> io::Result<()> {
     }
     Ok(())
 }
+
+/// Writes to a temporary file first so that an interrupted write never leaves a partial file behind.
+pub fn write_file_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, bytes)?;
+    fs::rename(&temporary_path, path)
+}
+
+/// Deletes the files directly inside the directory and returns how many were deleted.
+pub fn remove_files_in_directory(directory: &Path) -> io::Result<usize> {
+    if !directory.exists() {
+        return Ok(0);
+    }
+    let mut number_of_files_removed = 0;
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() {
+            fs::remove_file(path)?;
+            number_of_files_removed += 1;
+        }
+    }
+    Ok(number_of_files_removed)
+}
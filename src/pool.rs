@@ -79,24 +79,6 @@ impl<JobResult: Send + 'static> ThreadPool<JobResult> {
         self.job_number += 1;
     }
 
-    pub fn join(&mut self) -> Vec<JobResult> {
-        if self.job_number == 0 {
-            return Vec::new();
-        }
-        let mut results: Vec<Option<JobResult>> = Vec::with_capacity(self.job_number);
-        results.resize_with(self.job_number, || None);
-        let mut received = 0;
-        while let Ok((number, value)) = self.receiver.recv() {
-            results[number] = Some(value);
-            received += 1;
-            if received == self.job_number {
-                break;
-            }
-        }
-        self.job_number = 0;
-        results.into_iter().map(|r| r.unwrap()).collect()
-    }
-
     pub fn join_unordered(&mut self) -> Vec<JobResult> {
         if self.job_number == 0 {
             return Vec::new();
@@ -39,6 +39,6 @@ pub fn filter_out_recommendations_containing_action(
     rejected_action: &BasicAction,
 ) {
     filter_out_recommendations_with_action_matching_filter(recommendations, |action| {
-        action.to_json() == rejected_action.to_json()
+        action == rejected_action
     });
 }
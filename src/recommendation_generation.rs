@@ -1,12 +1,19 @@
-const FIVE_MINUTES_IN_SECONDS: u32 = 5 * 60;
-const DEFAULT_MAX_PROSE_SIZE_TO_CONSIDER: usize = 10;
-use crate::action_records::{Argument, BasicAction, Command, CommandChain, Entry, TalonCapture};
+pub const FIVE_MINUTES_IN_SECONDS: u32 = 5 * 60;
+use crate::action_interning::{ActionId, ActionInterner};
+use crate::action_records::{
+    Argument, BasicAction, Command, CommandChain, CommandContext, Entry,
+    GENERATED_LIST_CAPTURE_NAME, TalonCapture,
+};
 use crate::action_utilities::*;
+use crate::analysis_settings::AnalysisSettings;
+use crate::binary_encoding::{BinaryDecoder, BinaryEncoder};
 use crate::pool;
+use crate::record_cache::{decode_actions, decode_context, encode_actions, encode_context};
 use crate::text_separation::{
     TextSeparationAnalyzer, compute_case_string_for_prose, has_valid_case,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, hash_map};
+use std::ops::Range;
 use std::sync::Arc;
 
 fn compute_number_of_words(command_chain: &CommandChain) -> u32 {
@@ -25,6 +32,12 @@ pub struct CommandStatistics {
     pub total_number_of_words_dictated: u32,
     pub number_of_words_saved: u32,
     pub instantiation_set: Option<ActionSet>,
+    /// How many uses happened in each context, ignoring uses without any recorded context.
+    pub contexts: BTreeMap<CommandContext, usize>,
+    /// The application the command should be limited to, or None if the command should be available everywhere.
+    pub application: Option<String>,
+    /// Whether the command was found with other commands spoken between its commands, which the command drops.
+    pub is_gapped: bool,
 }
 
 impl CommandStatistics {
@@ -37,6 +50,9 @@ impl CommandStatistics {
             total_number_of_words_dictated: 0,
             number_of_words_saved: 0,
             instantiation_set: None,
+            contexts: BTreeMap::new(),
+            application: None,
+            is_gapped: false,
         }
     }
 
@@ -50,6 +66,9 @@ impl CommandStatistics {
             total_number_of_words_dictated: 0,
             number_of_words_saved: 0,
             instantiation_set,
+            contexts: BTreeMap::new(),
+            application: None,
+            is_gapped: false,
         }
     }
 
@@ -60,9 +79,295 @@ impl CommandStatistics {
         self.total_number_of_words_dictated as f32 / self.number_of_times_used as f32
     }
 
+    fn compute_concrete_number_of_words_saved(&mut self) {
+        self.number_of_words_saved =
+            self.number_of_times_used as u32 * (self.get_average_words_dictated() as u32 - 1);
+    }
+
+    fn get_number_of_instantiations(&self) -> usize {
+        self.instantiation_set
+            .as_ref()
+            .map_or(0, |instantiation_set| instantiation_set.get_size())
+    }
+
+    /// Adds the usage of the same potential command counted in a different application.
+    /// The result is no longer limited to an application.
+    fn merge(&mut self, other: CommandStatistics) {
+        self.add_usage(other);
+        self.application = None;
+    }
+
+    /// Adds the usage of the same potential command counted in chains that do not overlap the ones counted here.
+    fn add_usage(&mut self, other: CommandStatistics) {
+        self.number_of_times_used += other.number_of_times_used;
+        self.total_number_of_words_dictated += other.total_number_of_words_dictated;
+        self.number_of_words_saved += other.number_of_words_saved;
+        if let (Some(instantiation_set), Some(other_instantiation_set)) =
+            (self.instantiation_set.as_mut(), other.instantiation_set)
+        {
+            instantiation_set.extend(&other_instantiation_set);
+        }
+        for (context, count) in other.contexts {
+            *self.contexts.entry(context).or_insert(0) += count;
+        }
+    }
+
+    /// The index of the insert action that inserts an item from a generated list, if the command has one.
+    fn find_generated_list_insert_index(&self) -> Option<usize> {
+        self.actions.iter().position(|action| {
+            action.get_name() == "insert"
+                && matches!(
+                    action.get_arguments().first(),
+                    Some(Argument::CaptureArgument(capture)) if capture.is_generated_list()
+                )
+        })
+    }
+
+    pub fn uses_generated_list(&self) -> bool {
+        self.find_generated_list_insert_index().is_some()
+    }
+
+    /// The sorted texts the generated list of the command must contain, which are the texts inserted by its instantiations.
+    pub fn compute_generated_list_values(&self) -> Vec<String> {
+        let (Some(index), Some(instantiation_set)) = (
+            self.find_generated_list_insert_index(),
+            &self.instantiation_set,
+        ) else {
+            return Vec::new();
+        };
+        let mut values: Vec<String> = instantiation_set
+            .get_action_sequences()
+            .iter()
+            .filter_map(|actions| actions.get(index))
+            .filter(|action| is_insert(action))
+            .map(|action| get_insert_text(action).clone())
+            .collect();
+        values.sort();
+        values.dedup();
+        values
+    }
+
+    /// Whether a number or key capture of the command stands for the same value in every instantiation,
+    /// in which case the command that keeps that value is the better recommendation.
+    fn has_number_capture_with_one_value(&self) -> bool {
+        let Some(instantiation_set) = &self.instantiation_set else {
+            return false;
+        };
+        let mut values: HashMap<(usize, usize, usize), HashSet<String>> = HashMap::new();
+        for actions in instantiation_set.get_action_sequences() {
+            if actions.len() != self.actions.len() {
+                continue;
+            }
+            for (action_index, (abstract_action, action)) in
+                self.actions.iter().zip(&actions).enumerate()
+            {
+                for (argument_index, (abstract_argument, argument)) in abstract_action
+                    .get_arguments()
+                    .iter()
+                    .zip(action.get_arguments())
+                    .enumerate()
+                {
+                    match (abstract_argument, argument) {
+                        (Argument::CaptureArgument(capture), _)
+                            if capture.get_name() == NUMBER_CAPTURE_NAME =>
+                        {
+                            values
+                                .entry((action_index, argument_index, 0))
+                                .or_default()
+                                .insert(format!("{:?}", argument));
+                        }
+                        (
+                            Argument::StringArgument(abstract_keys),
+                            Argument::StringArgument(keys),
+                        ) if abstract_action.get_name() == "key" => {
+                            for (key_index, (abstract_key, key)) in
+                                abstract_keys.split(' ').zip(keys.split(' ')).enumerate()
+                            {
+                                if abstract_key.contains('{') {
+                                    values
+                                        .entry((action_index, argument_index, key_index))
+                                        .or_default()
+                                        .insert(key.to_string());
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        values.values().any(|values| values.len() == 1)
+    }
+
+    /// The numbers every number capture of the command stands for across its instantiations, by capture instance.
+    fn compute_number_capture_values(&self) -> BTreeMap<i32, HashSet<i32>> {
+        let mut values: BTreeMap<i32, HashSet<i32>> = BTreeMap::new();
+        let Some(instantiation_set) = &self.instantiation_set else {
+            return values;
+        };
+        for actions in instantiation_set.get_action_sequences() {
+            if actions.len() != self.actions.len() {
+                continue;
+            }
+            for (abstract_action, action) in self.actions.iter().zip(&actions) {
+                for (abstract_argument, argument) in abstract_action
+                    .get_arguments()
+                    .iter()
+                    .zip(action.get_arguments())
+                {
+                    match (abstract_argument, argument) {
+                        (Argument::CaptureArgument(capture), Argument::IntArgument(number))
+                            if capture.get_name() == NUMBER_CAPTURE_NAME =>
+                        {
+                            values
+                                .entry(capture.get_instance())
+                                .or_default()
+                                .insert(*number);
+                        }
+                        (
+                            Argument::StringArgument(abstract_keys),
+                            Argument::StringArgument(keys),
+                        ) if abstract_action.get_name() == "key" => {
+                            for (abstract_key, key) in abstract_keys.split(' ').zip(keys.split(' '))
+                            {
+                                if let (Some((_, instance)), Some((_, count))) = (
+                                    find_key_repeat_count_capture_instance(abstract_key),
+                                    split_key_repeat_count(key),
+                                ) {
+                                    values.entry(instance).or_default().insert(count);
+                                }
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        values
+    }
+
+    /// Puts the number back in place of every number capture that stands for the same number in every instantiation,
+    /// so that numbers that never vary, like the 0 in mouse_scroll(0, 3), are kept.
+    /// The remaining number captures are numbered again in order.
+    /// A command whose number captures all stand for a single number is left for the filter to remove.
+    fn keep_numbers_that_never_vary(&mut self) {
+        let values = self.compute_number_capture_values();
+        let constants: HashMap<i32, i32> = values
+            .iter()
+            .filter(|(_, numbers)| numbers.len() == 1)
+            .filter_map(|(instance, numbers)| Some((*instance, *numbers.iter().next()?)))
+            .collect();
+        if constants.is_empty() || constants.len() == values.len() {
+            return;
+        }
+        let new_instances: HashMap<i32, i32> = values
+            .keys()
+            .filter(|instance| !constants.contains_key(instance))
+            .zip(1..)
+            .map(|(instance, new_instance)| (*instance, new_instance))
+            .collect();
+        for action in self.actions.iter_mut() {
+            let arguments = action
+                .get_arguments()
+                .iter()
+                .map(|argument| match argument {
+                    Argument::CaptureArgument(capture)
+                        if capture.get_name() == NUMBER_CAPTURE_NAME =>
+                    {
+                        match constants.get(&capture.get_instance()) {
+                            Some(number) => Argument::IntArgument(*number),
+                            None => Argument::CaptureArgument(TalonCapture::new(
+                                NUMBER_CAPTURE_NAME,
+                                new_instances[&capture.get_instance()],
+                            )),
+                        }
+                    }
+                    Argument::StringArgument(keys) if action.get_name() == "key" => {
+                        Argument::StringArgument(map_keys(keys, |key| {
+                            match find_key_repeat_count_capture_instance(key) {
+                                Some((key, instance)) => match constants.get(&instance) {
+                                    Some(count) => format!("{}:{}", key, count),
+                                    None => format!(
+                                        "{}:{{{}}}",
+                                        key,
+                                        TalonCapture::new(
+                                            NUMBER_CAPTURE_NAME,
+                                            new_instances[&instance]
+                                        )
+                                        .compute_string_representation()
+                                    ),
+                                },
+                                None => key.to_string(),
+                            }
+                        }))
+                    }
+                    _ => argument.clone(),
+                })
+                .collect();
+            *action = BasicAction::new(action.get_name(), arguments);
+        }
+        // Every capture was assumed to take a word to say, and the numbers put back no longer need one.
+        self.number_of_words_saved += (constants.len() * self.number_of_times_used) as u32;
+    }
+
     pub fn process_usage(&mut self, command_chain: &CommandChain) {
         self.number_of_times_used += 1;
         self.total_number_of_words_dictated += compute_number_of_words(command_chain);
+        let context = command_chain.get_command().get_context();
+        if !context.is_empty() {
+            *self.contexts.entry(context.clone()).or_insert(0) += 1;
+        }
+    }
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encode_actions(&self.actions, encoder);
+        encoder.write_u64(self.number_of_times_used as u64);
+        encoder.write_u64(self.number_of_actions as u64);
+        encoder.write_u32(self.total_number_of_words_dictated);
+        encoder.write_u32(self.number_of_words_saved);
+        encoder.write_bool(self.instantiation_set.is_some());
+        if let Some(instantiation_set) = &self.instantiation_set {
+            instantiation_set.encode(encoder);
+        }
+        encoder.write_length(self.contexts.len());
+        for (context, count) in &self.contexts {
+            encode_context(context, encoder);
+            encoder.write_u64(*count as u64);
+        }
+        encoder.write_optional_string(self.application.as_deref());
+        encoder.write_bool(self.is_gapped);
+    }
+
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        let actions = decode_actions(decoder)?;
+        let number_of_times_used = decoder.read_u64()? as usize;
+        let number_of_actions = decoder.read_u64()? as usize;
+        let total_number_of_words_dictated = decoder.read_u32()?;
+        let number_of_words_saved = decoder.read_u32()?;
+        let instantiation_set = if decoder.read_bool()? {
+            Some(ActionSet::decode(decoder)?)
+        } else {
+            None
+        };
+        let number_of_contexts = decoder.read_length()?;
+        let mut contexts = BTreeMap::new();
+        for _ in 0..number_of_contexts {
+            let context = decode_context(decoder)?;
+            contexts.insert(context, decoder.read_u64()? as usize);
+        }
+        let application = decoder.read_optional_string()?;
+        let is_gapped = decoder.read_bool()?;
+        Ok(CommandStatistics {
+            actions,
+            number_of_times_used,
+            number_of_actions,
+            total_number_of_words_dictated,
+            number_of_words_saved,
+            instantiation_set,
+            contexts,
+            application,
+            is_gapped,
+        })
     }
 }
 
@@ -86,6 +391,22 @@ impl ChainHandler {
     pub fn process_relevant_usage(&mut self, command_chain: &CommandChain) {
         self.chain = Some(command_chain.get_chain_ending_index());
     }
+
+    /// Keeps whichever handler counted the later usage.
+    fn merge(&mut self, other: &ChainHandler) {
+        self.chain = self.chain.max(other.chain);
+    }
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_optional_u64(self.chain.map(|chain| chain as u64));
+    }
+
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        let chain = decoder.read_optional_u64()?;
+        Ok(ChainHandler {
+            chain: chain.map(|chain| chain as usize),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -104,6 +425,13 @@ fn compute_number_of_actions(actions: &Vec<BasicAction>) -> usize {
                     number_of_actions += unsigned_repeat_count - 1;
                 }
             }
+        } else if action.get_name() == REPEAT_BLOCK_ACTION_NAME
+            && let [
+                Argument::IntArgument(repeat_count),
+                Argument::IntArgument(number_of_actions_in_block),
+            ] = action.get_arguments().as_slice()
+        {
+            number_of_actions += *repeat_count as usize * *number_of_actions_in_block as usize - 1;
         }
     }
     number_of_actions
@@ -147,16 +475,17 @@ impl PotentialCommandInformation {
         self.statistics.process_usage(command_chain);
     }
 
-    pub fn compute_number_of_words_saved(&mut self) {
-        let words_saved = self.statistics.number_of_times_used as u32
-            * (self.statistics.get_average_words_dictated() as u32 - 1);
-        self.statistics.number_of_words_saved = words_saved;
+    fn add_independent_usage(&mut self, other: PotentialCommandInformation) {
+        self.statistics.add_usage(other.statistics);
+        self.chain_handler.merge(&other.chain_handler);
     }
 }
 
+/// A set of action sequences. A single action is stored as a sequence of one action.
 #[derive(Clone, Debug)]
 pub struct ActionSet {
-    set: HashSet<String>,
+    interner: ActionInterner,
+    set: HashSet<Vec<ActionId>>,
 }
 
 pub fn compute_string_representation_of_actions(actions: &[BasicAction]) -> String {
@@ -167,49 +496,65 @@ pub fn compute_string_representation_of_actions(actions: &[BasicAction]) -> Stri
         .join("")
 }
 
-pub fn compute_string_representation_of_chain_actions(command_chain: &CommandChain) -> String {
-    let actions = command_chain.get_command().get_actions();
-    compute_string_representation_of_actions(actions)
-}
-
 impl ActionSet {
     pub fn new() -> Self {
         Self {
+            interner: ActionInterner::new(),
             set: HashSet::new(),
         }
     }
 
-    pub fn insert(&mut self, actions: &Vec<BasicAction>) {
-        let representation = compute_string_representation_of_actions(actions);
-        self.set.insert(representation);
-    }
-
-    pub fn insert_representation(&mut self, representation: &str) {
-        self.set.insert(representation.to_string());
+    pub fn insert(&mut self, actions: &[BasicAction]) {
+        let identifiers = self.interner.intern_actions(actions);
+        self.set.insert(identifiers);
     }
 
     pub fn insert_action(&mut self, action: &BasicAction) {
-        let representation = action.to_json();
-        self.set.insert(representation);
+        self.insert(std::slice::from_ref(action));
     }
 
-    pub fn contains(&self, actions: &Vec<BasicAction>) -> bool {
-        let representation = compute_string_representation_of_actions(actions);
-        self.set.contains(&representation)
+    pub fn contains(&self, actions: &[BasicAction]) -> bool {
+        self.interner
+            .find_actions(actions)
+            .is_some_and(|identifiers| self.set.contains(&identifiers))
     }
 
     pub fn contains_action(&self, action: &BasicAction) -> bool {
-        let representation = action.to_json();
-        self.set.contains(&representation)
-    }
-
-    pub fn contains_representation(&self, representation: &str) -> bool {
-        self.set.contains(representation)
+        self.contains(std::slice::from_ref(action))
     }
 
     pub fn get_size(&self) -> usize {
         self.set.len()
     }
+
+    pub fn get_action_sequences(&self) -> Vec<Vec<BasicAction>> {
+        self.set
+            .iter()
+            .map(|identifiers| self.interner.get_actions(identifiers))
+            .collect()
+    }
+
+    /// Adds every sequence in the other set.
+    pub fn extend(&mut self, other: &ActionSet) {
+        for identifiers in &other.set {
+            self.insert(&other.interner.get_actions(identifiers));
+        }
+    }
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_length(self.set.len());
+        for identifiers in &self.set {
+            encode_actions(&self.interner.get_actions(identifiers), encoder);
+        }
+    }
+
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        let mut action_set = ActionSet::new();
+        for _ in 0..decoder.read_length()? {
+            action_set.insert(&decode_actions(decoder)?);
+        }
+        Ok(action_set)
+    }
 }
 
 pub struct AbstractCommandInstantiation {
@@ -253,16 +598,13 @@ impl PotentialAbstractCommandInformation {
         }
     }
 
-    pub fn get_statistics(&self) -> &CommandStatistics {
-        &self.statistics
+    fn add_independent_usage(&mut self, other: PotentialAbstractCommandInformation) {
+        self.statistics.add_usage(other.statistics);
+        self.chain_handler.merge(&other.chain_handler);
     }
 
-    pub fn get_number_of_instantiations(&self) -> usize {
-        self.statistics
-            .instantiation_set
-            .as_ref()
-            .unwrap()
-            .get_size()
+    pub fn get_statistics(&self) -> &CommandStatistics {
+        &self.statistics
     }
 }
 
@@ -270,16 +612,82 @@ fn create_repeat_action(repeat_count: i32) -> BasicAction {
     BasicAction::new("repeat", vec![Argument::IntArgument(repeat_count)])
 }
 
+/// Stands for running the given number of actions before it again the given number of times.
+/// Talon script cannot run earlier lines again, so Talon files call a helper action generated for the block instead.
+pub const REPEAT_BLOCK_ACTION_NAME: &str =
+    "user.fire_chicken_auto_generated_command_action_repeat_block";
+
+fn create_repeat_block_action(repeat_count: i32, number_of_actions_in_block: usize) -> BasicAction {
+    BasicAction::new(
+        REPEAT_BLOCK_ACTION_NAME,
+        vec![
+            Argument::IntArgument(repeat_count),
+            Argument::IntArgument(number_of_actions_in_block as i32),
+        ],
+    )
+}
+
+fn is_repeat_action(action: &BasicAction) -> bool {
+    action.get_name() == "repeat" || action.get_name() == REPEAT_BLOCK_ACTION_NAME
+}
+
+/// The number of times the block of actions starting at the index is repeated right after itself.
+fn count_block_repetitions(actions: &[BasicAction], start: usize, block_size: usize) -> usize {
+    let block = &actions[start..start + block_size];
+    actions[start + block_size..]
+        .chunks_exact(block_size)
+        .take_while(|chunk| *chunk == block)
+        .count()
+}
+
+/// Replaces runs of a repeated block of two or more actions with the block followed by a repeat block action.
+/// The block covering the most actions is chosen at each position, preferring smaller blocks.
+fn compute_block_repeat_simplified_command_chain(command_chain: &CommandChain) -> CommandChain {
+    let actions = command_chain.get_command().get_actions();
+    let mut new_actions = Vec::new();
+    let mut index = 0;
+    while index < actions.len() {
+        let mut best_block: Option<(usize, usize)> = None;
+        for block_size in 2..=(actions.len() - index) / 2 {
+            let repetitions = count_block_repetitions(actions, index, block_size);
+            if repetitions > 0
+                && best_block.is_none_or(|(best_size, best_repetitions)| {
+                    block_size * (repetitions + 1) > best_size * (best_repetitions + 1)
+                })
+            {
+                best_block = Some((block_size, repetitions));
+            }
+        }
+        match best_block {
+            Some((block_size, repetitions)) => {
+                new_actions.extend_from_slice(&actions[index..index + block_size]);
+                new_actions.push(create_repeat_block_action(repetitions as i32, block_size));
+                index += block_size * (repetitions + 1);
+            }
+            None => {
+                new_actions.push(actions[index].clone());
+                index += 1;
+            }
+        }
+    }
+
+    let command = command_chain.get_command();
+    let new_command = command.create_modified_copy(command.get_name(), new_actions);
+    CommandChain::new(
+        new_command,
+        command_chain.get_chain_number(),
+        command_chain.get_size(),
+    )
+}
+
 fn compute_command_chain_copy_with_new_name_and_actions(
     command_chain: &CommandChain,
     new_name: &str,
     new_actions: Vec<BasicAction>,
 ) -> CommandChain {
-    let new_command = Command::new(
-        new_name,
-        new_actions,
-        command_chain.get_command().get_seconds_since_last_action(),
-    );
+    let new_command = command_chain
+        .get_command()
+        .create_modified_copy(new_name, new_actions);
     CommandChain::new(
         new_command,
         command_chain.get_chain_number(),
@@ -309,11 +717,8 @@ fn compute_repeat_simplified_command_chain(command_chain: &CommandChain) -> Comm
         new_actions.push(create_repeat_action(repeat_count));
     }
 
-    let new_command = Command::new(
-        command_chain.get_command().get_name(),
-        new_actions,
-        command_chain.get_command().get_seconds_since_last_action(),
-    );
+    let command = command_chain.get_command();
+    let new_command = command.create_modified_copy(command.get_name(), new_actions);
     CommandChain::new(
         new_command,
         command_chain.get_chain_number(),
@@ -341,11 +746,8 @@ fn compute_insert_simplified_command_chain(command_chain: &CommandChain) -> Comm
         new_actions.push(create_insert_action(current_insert_text.as_str()));
     }
 
-    let new_command = Command::new(
-        command_chain.get_command().get_name(),
-        new_actions,
-        command_chain.get_command().get_seconds_since_last_action(),
-    );
+    let command = command_chain.get_command();
+    let new_command = command.create_modified_copy(command.get_name(), new_actions);
     CommandChain::new(
         new_command,
         command_chain.get_chain_number(),
@@ -358,7 +760,7 @@ fn should_make_abstract_repeat_representation(command_chain: &CommandChain) -> b
     if actions.len() <= 2 {
         return false;
     }
-    actions.iter().any(|action| action.get_name() == "repeat")
+    actions.iter().any(is_repeat_action)
 }
 
 fn make_abstract_repeat_representation_for(
@@ -370,13 +772,15 @@ fn make_abstract_repeat_representation_for(
     let mut new_name = command_chain.get_command().get_name().to_string();
 
     for action in actions {
-        if action.get_name() == "repeat" {
+        if is_repeat_action(action) {
             instances += 1;
             let mut capture = TalonCapture::new("number_small", instances);
             capture.set_postfix(" - 1");
             new_name.push_str(&format!(" {}", capture.compute_command_component()));
-            let argument = Argument::CaptureArgument(capture);
-            let repeat_action = BasicAction::new("repeat", vec![argument]);
+            // A repeat block action keeps the number of actions in its block.
+            let mut arguments = vec![Argument::CaptureArgument(capture)];
+            arguments.extend_from_slice(action.get_arguments().get(1..).unwrap_or_default());
+            let repeat_action = BasicAction::new(action.get_name(), arguments);
             new_actions.push(repeat_action);
         } else {
             new_actions.push(action.clone());
@@ -393,19 +797,332 @@ fn make_abstract_repeat_representation_for(
     }
 }
 
+/// Every number is replaced by Talon's number capture,
+/// since commands using number_small for some numbers and number for others could not be counted together.
+const NUMBER_CAPTURE_NAME: &str = "number";
+
+/// Splits a key with a repeat count like down:5 into the key and the count.
+fn split_key_repeat_count(key: &str) -> Option<(&str, i32)> {
+    let (key, count) = key.rsplit_once(':')?;
+    if key.is_empty() || count.is_empty() || !count.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Some((key, count.parse().ok()?))
+}
+
+fn is_abstractable_number_argument(action: &BasicAction, argument: &Argument) -> bool {
+    match argument {
+        Argument::IntArgument(number) => *number >= 0 && !is_repeat_action(action),
+        Argument::StringArgument(keys) => {
+            action.get_name() == "key"
+                && keys
+                    .split(' ')
+                    .any(|key| split_key_repeat_count(key).is_some())
+        }
+        _ => false,
+    }
+}
+
+fn count_abstractable_numbers(command_chain: &CommandChain) -> usize {
+    let mut number_of_numbers = 0;
+    for action in command_chain.get_command().get_actions() {
+        for argument in action.get_arguments() {
+            if !is_abstractable_number_argument(action, argument) {
+                continue;
+            }
+            number_of_numbers += match argument {
+                Argument::StringArgument(keys) => keys
+                    .split(' ')
+                    .filter(|key| split_key_repeat_count(key).is_some())
+                    .count(),
+                _ => 1,
+            };
+        }
+    }
+    number_of_numbers
+}
+
+/// Gives every argument replaced by a capture its own instance of the capture and adds it to the command name.
+struct CaptureCreator {
+    instances: HashMap<&'static str, i32>,
+    name: String,
+}
+
+impl CaptureCreator {
+    fn new(command_chain: &CommandChain) -> Self {
+        CaptureCreator {
+            instances: HashMap::new(),
+            name: command_chain.get_command().get_name().to_string(),
+        }
+    }
+
+    fn create(&mut self, capture_name: &'static str) -> TalonCapture {
+        let instance = self.instances.entry(capture_name).or_insert(0);
+        *instance += 1;
+        let capture = TalonCapture::new(capture_name, *instance);
+        self.name
+            .push_str(&format!(" {}", capture.compute_command_component()));
+        capture
+    }
+
+    fn get_number_of_captures(&self) -> u32 {
+        self.instances.values().sum::<i32>() as u32
+    }
+
+    fn create_instantiation(
+        self,
+        command_chain: &CommandChain,
+        new_actions: Vec<BasicAction>,
+    ) -> AbstractCommandInstantiation {
+        let new_command = compute_command_chain_copy_with_new_name_and_actions(
+            command_chain,
+            &self.name,
+            new_actions,
+        );
+        // Every capture is assumed to take a word to say on top of the word for the command itself.
+        let words_saved = compute_number_of_words(command_chain)
+            .saturating_sub(1 + self.get_number_of_captures());
+        AbstractCommandInstantiation {
+            command_chain: new_command,
+            concrete_command: command_chain.clone(),
+            words_saved,
+        }
+    }
+}
+
+fn map_keys(keys: &str, map_key: impl FnMut(&str) -> String) -> String {
+    keys.split(' ')
+        .map(map_key)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Replaces the integer arguments and repeat counts in key strings with number captures,
+/// so that commands differing only in those numbers are counted as the same abstract command.
+/// The numbers that turn out to be the same in every use are put back after counting.
+/// A repeat count in a key string uses the capture through string interpolation like key("down:{number_1}").
+fn make_abstract_number_representation_for(
+    command_chain: &CommandChain,
+) -> Option<AbstractCommandInstantiation> {
+    if count_abstractable_numbers(command_chain) == 0 {
+        return None;
+    }
+    let mut creator = CaptureCreator::new(command_chain);
+    let mut new_actions = Vec::new();
+    for action in command_chain.get_command().get_actions() {
+        let arguments = action
+            .get_arguments()
+            .iter()
+            .map(|argument| {
+                if !is_abstractable_number_argument(action, argument) {
+                    return argument.clone();
+                }
+                match argument {
+                    Argument::IntArgument(_) => {
+                        Argument::CaptureArgument(creator.create(NUMBER_CAPTURE_NAME))
+                    }
+                    Argument::StringArgument(keys) => Argument::StringArgument(map_keys(
+                        keys,
+                        |key| match split_key_repeat_count(key) {
+                            Some((key, _)) => format!(
+                                "{}:{{{}}}",
+                                key,
+                                creator
+                                    .create(NUMBER_CAPTURE_NAME)
+                                    .compute_string_representation()
+                            ),
+                            None => key.to_string(),
+                        },
+                    )),
+                    _ => argument.clone(),
+                }
+            })
+            .collect();
+        new_actions.push(BasicAction::new(action.get_name(), arguments));
+    }
+    Some(creator.create_instantiation(command_chain, new_actions))
+}
+
+/// The instance of the number capture used as the repeat count of a key like down:{number_2}.
+fn find_key_repeat_count_capture_instance(key: &str) -> Option<(&str, i32)> {
+    let (key, count) = key.rsplit_once(':')?;
+    let instance = count
+        .strip_prefix('{')?
+        .strip_suffix('}')?
+        .strip_prefix(NUMBER_CAPTURE_NAME)?
+        .strip_prefix('_')?;
+    Some((key, instance.parse().ok()?))
+}
+
+/// Splits a key like ctrl-a or alt-left:3 into the modifiers with their separators, the key name, and anything after the name.
+fn split_key_name(key: &str) -> (&str, &str, &str) {
+    let name_end = key.rfind(':').unwrap_or(key.len());
+    let name_start = key[..name_end].rfind('-').map_or(0, |index| index + 1);
+    (
+        &key[..name_start],
+        &key[name_start..name_end],
+        &key[name_end..],
+    )
+}
+
+const ARROW_KEYS: [&str; 4] = ["up", "down", "left", "right"];
+const SPECIAL_KEYS: [&str; 11] = [
+    "backspace",
+    "delete",
+    "end",
+    "enter",
+    "escape",
+    "home",
+    "insert",
+    "pagedown",
+    "pageup",
+    "space",
+    "tab",
+];
+
+/// The capture that can say any key of the same class, like user.letter for a to z.
+fn compute_key_capture_name(key_name: &str) -> Option<&'static str> {
+    let mut characters = key_name.chars();
+    let first_character = characters.next()?;
+    let is_single_character = characters.next().is_none();
+    if is_single_character && first_character.is_ascii_lowercase() {
+        Some("user.letter")
+    } else if is_single_character && first_character.is_ascii_digit() {
+        Some("user.number_key")
+    } else if ARROW_KEYS.contains(&key_name) {
+        Some("user.arrow_key")
+    } else if SPECIAL_KEYS.contains(&key_name) {
+        Some("user.special_key")
+    } else if first_character == 'f'
+        && key_name[1..]
+            .parse::<u32>()
+            .is_ok_and(|number| (1..=24).contains(&number))
+    {
+        Some("user.function_key")
+    } else {
+        None
+    }
+}
+
+/// The index of the action and of the key in its key string for every key that can be replaced by a capture.
+fn find_abstractable_keys(command_chain: &CommandChain) -> Vec<(usize, usize)> {
+    let mut keys = Vec::new();
+    for (action_index, action) in command_chain.get_command().get_actions().iter().enumerate() {
+        if action.get_name() != "key" {
+            continue;
+        }
+        if let Some(Argument::StringArgument(key_string)) = action.get_arguments().first() {
+            for (key_index, key) in key_string.split(' ').enumerate() {
+                if compute_key_capture_name(split_key_name(key).1).is_some() {
+                    keys.push((action_index, key_index));
+                }
+            }
+        }
+    }
+    keys
+}
+
+/// Replaces the key name of a key in a key string with a capture for its class of keys while keeping the modifiers,
+/// so that commands like ctrl-a and ctrl-b that differ only in that key are counted as the same abstract command.
+/// The key string uses the capture through string interpolation like key("ctrl-{user.letter_1}").
+fn make_abstract_key_representation_for(
+    command_chain: &CommandChain,
+    action_index: usize,
+    key_index: usize,
+) -> AbstractCommandInstantiation {
+    let mut creator = CaptureCreator::new(command_chain);
+    let mut new_actions = command_chain.get_command().get_actions().clone();
+    let action = &new_actions[action_index];
+    if let Some(Argument::StringArgument(key_string)) = action.get_arguments().first() {
+        let mut index = 0;
+        let new_key_string = map_keys(key_string, |key| {
+            let is_key_to_replace = index == key_index;
+            index += 1;
+            let (modifiers, key_name, suffix) = split_key_name(key);
+            match compute_key_capture_name(key_name) {
+                Some(capture_name) if is_key_to_replace => format!(
+                    "{}{{{}}}{}",
+                    modifiers,
+                    creator.create(capture_name).compute_string_representation(),
+                    suffix
+                ),
+                _ => key.to_string(),
+            }
+        });
+        new_actions[action_index] =
+            BasicAction::new("key", vec![Argument::StringArgument(new_key_string)]);
+    }
+    creator.create_instantiation(command_chain, new_actions)
+}
+
+/// Makes an abstract command for every key that can be replaced by a capture.
+fn make_abstract_key_representations_for(
+    command_chain: &CommandChain,
+) -> Vec<AbstractCommandInstantiation> {
+    find_abstractable_keys(command_chain)
+        .into_iter()
+        .map(|(action_index, key_index)| {
+            make_abstract_key_representation_for(command_chain, action_index, key_index)
+        })
+        .collect()
+}
+
+/// Inserted text longer than this is unlikely to be an item of a small vocabulary like a branch name or log level.
+const MAXIMUM_LIST_ITEM_LENGTH: usize = 30;
+/// The items of a generated list must be used at least this many times on average,
+/// so that only a small vocabulary said again and again becomes a list instead of text that is different every time.
+const MINIMUM_USES_PER_LIST_ITEM: usize = 2;
+
+/// Whether the text could be an item of a small vocabulary, which is a single short word of text.
+fn could_be_list_item(text: &str) -> bool {
+    text.len() <= MAXIMUM_LIST_ITEM_LENGTH
+        && text.chars().any(char::is_alphanumeric)
+        && !text.chars().any(char::is_whitespace)
+}
+
+/// Makes an abstract command for every insert that could be a list item that inserts an item from a generated list instead of its text.
+/// Chains that differ only in that insert are counted as the same abstract command,
+/// and the texts they insert become the items of the list.
+fn make_abstract_list_representations_for(
+    command_chain: &CommandChain,
+) -> Vec<AbstractCommandInstantiation> {
+    if command_chain.get_command().get_actions().len() <= 1 {
+        return Vec::new();
+    }
+    obtain_inserts_from_command_chain(command_chain)
+        .into_iter()
+        .filter(|insert| could_be_list_item(&insert.text))
+        .map(|insert| {
+            let mut creator = CaptureCreator::new(command_chain);
+            let mut new_actions = command_chain.get_command().get_actions().clone();
+            new_actions[insert.index] = BasicAction::new(
+                "insert",
+                vec![Argument::CaptureArgument(
+                    creator.create(GENERATED_LIST_CAPTURE_NAME),
+                )],
+            );
+            creator.create_instantiation(command_chain, new_actions)
+        })
+        .collect()
+}
+
 struct ProseMatch {
     analyzer: TextSeparationAnalyzer,
     name: String,
 }
 
+/// Replaces the prose found in the insert with the given instance of the prose capture.
+/// The command chain can already have prose captures, so the concrete command it came from is given separately.
 fn make_abstract_representation_for_prose_command(
     command_chain: &CommandChain,
+    concrete_command: &CommandChain,
     prose_match: &ProseMatch,
-    insert_to_modify_index: usize,
+    insert: &InsertAction,
+    capture_instance: i32,
 ) -> AbstractCommandInstantiation {
     let analyzer = &prose_match.analyzer;
     let actions = command_chain.get_command().get_actions();
-    let mut new_actions = actions[..insert_to_modify_index].to_vec();
+    let mut new_actions = actions[..insert.index].to_vec();
 
     {
         let text_before = analyzer.compute_text_before_prose();
@@ -414,7 +1131,7 @@ fn make_abstract_representation_for_prose_command(
         }
     }
 
-    let prose_argument = TalonCapture::new("user.text", 1);
+    let prose_argument = TalonCapture::new("user.text", capture_instance);
     new_actions.push(BasicAction::new(
         "user.fire_chicken_auto_generated_command_action_insert_formatted_text",
         vec![
@@ -431,26 +1148,102 @@ fn make_abstract_representation_for_prose_command(
         }
     }
 
-    if insert_to_modify_index + 1 < actions.len() {
-        new_actions.extend_from_slice(&actions[insert_to_modify_index + 1..]);
-    }
+    new_actions.extend_from_slice(&actions[insert.end_index..]);
 
     let new_command = compute_command_chain_copy_with_new_name_and_actions(
         command_chain,
         &prose_match.name,
         new_actions,
     );
-    let words_saved = compute_number_of_words(&new_command) - 2;
+    let words_saved =
+        compute_number_of_words(&new_command).saturating_sub(1 + capture_instance as u32);
     AbstractCommandInstantiation {
         command_chain: new_command,
-        concrete_command: command_chain.clone(),
-        words_saved: words_saved as u32,
+        concrete_command: concrete_command.clone(),
+        words_saved,
     }
 }
 
-struct InsertAction<'a> {
-    text: &'a String,
+struct InsertAction {
+    text: String,
     index: usize,
+    /// The index after the last action typing the text, which is more than one past the index when the text is typed by several actions.
+    end_index: usize,
+    /// When the text is typed by several actions, prose must start before the end of the text of the first action
+    /// and end after the start of the text of the last action so that it is not also found in fewer actions.
+    spanned_text_bounds: Option<(usize, usize)>,
+}
+
+impl InsertAction {
+    fn new(text: &str, index: usize) -> Self {
+        InsertAction {
+            text: text.to_string(),
+            index,
+            end_index: index + 1,
+            spanned_text_bounds: None,
+        }
+    }
+
+    fn does_prose_span_every_action(&self, analyzer: &TextSeparationAnalyzer) -> bool {
+        self.spanned_text_bounds
+            .is_none_or(|(first_action_text_end, last_action_text_start)| {
+                analyzer.compute_text_before_prose().len() < first_action_text_end
+                    && self.text.len() - analyzer.compute_text_after_prose().len()
+                        > last_action_text_start
+            })
+    }
+}
+
+/// The text typed by a key action pressing a single key that types a character, like key("space").
+fn compute_typed_key_text(action: &BasicAction) -> Option<&'static str> {
+    if action.get_name() != "key" {
+        return None;
+    }
+    match action.get_arguments().first() {
+        Some(Argument::StringArgument(key)) => match key.as_str() {
+            "space" => Some(" "),
+            "minus" => Some("-"),
+            "underscore" => Some("_"),
+            "period" | "dot" => Some("."),
+            "comma" => Some(","),
+            "slash" => Some("/"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Finds text typed by several inserts separated only by keys typing characters,
+/// since a spoken phrase can be typed that way and merging adjacent inserts does not join it.
+/// The formatted text typed by a prose capture replaces those keys, so any other action ends the text.
+/// Where another action like pressing enter would have to happen within the prose depends on the words dictated,
+/// so text on each side of it needs a prose capture of its own.
+fn obtain_spanning_inserts_from_actions(actions: &[BasicAction]) -> Vec<InsertAction> {
+    let mut inserts = Vec::new();
+    for (index, action) in actions.iter().enumerate() {
+        if !is_insert(action) {
+            continue;
+        }
+        let first_action_text_end = get_insert_text(action).len();
+        let mut text = get_insert_text(action).clone();
+        for (end_index, next_action) in actions.iter().enumerate().skip(index + 1) {
+            if is_insert(next_action) {
+                let last_action_text_start = text.len();
+                text.push_str(get_insert_text(next_action));
+                inserts.push(InsertAction {
+                    text: text.clone(),
+                    index,
+                    end_index: end_index + 1,
+                    spanned_text_bounds: Some((first_action_text_end, last_action_text_start)),
+                });
+            } else if let Some(typed_text) = compute_typed_key_text(next_action) {
+                text.push_str(typed_text);
+            } else {
+                break;
+            }
+        }
+    }
+    inserts
 }
 
 fn obtain_inserts_from_command_chain(command_chain: &CommandChain) -> Vec<InsertAction> {
@@ -461,11 +1254,7 @@ fn obtain_inserts_from_command_chain(command_chain: &CommandChain) -> Vec<Insert
         .enumerate()
         .filter_map(|(index, action)| {
             if is_insert(action) {
-                let insert_text = get_insert_text(action);
-                Some(InsertAction {
-                    text: insert_text,
-                    index,
-                })
+                Some(InsertAction::new(get_insert_text(action), index))
             } else {
                 None
             }
@@ -473,6 +1262,15 @@ fn obtain_inserts_from_command_chain(command_chain: &CommandChain) -> Vec<Insert
         .collect()
 }
 
+/// The inserts prose can be found in, which include text typed by several inserts.
+fn obtain_prose_inserts_from_command_chain(command_chain: &CommandChain) -> Vec<InsertAction> {
+    let mut inserts = obtain_inserts_from_command_chain(command_chain);
+    inserts.extend(obtain_spanning_inserts_from_actions(
+        command_chain.get_command().get_actions(),
+    ));
+    inserts
+}
+
 fn generate_prose_command_name(words: &[&str], starting_index: usize, prose_size: usize) -> String {
     let mut command_name_parts = words[..starting_index].to_vec();
     command_name_parts.push("<user.text>");
@@ -573,88 +1371,133 @@ fn is_acceptable_abstract_representation(representation: &CommandChain) -> bool
 
 fn make_abstract_prose_representations_for_command_given_insert(
     command_chain: &CommandChain,
+    concrete_command: &CommandChain,
     insert: &InsertAction,
+    capture_instance: i32,
     max_prose_size_to_consider: usize,
-) -> Vec<AbstractCommandInstantiation> {
-    let mut abstract_representations = Vec::new();
+    max_prose_captures: usize,
+    abstract_representations: &mut Vec<AbstractCommandInstantiation>,
+) {
     let prose_matches = find_prose_matches_for_command_given_insert(
         command_chain,
         insert,
         max_prose_size_to_consider,
     );
-    for match_found in prose_matches {
+    // Prose captures spoken one after another could be a single capture, so they must be separated by a spoken word.
+    // Prose typed by several actions must span all of them because prose in fewer actions is found in a shorter insert.
+    for match_found in prose_matches.into_iter().filter(|match_found| {
+        !match_found.name.contains("<user.text> <user.text>")
+            && insert.does_prose_span_every_action(&match_found.analyzer)
+    }) {
         let abstract_representation = make_abstract_representation_for_prose_command(
-            &command_chain,
+            command_chain,
+            concrete_command,
             &match_found,
-            insert.index,
+            insert,
+            capture_instance,
         );
+        if (capture_instance as usize) < max_prose_captures {
+            // Later captures only replace prose after this one so that every combination of captures is made once.
+            let capture_index = insert.index
+                + usize::from(!match_found.analyzer.compute_text_before_prose().is_empty());
+            make_abstract_prose_representations_for_command_given_inserts(
+                &abstract_representation.command_chain,
+                concrete_command,
+                capture_index + 1,
+                capture_instance + 1,
+                max_prose_size_to_consider,
+                max_prose_captures,
+                abstract_representations,
+            );
+        }
         if is_acceptable_abstract_representation(&abstract_representation.command_chain) {
             abstract_representations.push(abstract_representation);
         }
     }
-    abstract_representations
 }
 
+/// Makes abstract commands replacing prose in the inserts starting at the action index with the given capture instance,
+/// along with every abstract command that also replaces prose in later inserts with later instances.
 fn make_abstract_prose_representations_for_command_given_inserts(
     command_chain: &CommandChain,
-    inserts: &[InsertAction],
+    concrete_command: &CommandChain,
+    first_action_index: usize,
+    capture_instance: i32,
     max_prose_size_to_consider: usize,
-) -> Vec<AbstractCommandInstantiation> {
-    let mut abstract_representations = Vec::new();
-    for insert in inserts {
-        let representations_given_insert =
-            make_abstract_prose_representations_for_command_given_insert(
-                command_chain,
-                insert,
-                max_prose_size_to_consider,
-            );
-        abstract_representations.extend(representations_given_insert);
+    max_prose_captures: usize,
+    abstract_representations: &mut Vec<AbstractCommandInstantiation>,
+) {
+    for insert in obtain_prose_inserts_from_command_chain(command_chain)
+        .iter()
+        .filter(|insert| insert.index >= first_action_index)
+    {
+        make_abstract_prose_representations_for_command_given_insert(
+            command_chain,
+            concrete_command,
+            insert,
+            capture_instance,
+            max_prose_size_to_consider,
+            max_prose_captures,
+            abstract_representations,
+        );
     }
-    abstract_representations
 }
 
+/// Makes abstract commands with up to the given number of prose captures, each replacing prose in a different insert.
+/// Every capture allowed multiplies the number of abstract commands made from chains with a lot of dictation.
 pub fn make_abstract_prose_representations_for_command(
     command_chain: &CommandChain,
     max_prose_size_to_consider: usize,
+    max_prose_captures: usize,
 ) -> Vec<AbstractCommandInstantiation> {
-    let inserts = obtain_inserts_from_command_chain(command_chain);
-    if inserts.is_empty() {
-        Vec::new()
-    } else {
-        make_abstract_prose_representations_for_command_given_inserts(
-            command_chain,
-            &inserts,
-            max_prose_size_to_consider,
-        )
-    }
+    let mut abstract_representations = Vec::new();
+    make_abstract_prose_representations_for_command_given_inserts(
+        command_chain,
+        command_chain,
+        0,
+        1,
+        max_prose_size_to_consider,
+        max_prose_captures,
+        &mut abstract_representations,
+    );
+    abstract_representations
 }
 
-fn basic_concrete_command_filter(info: &CommandStatistics) -> bool {
-    info.number_of_words_saved > 0
-        && info.number_of_times_used > 1
-        && (info.number_of_actions as f32 / info.get_average_words_dictated() < 2.0
+fn basic_concrete_command_filter(info: &CommandStatistics, settings: &AnalysisSettings) -> bool {
+    info.number_of_words_saved >= settings.minimum_words_saved
+        && info.number_of_times_used >= settings.minimum_times_used
+        && (info.number_of_actions as f32 / info.get_average_words_dictated()
+            < settings.maximum_actions_per_word
             || info.number_of_actions as f32 * (info.number_of_times_used as f32).sqrt()
                 > info.get_average_words_dictated())
 }
 
-fn basic_abstract_command_filter(info: &PotentialAbstractCommandInformation) -> bool {
-    if info.get_statistics().get_average_words_dictated() < 2.0
-        || info.get_number_of_instantiations() <= 2
-        || info.get_statistics().number_of_words_saved < 1
+fn basic_abstract_command_filter(info: &CommandStatistics, settings: &AnalysisSettings) -> bool {
+    if info.get_average_words_dictated() < settings.abstract_minimum_average_words_dictated
+        || info.get_number_of_instantiations() < settings.abstract_minimum_instantiations
+        || info.number_of_words_saved < settings.minimum_words_saved
+        || (info.uses_generated_list()
+            && (info.get_number_of_instantiations() > settings.abstract_maximum_list_size
+                || info.number_of_times_used
+                    < MINIMUM_USES_PER_LIST_ITEM * info.get_number_of_instantiations()))
     {
         return false;
     }
-    basic_concrete_command_filter(info.get_statistics())
+    if info.has_number_capture_with_one_value() {
+        return false;
+    }
+    basic_concrete_command_filter(info, settings)
 }
 
 fn is_command_after_chain_start_exceeding_time_gap_threshold(
     record_entry: &Command,
     chain_start_index: usize,
     current_chain_index: usize,
+    chain_break_gap_seconds: u32,
 ) -> bool {
     match record_entry.get_seconds_since_last_action() {
         Some(seconds) => {
-            current_chain_index > chain_start_index && seconds > FIVE_MINUTES_IN_SECONDS
+            current_chain_index > chain_start_index && seconds > chain_break_gap_seconds
         }
         None => false,
     }
@@ -664,14 +1507,16 @@ fn should_command_chain_not_cross_entry_at_record_index(
     record: &[Entry],
     chain_start_index: usize,
     current_chain_index: usize,
+    chain_break_gap_seconds: u32,
 ) -> bool {
     let record_entry = &record[current_chain_index];
     match record_entry {
-        Entry::RecordingStart => true,
+        Entry::RecordingStart(_) => true,
         Entry::Command(record_entry) => is_command_after_chain_start_exceeding_time_gap_threshold(
             &record_entry,
             chain_start_index,
             current_chain_index,
+            chain_break_gap_seconds,
         ),
     }
 }
@@ -693,72 +1538,108 @@ fn add_next_record_command_to_chain(record: &[Entry], command_chain: &mut Comman
 fn simplify_command_chain(command_chain: &CommandChain) -> CommandChain {
     let mut simplified_chain = compute_insert_simplified_command_chain(command_chain);
     simplified_chain = compute_repeat_simplified_command_chain(&simplified_chain);
+    simplified_chain = compute_block_repeat_simplified_command_chain(&simplified_chain);
     simplified_chain
 }
 
-pub fn create_abstract_commands(command_chain: &CommandChain) -> Vec<AbstractCommandInstantiation> {
+pub fn create_abstract_commands(
+    command_chain: &CommandChain,
+    settings: &AnalysisSettings,
+) -> Vec<AbstractCommandInstantiation> {
     let mut commands = make_abstract_prose_representations_for_command(
         command_chain,
-        DEFAULT_MAX_PROSE_SIZE_TO_CONSIDER,
+        settings.max_prose_size,
+        settings.max_prose_captures,
     );
     if should_make_abstract_repeat_representation(command_chain) {
         let abstract_repeat_representation = make_abstract_repeat_representation_for(command_chain);
         commands.push(abstract_repeat_representation);
     }
+    commands.extend(make_abstract_number_representation_for(command_chain));
+    commands.extend(make_abstract_key_representations_for(command_chain));
+    commands.extend(make_abstract_list_representations_for(command_chain));
     commands
 }
 
+/// Identifies a potential command. Potential commands are counted separately for every application they are used in.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CommandKey {
+    application: Option<String>,
+    actions: Vec<ActionId>,
+}
+
+impl CommandKey {
+    fn new(command_chain: &CommandChain, interner: &mut ActionInterner) -> Self {
+        let command = command_chain.get_command();
+        CommandKey {
+            application: command.get_context().application.clone(),
+            actions: interner.intern_actions(command.get_actions()),
+        }
+    }
+
+    fn from_statistics(statistics: &CommandStatistics, interner: &mut ActionInterner) -> Self {
+        CommandKey {
+            application: statistics.application.clone(),
+            actions: interner.intern_actions(&statistics.actions),
+        }
+    }
+}
+
 fn process_abstract_command_usage(
-    abstract_commands: &mut HashMap<String, PotentialAbstractCommandInformation>,
+    abstract_commands: &mut HashMap<CommandKey, PotentialAbstractCommandInformation>,
+    interner: &mut ActionInterner,
     instantiation: AbstractCommandInstantiation,
-    representation: String,
 ) {
-    if let Some(info) = abstract_commands.get_mut(&representation) {
+    let key = CommandKey::new(&instantiation.command_chain, interner);
+    if let Some(info) = abstract_commands.get_mut(&key) {
         info.process_usage(instantiation);
     } else {
-        abstract_commands.insert(
-            representation,
-            PotentialAbstractCommandInformation::new(instantiation),
-        );
+        let mut abstract_info = PotentialAbstractCommandInformation::new(instantiation);
+        abstract_info.statistics.application = key.application.clone();
+        abstract_commands.insert(key, abstract_info);
     }
 }
 
 pub fn handle_needed_abstract_commands(
-    abstract_commands: &mut HashMap<String, PotentialAbstractCommandInformation>,
+    abstract_commands: &mut HashMap<CommandKey, PotentialAbstractCommandInformation>,
+    interner: &mut ActionInterner,
     command_chain: &CommandChain,
+    settings: &AnalysisSettings,
 ) {
-    let abstractions = create_abstract_commands(command_chain);
+    let abstractions = create_abstract_commands(command_chain, settings);
     for abstract_command in abstractions {
-        let representation =
-            compute_string_representation_of_chain_actions(&abstract_command.command_chain);
-        process_abstract_command_usage(abstract_commands, abstract_command, representation);
+        process_abstract_command_usage(abstract_commands, interner, abstract_command);
     }
 }
 
 fn process_concrete_command_usage(
-    concrete_commands: &mut HashMap<String, PotentialCommandInformation>,
+    concrete_commands: &mut HashMap<CommandKey, PotentialCommandInformation>,
+    interner: &mut ActionInterner,
     command_chain: &CommandChain,
-    representation: String,
 ) {
-    if let Some(info) = concrete_commands.get_mut(&representation) {
+    let key = CommandKey::new(command_chain, interner);
+    if let Some(info) = concrete_commands.get_mut(&key) {
         info.process_usage(command_chain);
     } else {
         let mut concrete_info =
             PotentialCommandInformation::new(command_chain.get_command().get_actions().clone());
+        concrete_info.statistics.application = key.application.clone();
         concrete_info.process_usage(command_chain);
-        concrete_commands.insert(representation, concrete_info);
+        concrete_commands.insert(key, concrete_info);
     }
 }
 
 fn process_insert_action(
     simplified_command_chain: &CommandChain,
     insert: &InsertAction,
-    abstract_commands: &mut HashMap<String, PotentialAbstractCommandInformation>,
+    abstract_commands: &mut HashMap<CommandKey, PotentialAbstractCommandInformation>,
+    interner: &mut ActionInterner,
+    max_prose_size: usize,
 ) {
     let dictation = simplified_command_chain.get_command().get_name();
     let words: Vec<&str> = dictation.split_whitespace().collect();
     for starting_index in 0..words.len() {
-        let maximum_size = DEFAULT_MAX_PROSE_SIZE_TO_CONSIDER.min(words.len() - starting_index + 1);
+        let maximum_size = max_prose_size.min(words.len() - starting_index + 1);
         for prose_size in 1..maximum_size {
             if let Ok(match_found) = find_prose_match_for_command_given_insert_at_interval(
                 &words,
@@ -767,18 +1648,17 @@ fn process_insert_action(
                 prose_size,
             ) {
                 let abstract_representation = make_abstract_representation_for_prose_command(
+                    &simplified_command_chain,
                     &simplified_command_chain,
                     &match_found,
-                    insert.index,
+                    insert,
+                    1,
                 );
                 if is_acceptable_abstract_representation(&abstract_representation.command_chain) {
-                    let representation = compute_string_representation_of_chain_actions(
-                        &abstract_representation.command_chain,
-                    );
                     process_abstract_command_usage(
                         abstract_commands,
+                        interner,
                         abstract_representation,
-                        representation,
                     );
                 }
             } else {
@@ -798,11 +1678,7 @@ fn create_insert_action_iterator(
         .enumerate()
         .filter_map(|(index, action)| {
             if is_insert(action) {
-                let insert_text = get_insert_text(action);
-                let insert = InsertAction {
-                    text: insert_text,
-                    index,
-                };
+                let insert = InsertAction::new(get_insert_text(action), index);
                 Some(insert)
             } else {
                 None
@@ -810,126 +1686,630 @@ fn create_insert_action_iterator(
         })
 }
 
-fn do_asynchronous_chain_work(
-    start_index: usize,
-    ending_index: usize,
-    record: Arc<Vec<Entry>>,
-) -> (
-    CommandChain,
-    String,
-    Vec<AbstractCommandInstantiation>,
-    Vec<String>,
-) {
-    let mut concrete_chain = CommandChain::empty(start_index);
-    for _index in start_index..=ending_index {
-        add_next_record_command_to_chain(&record, &mut concrete_chain);
+/// The number of entries a chain starting at each index can cover without crossing a recording start or a time gap.
+fn compute_unbroken_chain_sizes(record: &[Entry], chain_break_gap_seconds: u32) -> Vec<usize> {
+    let mut sizes = vec![0; record.len()];
+    for start in (0..record.len()).rev() {
+        if matches!(record[start], Entry::RecordingStart(_)) {
+            continue;
+        }
+        let next_index = start + 1;
+        sizes[start] = if next_index < record.len()
+            && !should_command_chain_not_cross_entry_at_record_index(
+                record,
+                start,
+                next_index,
+                chain_break_gap_seconds,
+            ) {
+            sizes[next_index] + 1
+        } else {
+            1
+        };
     }
-    let simplified_command_chain = simplify_command_chain(&concrete_chain);
-    let abstract_commands = create_abstract_commands(&simplified_command_chain);
-    let abstract_representations = abstract_commands
+    sizes
+}
+
+/// The action with every argument an abstraction could replace with a capture masked,
+/// so that commands that could become the same abstract command have the same shape.
+/// Inserted text, integers, the repeat counts of keys, and keys with a key class capture are masked.
+fn compute_abstractable_action_shape(action: &BasicAction) -> BasicAction {
+    let arguments = action
+        .get_arguments()
         .iter()
-        .map(|a| compute_string_representation_of_chain_actions(&a.command_chain))
+        .map(|argument| match argument {
+            Argument::StringArgument(_) if is_insert(action) => {
+                Argument::StringArgument(String::new())
+            }
+            Argument::StringArgument(keys) if action.get_name() == "key" => {
+                Argument::StringArgument(map_keys(keys, |key| {
+                    let (modifiers, key_name, suffix) = split_key_name(key);
+                    let key_name = compute_key_capture_name(key_name).unwrap_or(key_name);
+                    let suffix = if split_key_repeat_count(key).is_some() {
+                        ":#"
+                    } else {
+                        suffix
+                    };
+                    format!("{}{}{}", modifiers, key_name, suffix)
+                }))
+            }
+            Argument::IntArgument(_) => Argument::IntArgument(0),
+            other => other.clone(),
+        })
         .collect();
-    let concrete_representation =
-        compute_string_representation_of_chain_actions(&simplified_command_chain);
-    (
-        simplified_command_chain,
-        concrete_representation,
-        abstract_commands,
-        abstract_representations,
-    )
+    BasicAction::new(action.get_name(), arguments)
 }
 
-fn compute_chain_size(record: &Arc<Vec<Entry>>, chain: usize, chain_target: usize) -> usize {
-    let mut num_targets = 0;
-    for chain_ending_index in chain..chain_target {
-        if should_command_chain_not_cross_entry_at_record_index(record, chain, chain_ending_index) {
-            break;
+fn compute_abstractable_command_shape(command: &Command) -> Vec<BasicAction> {
+    command
+        .get_actions()
+        .iter()
+        .map(compute_abstractable_action_shape)
+        .collect()
+}
+
+/// Gives every command in the record with the same representation of its actions the same number.
+/// Recording starts get no number.
+fn compute_command_identifiers(
+    record: &[Entry],
+    compute_representation: impl Fn(&Command) -> Vec<BasicAction>,
+) -> Vec<Option<usize>> {
+    let mut interner = ActionInterner::new();
+    let mut identifiers: HashMap<Vec<ActionId>, usize> = HashMap::new();
+    record
+        .iter()
+        .map(|entry| match entry {
+            Entry::Command(command) => {
+                let representation = interner.intern_actions(&compute_representation(command));
+                let number_of_identifiers = identifiers.len();
+                Some(
+                    *identifiers
+                        .entry(representation)
+                        .or_insert(number_of_identifiers),
+                )
+            }
+            Entry::RecordingStart(_) => None,
+        })
+        .collect()
+}
+
+/// Computes how many chains starting at each index of the record should be processed.
+/// Chains grow one command at a time and a chain is only extended while it occurs at least twice in the record,
+/// because an extension of a chain that occurs once can only occur once itself.
+/// Chains are compared by the shapes of their commands so that chains whose concrete commands differ each time
+/// are still extended while they could become the same abstract command.
+/// This finds arbitrarily long repeated patterns without processing every long chain,
+/// but a session repeating a short pattern many times still has a chain at every start reaching nearly to its end,
+/// so without a maximum chain size the work grows with the square of the length of such sessions.
+pub fn compute_chain_sizes(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    chain_break_gap_seconds: u32,
+) -> Vec<usize> {
+    let size_limits: Vec<usize> = compute_unbroken_chain_sizes(record, chain_break_gap_seconds)
+        .into_iter()
+        .map(|size| max_chain_size.map_or(size, |max_chain_size| size.min(max_chain_size)))
+        .collect();
+    let command_identifiers =
+        compute_command_identifiers(record, compute_abstractable_command_shape);
+    let mut chain_sizes: Vec<usize> = size_limits.iter().map(|limit| (*limit).min(1)).collect();
+    // The chains of the current size paired with a number identifying the commands in them.
+    let mut chains: Vec<(usize, usize)> = (0..record.len())
+        .filter(|start| chain_sizes[*start] == 1)
+        .filter_map(|start| command_identifiers[start].map(|identifier| (start, identifier)))
+        .collect();
+    let mut chain_size = 1;
+    while !chains.is_empty() {
+        let mut occurrences: HashMap<usize, usize> = HashMap::new();
+        for (_, identifier) in &chains {
+            *occurrences.entry(*identifier).or_insert(0) += 1;
         }
-        num_targets += 1;
+        let mut extended_identifiers: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut extended_chains = Vec::new();
+        for (start, identifier) in chains {
+            if occurrences[&identifier] < 2 || size_limits[start] <= chain_size {
+                continue;
+            }
+            let Some(next_command_identifier) = command_identifiers[start + chain_size] else {
+                continue;
+            };
+            let number_of_identifiers = extended_identifiers.len();
+            let extended_identifier = *extended_identifiers
+                .entry((identifier, next_command_identifier))
+                .or_insert(number_of_identifiers);
+            chain_sizes[start] = chain_size + 1;
+            extended_chains.push((start, extended_identifier));
+        }
+        chains = extended_chains;
+        chain_size += 1;
     }
-    num_targets
+    chain_sizes
 }
 
-fn create_commands(record: Vec<Entry>, max_chain_size: usize) -> Vec<CommandStatistics> {
-    let record = Arc::new(record);
-    let mut concrete_commands: HashMap<String, PotentialCommandInformation> = HashMap::new();
-    let mut abstract_commands: HashMap<String, PotentialAbstractCommandInformation> =
-        HashMap::new();
-    let mut pool: pool::ThreadPool<(
-        CommandChain,
-        String,
-        Vec<AbstractCommandInstantiation>,
-        Vec<String>,
-    )> = pool::ThreadPool::create_with_max_threads();
-    let record_length = record.len();
-    for chain in 0..record.len() {
-        println!("Processing chain {}/{}", chain + 1, record_length);
-        let chain_size =
-            compute_chain_size(&record, chain, record_length.min(chain + max_chain_size));
-        for chain_ending_index in chain..chain + chain_size {
-            let record_clone = Arc::clone(&record);
-            pool.execute(move || {
-                do_asynchronous_chain_work(chain, chain_ending_index, record_clone)
-            });
-        }
-        let results = pool.join();
-        for (
-            concrete_chain,
-            concrete_representation,
-            mut abstractions,
-            mut abstract_representations,
-        ) in results
-        {
-            process_concrete_command_usage(
-                &mut concrete_commands,
-                &concrete_chain,
-                concrete_representation,
+/// The commands of an occurrence of a gapped pattern, which may skip commands between them.
+struct GappedOccurrence {
+    indices: Vec<usize>,
+    number_of_skipped_commands: usize,
+    /// A number identifying the commands in the occurrence.
+    pattern: usize,
+}
+
+/// The seconds from the start of the record to when each entry was spoken, counting unknown time differences as 0.
+fn compute_elapsed_seconds(record: &[Entry]) -> Vec<u64> {
+    let mut elapsed_seconds = 0;
+    record
+        .iter()
+        .map(|entry| {
+            if let Entry::Command(command) = entry {
+                elapsed_seconds += command.get_seconds_since_last_action().unwrap_or(0) as u64;
+            }
+            elapsed_seconds
+        })
+        .collect()
+}
+
+/// Finds the occurrences of patterns of two or more commands that skip at least one command in between.
+/// An occurrence can skip at most gapped_maximum_skipped_commands commands in total, must be spoken within gapped_window_seconds,
+/// and never crosses a recording start or a time gap.
+/// Like compute_chain_sizes, patterns grow one command at a time and are only extended while they occur at least twice,
+/// and only the occurrence of a pattern skipping the fewest commands is kept at each start.
+/// Occurrences sharing a command with an occurrence of the same pattern that skips nothing are left out,
+/// since that command is already counted by the ordinary command.
+fn find_gapped_occurrences(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    settings: &AnalysisSettings,
+) -> Vec<Vec<usize>> {
+    let size_limits = compute_unbroken_chain_sizes(record, settings.chain_break_gap_seconds);
+    let command_identifiers =
+        compute_command_identifiers(record, |command| command.get_actions().clone());
+    let elapsed_seconds = compute_elapsed_seconds(record);
+    let mut occurrences: Vec<GappedOccurrence> = (0..record.len())
+        .filter_map(|start| {
+            command_identifiers[start].map(|identifier| GappedOccurrence {
+                indices: vec![start],
+                number_of_skipped_commands: 0,
+                pattern: identifier,
+            })
+        })
+        .collect();
+    let mut gapped_occurrences = Vec::new();
+    let mut pattern_size = 1;
+    while !occurrences.is_empty()
+        && max_chain_size.is_none_or(|max_chain_size| pattern_size < max_chain_size)
+    {
+        let mut number_of_occurrences: HashMap<usize, usize> = HashMap::new();
+        for occurrence in &occurrences {
+            *number_of_occurrences.entry(occurrence.pattern).or_insert(0) += 1;
+        }
+        let mut extended_patterns: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut extended_occurrences = Vec::new();
+        for occurrence in occurrences {
+            if number_of_occurrences[&occurrence.pattern] < 2 {
+                continue;
+            }
+            let start = occurrence.indices[0];
+            let last_index = occurrence.indices[occurrence.indices.len() - 1];
+            let number_of_skippable_commands =
+                settings.gapped_maximum_skipped_commands - occurrence.number_of_skipped_commands;
+            let mut occurrence_extended_patterns = Vec::new();
+            for number_of_skipped_commands in 0..=number_of_skippable_commands {
+                let next_index = last_index + 1 + number_of_skipped_commands;
+                if next_index >= start + size_limits[start]
+                    || elapsed_seconds[next_index] - elapsed_seconds[start]
+                        > settings.gapped_window_seconds as u64
+                {
+                    break;
+                }
+                let Some(next_command_identifier) = command_identifiers[next_index] else {
+                    break;
+                };
+                let number_of_patterns = extended_patterns.len();
+                let extended_pattern = *extended_patterns
+                    .entry((occurrence.pattern, next_command_identifier))
+                    .or_insert(number_of_patterns);
+                // The first extension found with each pattern skips the fewest commands.
+                if occurrence_extended_patterns.contains(&extended_pattern) {
+                    continue;
+                }
+                occurrence_extended_patterns.push(extended_pattern);
+                let mut indices = occurrence.indices.clone();
+                indices.push(next_index);
+                extended_occurrences.push(GappedOccurrence {
+                    indices,
+                    number_of_skipped_commands: occurrence.number_of_skipped_commands
+                        + number_of_skipped_commands,
+                    pattern: extended_pattern,
+                });
+            }
+        }
+        let mut contiguous_indices: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for occurrence in &extended_occurrences {
+            if occurrence.number_of_skipped_commands == 0 {
+                contiguous_indices
+                    .entry(occurrence.pattern)
+                    .or_default()
+                    .extend(&occurrence.indices);
+            }
+        }
+        gapped_occurrences.extend(
+            extended_occurrences
+                .iter()
+                .filter(|occurrence| {
+                    occurrence.number_of_skipped_commands > 0
+                        && contiguous_indices
+                            .get(&occurrence.pattern)
+                            .is_none_or(|indices| {
+                                !occurrence
+                                    .indices
+                                    .iter()
+                                    .any(|index| indices.contains(index))
+                            })
+                })
+                .map(|occurrence| occurrence.indices.clone()),
+        );
+        occurrences = extended_occurrences;
+        pattern_size += 1;
+    }
+    gapped_occurrences
+}
+
+/// Merges the commands at the indices into a chain covering every entry from the first index to the last.
+fn create_gapped_command_chain(record: &[Entry], indices: &[usize]) -> CommandChain {
+    let mut command_chain = CommandChain::empty(indices[0]);
+    for index in indices {
+        if let Entry::Command(command) = &record[*index] {
+            command_chain.append_command(command.clone());
+        }
+    }
+    let size = indices[indices.len() - 1] - indices[0] + 1;
+    CommandChain::new(command_chain.get_command().clone(), indices[0], size)
+}
+
+/// Finds potential commands made of commands spoken close together with other commands between them, which are dropped.
+/// Finds nothing unless gapped_maximum_skipped_commands is positive.
+/// The usages of a gapped command are counted without overlapping each other like the usages of any other command,
+/// but only usages that skip commands are counted because the others are counted as ordinary commands.
+pub fn create_gapped_commands(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    settings: &AnalysisSettings,
+) -> Vec<CommandStatistics> {
+    if settings.gapped_maximum_skipped_commands == 0 {
+        return Vec::new();
+    }
+    let mut occurrences = find_gapped_occurrences(record, max_chain_size, settings);
+    occurrences.sort_by_key(|indices| (indices[0], indices[indices.len() - 1]));
+    let mut gapped_commands = HashMap::new();
+    let mut interner = ActionInterner::new();
+    for indices in occurrences {
+        let command_chain = create_gapped_command_chain(record, &indices);
+        process_concrete_command_usage(
+            &mut gapped_commands,
+            &mut interner,
+            &simplify_command_chain(&command_chain),
+        );
+    }
+    let mut statistics = Vec::new();
+    let gapped_statistics = merge_statistics_across_applications(
+        gapped_commands
+            .into_iter()
+            .map(|(key, info)| (key, info.move_statistics())),
+    );
+    for mut info in gapped_statistics {
+        info.compute_concrete_number_of_words_saved();
+        info.is_gapped = true;
+        if basic_concrete_command_filter(&info, settings) {
+            statistics.push(info);
+        }
+    }
+    statistics
+}
+
+/// The usage of every potential command found in the chains processed so far.
+pub struct CommandCounts {
+    concrete_commands: HashMap<CommandKey, PotentialCommandInformation>,
+    abstract_commands: HashMap<CommandKey, PotentialAbstractCommandInformation>,
+    /// Interns the actions of every potential command so that they can be identified quickly.
+    interner: ActionInterner,
+}
+
+impl CommandCounts {
+    pub fn new() -> Self {
+        CommandCounts {
+            concrete_commands: HashMap::new(),
+            abstract_commands: HashMap::new(),
+            interner: ActionInterner::new(),
+        }
+    }
+
+    /// The keys are not written because they can be computed again from the statistics.
+    pub fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_length(self.concrete_commands.len());
+        for info in self.concrete_commands.values() {
+            info.statistics.encode(encoder);
+            info.chain_handler.encode(encoder);
+        }
+        encoder.write_length(self.abstract_commands.len());
+        for info in self.abstract_commands.values() {
+            info.statistics.encode(encoder);
+            info.chain_handler.encode(encoder);
+        }
+    }
+
+    pub fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        let mut counts = CommandCounts::new();
+        for _ in 0..decoder.read_length()? {
+            let info = PotentialCommandInformation {
+                statistics: CommandStatistics::decode(decoder)?,
+                chain_handler: ChainHandler::decode(decoder)?,
+            };
+            let key = CommandKey::from_statistics(info.get_statistics(), &mut counts.interner);
+            counts.concrete_commands.insert(key, info);
+        }
+        for _ in 0..decoder.read_length()? {
+            let info = PotentialAbstractCommandInformation {
+                statistics: CommandStatistics::decode(decoder)?,
+                chain_handler: ChainHandler::decode(decoder)?,
+            };
+            let key = CommandKey::from_statistics(info.get_statistics(), &mut counts.interner);
+            counts.abstract_commands.insert(key, info);
+        }
+        Ok(counts)
+    }
+
+    /// Adds counts made from chains that do not overlap any of the chains counted here.
+    fn add_independent_counts(&mut self, other: CommandCounts) {
+        for (_, info) in other.concrete_commands {
+            let key = CommandKey::from_statistics(info.get_statistics(), &mut self.interner);
+            match self.concrete_commands.entry(key) {
+                hash_map::Entry::Occupied(mut existing) => {
+                    existing.get_mut().add_independent_usage(info)
+                }
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(info);
+                }
+            }
+        }
+        for (_, info) in other.abstract_commands {
+            let key = CommandKey::from_statistics(info.get_statistics(), &mut self.interner);
+            match self.abstract_commands.entry(key) {
+                hash_map::Entry::Occupied(mut existing) => {
+                    existing.get_mut().add_independent_usage(info)
+                }
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(info);
+                }
+            }
+        }
+    }
+}
+
+/// Processes every chain starting at the index, from the shortest to the longest.
+fn count_chains_starting_at(
+    record: &[Entry],
+    start: usize,
+    chain_size: usize,
+    settings: &AnalysisSettings,
+    counts: &mut CommandCounts,
+) {
+    let mut concrete_chain = CommandChain::empty(start);
+    for _ in 0..chain_size {
+        add_next_record_command_to_chain(record, &mut concrete_chain);
+        let simplified_command_chain = simplify_command_chain(&concrete_chain);
+        let mut abstractions = create_abstract_commands(&simplified_command_chain, settings);
+        process_concrete_command_usage(
+            &mut counts.concrete_commands,
+            &mut counts.interner,
+            &simplified_command_chain,
+        );
+        while let Some(instantiation) = abstractions.pop() {
+            process_abstract_command_usage(
+                &mut counts.abstract_commands,
+                &mut counts.interner,
+                instantiation,
             );
-            while !abstractions.is_empty() {
-                let index = abstractions.len() - 1;
-                let instantiation = abstractions.remove(index);
-                let representation = abstract_representations.remove(index);
-                process_abstract_command_usage(
-                    &mut abstract_commands,
-                    instantiation,
-                    representation,
+        }
+    }
+}
+
+/// Splits the chain starts into consecutive parts with about the target number of chains each.
+/// A part only ends where no chain starting before it, including chains counted before the range, reaches past it,
+/// so every part can be counted separately without changing which usages the chain handlers count.
+fn split_chain_starts_into_independent_parts(
+    chain_starts: Range<usize>,
+    chain_sizes: &[usize],
+    target_number_of_chains: usize,
+) -> Vec<Range<usize>> {
+    let mut furthest_chain_end = chain_sizes[..chain_starts.start]
+        .iter()
+        .enumerate()
+        .map(|(start, size)| start + size)
+        .max()
+        .unwrap_or(0);
+    let mut parts = Vec::new();
+    let mut part_start = chain_starts.start;
+    let mut number_of_chains_in_part = 0;
+    for start in chain_starts.clone() {
+        if furthest_chain_end <= start && number_of_chains_in_part >= target_number_of_chains {
+            parts.push(part_start..start);
+            part_start = start;
+            number_of_chains_in_part = 0;
+        }
+        number_of_chains_in_part += chain_sizes[start];
+        furthest_chain_end = furthest_chain_end.max(start + chain_sizes[start]);
+    }
+    if part_start < chain_starts.end {
+        parts.push(part_start..chain_starts.end);
+    }
+    parts
+}
+
+/// Each worker gets several parts of the record so that workers given parts with slow chains do not hold up the rest.
+const PARTS_PER_WORKER: usize = 4;
+
+/// Processes the chains starting at each index in the range, using the chain sizes from compute_chain_sizes.
+/// The chain handlers count usages correctly only if the chains are processed in order of their starting index,
+/// so the range is split into parts that no chain crosses and the workers count whole parts in order into their own counts.
+/// The first part is counted directly into the given counts because chains counted before the range may reach into it.
+pub fn count_command_chains(
+    record: &Arc<Vec<Entry>>,
+    chain_starts: Range<usize>,
+    chain_sizes: &[usize],
+    settings: &AnalysisSettings,
+    counts: &mut CommandCounts,
+) {
+    let mut pool: pool::ThreadPool<CommandCounts> = pool::ThreadPool::create_with_max_threads();
+    let total_number_of_chains: usize = chain_sizes[chain_starts.clone()].iter().sum();
+    let target_number_of_chains =
+        (total_number_of_chains / (pool.compute_number_of_workers() * PARTS_PER_WORKER)).max(1);
+    let mut parts = split_chain_starts_into_independent_parts(
+        chain_starts,
+        chain_sizes,
+        target_number_of_chains,
+    )
+    .into_iter();
+    let first_part = parts.next();
+    let number_of_parts = parts.len();
+    let part_settings = *settings;
+    let record_length = record.len();
+    for part in parts {
+        let record_clone = Arc::clone(record);
+        let part_chain_sizes = chain_sizes[part.clone()].to_vec();
+        pool.execute(move || {
+            let mut part_counts = CommandCounts::new();
+            for (start, chain_size) in part.clone().zip(part_chain_sizes) {
+                count_chains_starting_at(
+                    &record_clone,
+                    start,
+                    chain_size,
+                    &part_settings,
+                    &mut part_counts,
                 );
             }
+            println!(
+                "Processed chains starting at {} to {} of {}",
+                part.start + 1,
+                part.end,
+                record_length
+            );
+            part_counts
+        });
+    }
+    if let Some(first_part) = first_part {
+        for start in first_part.clone() {
+            count_chains_starting_at(record, start, chain_sizes[start], settings, counts);
         }
-
-        // let insert_iterator = create_insert_action_iterator(&simplified_command_chain);
-        // insert_iterator.for_each(|insert| {
-        //     process_insert_action(&simplified_command_chain, &insert, &mut abstract_commands);
-        // });
-        // if should_make_abstract_repeat_representation(&simplified_command_chain) {
-        //     let abstract_repeat_representation =
-        //         make_abstract_repeat_representation_for(&simplified_command_chain);
-        //     process_abstract_command_usage(
-        //         &mut abstract_commands,
-        //         abstract_repeat_representation,
-        //     );
-        // }
+        println!(
+            "Processed chains starting at {} to {} of {}",
+            first_part.start + 1,
+            first_part.end,
+            record_length
+        );
+    }
+    if number_of_parts > 0 {
+        let part_counts = pool.reduce(|mut counts, other_counts| {
+            counts.add_independent_counts(other_counts);
+            counts
+        });
+        counts.add_independent_counts(part_counts);
     }
-    for info in concrete_commands.values_mut() {
-        info.compute_number_of_words_saved();
+}
+
+/// The index of the first chain start whose chains could reach entries appended after the record.
+/// The chains starting before this index end at a recording start, a time gap, or the maximum chain size,
+/// so appending to the record can only change which of them are processed through compute_chain_sizes.
+pub fn compute_first_chain_start_affected_by_appending(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    chain_break_gap_seconds: u32,
+) -> usize {
+    let record_length = record.len();
+    compute_unbroken_chain_sizes(record, chain_break_gap_seconds)
+        .iter()
+        .enumerate()
+        .position(|(start, size)| {
+            start + size == record_length
+                && max_chain_size
+                    .is_none_or(|max_chain_size| record_length - start < max_chain_size)
+        })
+        .unwrap_or(record_length)
+}
+
+/// Combines the statistics of each potential command counted in different applications.
+/// A potential command used in only one application stays limited to that application,
+/// while one used in several applications or outside of any known application is made available everywhere.
+fn merge_statistics_across_applications(
+    statistics: impl Iterator<Item = (CommandKey, CommandStatistics)>,
+) -> Vec<CommandStatistics> {
+    let mut merged_statistics: HashMap<Vec<ActionId>, CommandStatistics> = HashMap::new();
+    for (key, statistics) in statistics {
+        if let Some(existing_statistics) = merged_statistics.get_mut(&key.actions) {
+            existing_statistics.merge(statistics);
+        } else {
+            merged_statistics.insert(key.actions, statistics);
+        }
     }
+    merged_statistics.into_values().collect()
+}
+
+pub fn create_commands_from_counts(
+    counts: CommandCounts,
+    settings: &AnalysisSettings,
+) -> Vec<CommandStatistics> {
+    let CommandCounts {
+        concrete_commands,
+        abstract_commands,
+        ..
+    } = counts;
     let mut statistics = Vec::new();
-    concrete_commands.values().for_each(|info| {
-        if basic_concrete_command_filter(info.get_statistics()) {
-            statistics.push(info.get_statistics().clone());
+    let concrete_statistics = merge_statistics_across_applications(
+        concrete_commands
+            .into_iter()
+            .map(|(key, info)| (key, info.move_statistics())),
+    );
+    for mut info in concrete_statistics {
+        info.compute_concrete_number_of_words_saved();
+        if basic_concrete_command_filter(&info, settings) {
+            statistics.push(info);
         }
-    });
-    abstract_commands.values().for_each(|info| {
-        if basic_abstract_command_filter(info) {
-            let abstract_info = info.get_statistics().clone();
-            statistics.push(abstract_info);
+    }
+    let abstract_statistics = merge_statistics_across_applications(
+        abstract_commands
+            .into_iter()
+            .map(|(key, info)| (key, info.statistics)),
+    );
+    for mut info in abstract_statistics {
+        info.keep_numbers_that_never_vary();
+        if basic_abstract_command_filter(&info, settings) {
+            statistics.push(info);
         }
-    });
+    }
 
     statistics
 }
 
+fn create_commands(
+    record: Vec<Entry>,
+    max_chain_size: Option<usize>,
+    settings: &AnalysisSettings,
+) -> Vec<CommandStatistics> {
+    let gapped_commands = create_gapped_commands(&record, max_chain_size, settings);
+    let record = Arc::new(record);
+    let chain_sizes =
+        compute_chain_sizes(&record, max_chain_size, settings.chain_break_gap_seconds);
+    let mut counts = CommandCounts::new();
+    count_command_chains(
+        &record,
+        0..record.len(),
+        &chain_sizes,
+        settings,
+        &mut counts,
+    );
+    let mut commands = create_commands_from_counts(counts, settings);
+    commands.extend(gapped_commands);
+    commands
+}
+
 pub fn compare_information(a: &CommandStatistics, b: &CommandStatistics) -> std::cmp::Ordering {
     b.number_of_times_used.cmp(&a.number_of_times_used)
 }
@@ -940,8 +2320,736 @@ pub fn create_sorted_info(commands: &mut Vec<CommandStatistics>) {
 
 pub fn compute_recommendations_from_record(
     record: Vec<Entry>,
-    max_chain_size: usize,
+    max_chain_size: Option<usize>,
+    settings: &AnalysisSettings,
 ) -> Vec<CommandStatistics> {
-    let recommendations = create_commands(record, max_chain_size);
+    let recommendations = create_commands(record, max_chain_size, settings);
     recommendations
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recommendation_scoring::filter_out_recommendations_redundant_smaller_commands;
+
+    fn create_command(name: &str, key: &str, application: &str) -> Entry {
+        let mut command = Command::new(
+            name,
+            vec![BasicAction::new(
+                "key",
+                vec![Argument::StringArgument(String::from(key))],
+            )],
+            None,
+        );
+        command.set_context(CommandContext {
+            application: Some(String::from(application)),
+            ..CommandContext::default()
+        });
+        Entry::Command(command)
+    }
+
+    fn find_command_using_key<'a>(
+        recommendations: &'a [CommandStatistics],
+        key: &str,
+    ) -> &'a CommandStatistics {
+        let actions = vec![BasicAction::new(
+            "key",
+            vec![Argument::StringArgument(String::from(key))],
+        )];
+        recommendations
+            .iter()
+            .find(|statistics| statistics.actions == actions)
+            .expect("Expected a recommendation using the key")
+    }
+
+    #[test]
+    fn test_commands_are_limited_to_their_only_application() {
+        let mut record = Vec::new();
+        for _ in 0..3 {
+            record.push(Entry::RecordingStart(None));
+            record.push(create_command("save the file now", "ctrl-s", "Code"));
+            record.push(Entry::RecordingStart(None));
+            record.push(create_command("open a new tab", "ctrl-t", "Code"));
+            record.push(Entry::RecordingStart(None));
+            record.push(create_command("open a new tab", "ctrl-t", "Firefox"));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, Some(1), &AnalysisSettings::default());
+        let save = find_command_using_key(&recommendations, "ctrl-s");
+        assert_eq!(save.application, Some(String::from("Code")));
+        assert_eq!(save.number_of_times_used, 3);
+        let new_tab = find_command_using_key(&recommendations, "ctrl-t");
+        assert_eq!(new_tab.application, None);
+        assert_eq!(new_tab.number_of_times_used, 6);
+        assert_eq!(new_tab.contexts.len(), 2);
+    }
+
+    #[test]
+    fn test_chains_only_grow_while_they_repeat() {
+        let mut record = vec![Entry::RecordingStart(None)];
+        // These keys have no key class capture, so chains of them are only extended when the keys repeat.
+        for key in ["mute", "play", "stop", "mute", "play", "next", "prev"] {
+            record.push(create_command(key, key, "Code"));
+        }
+        // Only the chain of mute and play repeats, so only chains starting with it are extended past it.
+        assert_eq!(
+            compute_chain_sizes(&record, None, FIVE_MINUTES_IN_SECONDS),
+            vec![0, 3, 2, 1, 3, 2, 1, 1]
+        );
+        assert_eq!(
+            compute_chain_sizes(&record, Some(2), FIVE_MINUTES_IN_SECONDS),
+            vec![0, 2, 2, 1, 2, 2, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_chains_grow_while_their_abstract_commands_repeat() {
+        let mut record = Vec::new();
+        for word in ["alpha", "bravo", "charlie", "delta", "echo"] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                &format!("word {}", word),
+                vec![create_insert_action(word)],
+                None,
+            )));
+            record.push(create_command("press enter", "enter", "Code"));
+        }
+        for max_chain_size in [None, Some(2)] {
+            let recommendations = compute_recommendations_from_record(
+                record.clone(),
+                max_chain_size,
+                &AnalysisSettings::default(),
+            );
+            let abstract_command = recommendations
+                .iter()
+                .find(|statistics| {
+                    statistics.actions
+                        == vec![
+                            BasicAction::new(
+                                "user.fire_chicken_auto_generated_command_action_insert_formatted_text",
+                                vec![
+                                    Argument::CaptureArgument(TalonCapture::new("user.text", 1)),
+                                    Argument::StringArgument(String::from("lower")),
+                                    Argument::StringArgument(String::new()),
+                                ],
+                            ),
+                            BasicAction::new(
+                                "key",
+                                vec![Argument::StringArgument(String::from("enter"))],
+                            ),
+                        ]
+                })
+                .expect("Expected the dictated word and enter to become an abstract command");
+            assert_eq!(abstract_command.number_of_times_used, 5);
+            assert_eq!(abstract_command.get_number_of_instantiations(), 5);
+        }
+    }
+
+    #[test]
+    fn test_long_repeated_chains_are_found_without_a_maximum() {
+        let keys: Vec<String> = (0..30).map(|index| format!("f{}", index)).collect();
+        let mut record = Vec::new();
+        for _ in 0..2 {
+            record.push(Entry::RecordingStart(None));
+            for key in &keys {
+                record.push(create_command(&format!("press {}", key), key, "Code"));
+            }
+        }
+        let chain_sizes = compute_chain_sizes(&record, None, FIVE_MINUTES_IN_SECONDS);
+        assert_eq!(chain_sizes[1], keys.len());
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        assert!(
+            recommendations
+                .iter()
+                .any(|statistics| statistics.actions.len() == keys.len()
+                    && statistics.number_of_times_used == 2)
+        );
+    }
+
+    #[test]
+    fn test_counting_in_parts_matches_counting_in_order() {
+        let mut record = Vec::new();
+        for session in 0..6 {
+            record.push(Entry::RecordingStart(None));
+            for key in ["a", "b", "a", "b", "c", "a", "b"].iter().skip(session % 3) {
+                record.push(create_command(&format!("press {}", key), key, "Code"));
+            }
+        }
+        let record = Arc::new(record);
+        let settings = AnalysisSettings::default();
+        let chain_sizes = compute_chain_sizes(&record, Some(3), settings.chain_break_gap_seconds);
+        let parts = split_chain_starts_into_independent_parts(1..record.len(), &chain_sizes, 1);
+        assert!(parts.len() > 1);
+        for part in &parts[1..] {
+            assert!((0..part.start).all(|start| start + chain_sizes[start] <= part.start));
+        }
+        let summarize = |counts: &CommandCounts| {
+            let mut summary: Vec<(String, usize, usize, usize)> = counts
+                .concrete_commands
+                .values()
+                .map(|info| (info.get_statistics(), 0))
+                .chain(
+                    counts
+                        .abstract_commands
+                        .values()
+                        .map(|info| (info.get_statistics(), 1)),
+                )
+                .map(|(statistics, kind)| {
+                    (
+                        compute_string_representation_of_actions(&statistics.actions),
+                        kind,
+                        statistics.number_of_times_used,
+                        statistics.get_number_of_instantiations(),
+                    )
+                })
+                .collect();
+            summary.sort();
+            summary
+        };
+        let mut sequential_counts = CommandCounts::new();
+        for (start, chain_size) in chain_sizes.iter().enumerate() {
+            count_chains_starting_at(
+                &record,
+                start,
+                *chain_size,
+                &settings,
+                &mut sequential_counts,
+            );
+        }
+        let mut counts = CommandCounts::new();
+        count_command_chains(&record, 0..5, &chain_sizes, &settings, &mut counts);
+        count_command_chains(
+            &record,
+            5..record.len(),
+            &chain_sizes,
+            &settings,
+            &mut counts,
+        );
+        assert_eq!(summarize(&counts), summarize(&sequential_counts));
+    }
+
+    #[test]
+    fn test_numbers_are_abstracted_into_captures() {
+        let mut record = Vec::new();
+        for line in [12, 40, 7, 150] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                "go to line then press down a few times",
+                vec![
+                    BasicAction::new("edit.line_goto", vec![Argument::IntArgument(line)]),
+                    BasicAction::new(
+                        "key",
+                        vec![Argument::StringArgument(format!("down:{}", line % 5 + 1))],
+                    ),
+                ],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| {
+                statistics.actions
+                    == vec![
+                        BasicAction::new(
+                            "edit.line_goto",
+                            vec![Argument::CaptureArgument(TalonCapture::new("number", 1))],
+                        ),
+                        BasicAction::new(
+                            "key",
+                            vec![Argument::StringArgument(String::from("down:{number_2}"))],
+                        ),
+                    ]
+            })
+            .expect("Expected the numbers to be replaced by captures");
+        assert_eq!(abstract_command.number_of_times_used, 4);
+        assert_eq!(abstract_command.get_number_of_instantiations(), 4);
+    }
+
+    #[test]
+    fn test_only_varying_numbers_of_single_actions_are_abstracted() {
+        let mut record = Vec::new();
+        for amount in [3, 5, 8, 12] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                "scroll down by this many lines please",
+                vec![BasicAction::new(
+                    "user.mouse_scroll",
+                    vec![Argument::IntArgument(0), Argument::IntArgument(amount)],
+                )],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_commands: Vec<&Vec<BasicAction>> = recommendations
+            .iter()
+            .filter(|statistics| statistics.get_number_of_instantiations() > 0)
+            .map(|statistics| &statistics.actions)
+            .collect();
+        assert_eq!(
+            abstract_commands,
+            vec![&vec![BasicAction::new(
+                "user.mouse_scroll",
+                vec![
+                    Argument::IntArgument(0),
+                    Argument::CaptureArgument(TalonCapture::new("number", 1)),
+                ],
+            )]]
+        );
+    }
+
+    #[test]
+    fn test_numbers_that_never_vary_are_kept_among_many_numbers() {
+        let mut record = Vec::new();
+        for amount in [3, 5, 8, 12] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                "move the mouse over and scroll down a little",
+                vec![
+                    BasicAction::new(
+                        "mouse_move",
+                        vec![Argument::IntArgument(100), Argument::IntArgument(200)],
+                    ),
+                    BasicAction::new(
+                        "user.mouse_scroll",
+                        vec![Argument::IntArgument(0), Argument::IntArgument(amount)],
+                    ),
+                    BasicAction::new(
+                        "key",
+                        vec![Argument::StringArgument(format!(
+                            "down:2 up:{}",
+                            amount % 3 + 1
+                        ))],
+                    ),
+                ],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_commands: Vec<&Vec<BasicAction>> = recommendations
+            .iter()
+            .filter(|statistics| statistics.get_number_of_instantiations() > 0)
+            .map(|statistics| &statistics.actions)
+            .collect();
+        assert_eq!(
+            abstract_commands,
+            vec![&vec![
+                BasicAction::new(
+                    "mouse_move",
+                    vec![Argument::IntArgument(100), Argument::IntArgument(200)],
+                ),
+                BasicAction::new(
+                    "user.mouse_scroll",
+                    vec![
+                        Argument::IntArgument(0),
+                        Argument::CaptureArgument(TalonCapture::new("number", 1)),
+                    ],
+                ),
+                BasicAction::new(
+                    "key",
+                    vec![Argument::StringArgument(String::from(
+                        "down:2 up:{number_2}"
+                    ))],
+                ),
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_keys_are_abstracted_into_key_class_captures() {
+        assert_eq!(split_key_name("ctrl-shift-a"), ("ctrl-shift-", "a", ""));
+        assert_eq!(split_key_name("alt-left:3"), ("alt-", "left", ":3"));
+        assert_eq!(compute_key_capture_name("f12"), Some("user.function_key"));
+        assert_eq!(compute_key_capture_name("7"), Some("user.number_key"));
+        assert_eq!(compute_key_capture_name("ctrl"), None);
+        let create_key_action =
+            |keys: &str| BasicAction::new("key", vec![Argument::StringArgument(keys.to_string())]);
+        let mut record = Vec::new();
+        for direction in ["left", "right", "up", "down"] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                "move the window over and confirm",
+                vec![
+                    create_key_action(&format!("super-{}", direction)),
+                    create_key_action("enter"),
+                ],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| {
+                statistics.actions
+                    == vec![
+                        create_key_action("super-{user.arrow_key_1}"),
+                        create_key_action("enter"),
+                    ]
+            })
+            .expect("Expected the arrow key to be replaced by a capture");
+        assert_eq!(abstract_command.number_of_times_used, 4);
+        assert_eq!(abstract_command.get_number_of_instantiations(), 4);
+    }
+
+    #[test]
+    fn test_keys_of_single_key_commands_are_abstracted() {
+        let create_key_action =
+            |keys: &str| BasicAction::new("key", vec![Argument::StringArgument(keys.to_string())]);
+        let mut record = Vec::new();
+        for letter in ["a", "b", "c", "a"] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                &format!("press control {}", letter),
+                vec![create_key_action(&format!("ctrl-{}", letter))],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| {
+                statistics.actions == vec![create_key_action("ctrl-{user.letter_1}")]
+            })
+            .expect("Expected the letter of a single key action to be replaced by a capture");
+        assert_eq!(abstract_command.number_of_times_used, 4);
+        assert_eq!(abstract_command.get_number_of_instantiations(), 3);
+    }
+
+    #[test]
+    fn test_inserts_from_a_small_set_become_generated_list_items() {
+        let mut record = Vec::new();
+        for branch in ["main", "develop", "release", "main", "develop", "main"] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                "check out the usual branch",
+                vec![
+                    BasicAction::new("user.git_checkout", Vec::new()),
+                    create_insert_action(branch),
+                    BasicAction::new("key", vec![Argument::StringArgument(String::from("enter"))]),
+                ],
+                None,
+            )));
+        }
+        let mut recommendations =
+            compute_recommendations_from_record(record.clone(), None, &AnalysisSettings::default());
+        let position = recommendations
+            .iter()
+            .position(|statistics| statistics.uses_generated_list())
+            .expect("Expected a command inserting an item from a generated list");
+        let list_command = recommendations.swap_remove(position);
+        assert_eq!(list_command.number_of_times_used, 6);
+        assert_eq!(
+            list_command.compute_generated_list_values(),
+            vec!["develop", "main", "release"]
+        );
+        let different_every_time: Vec<Entry> = record
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| match entry {
+                Entry::Command(command) => {
+                    let mut actions = command.get_actions().clone();
+                    actions[1] = create_insert_action(&format!("branch-{}", index));
+                    Entry::Command(command.create_modified_copy(command.get_name(), actions))
+                }
+                Entry::RecordingStart(_) => Entry::RecordingStart(None),
+            })
+            .collect();
+        assert!(
+            !compute_recommendations_from_record(
+                different_every_time,
+                None,
+                &AnalysisSettings::default()
+            )
+            .iter()
+            .any(|statistics| statistics.uses_generated_list())
+        );
+        let settings = AnalysisSettings {
+            abstract_maximum_list_size: 2,
+            ..AnalysisSettings::default()
+        };
+        assert!(
+            !compute_recommendations_from_record(record, None, &settings)
+                .iter()
+                .any(|statistics| statistics.uses_generated_list())
+        );
+    }
+
+    #[test]
+    fn test_abstract_commands_can_have_several_prose_captures() {
+        let create_formatted_text_action = |instance| {
+            BasicAction::new(
+                "user.fire_chicken_auto_generated_command_action_insert_formatted_text",
+                vec![
+                    Argument::CaptureArgument(TalonCapture::new("user.text", instance)),
+                    Argument::StringArgument(String::from("lower")),
+                    Argument::StringArgument(String::new()),
+                ],
+            )
+        };
+        let mut record = Vec::new();
+        for (name, parameter) in [("foo", "bar"), ("alpha", "beta"), ("gamma", "delta")] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                &format!("define function {} with {}", name, parameter),
+                vec![
+                    BasicAction::new("edit.line_end", Vec::new()),
+                    create_insert_action(&format!("def {}({}):", name, parameter)),
+                ],
+                None,
+            )));
+        }
+        let two_capture_actions = vec![
+            BasicAction::new("edit.line_end", Vec::new()),
+            create_insert_action("def "),
+            create_formatted_text_action(1),
+            create_insert_action("("),
+            create_formatted_text_action(2),
+            create_insert_action("):"),
+        ];
+        let settings = AnalysisSettings {
+            max_prose_captures: 1,
+            ..AnalysisSettings::default()
+        };
+        let recommendations = compute_recommendations_from_record(record.clone(), None, &settings);
+        assert!(
+            recommendations
+                .iter()
+                .all(|statistics| statistics.actions != two_capture_actions)
+        );
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| statistics.actions == two_capture_actions)
+            .expect("Expected both the name and the parameter to be replaced by prose captures");
+        assert_eq!(abstract_command.number_of_times_used, 3);
+        let instantiation_set = abstract_command.instantiation_set.as_ref().unwrap();
+        assert_eq!(instantiation_set.get_size(), 3);
+        assert!(instantiation_set.contains(&[
+            BasicAction::new("edit.line_end", Vec::new()),
+            create_insert_action("def alpha(beta):"),
+        ]));
+    }
+
+    #[test]
+    fn test_prose_can_span_inserts_separated_by_typed_keys() {
+        let mut record = Vec::new();
+        for (first_word, second_word) in [("hello", "world"), ("good", "morning"), ("see", "you")] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(Command::new(
+                &format!("type {} {} and submit", first_word, second_word),
+                vec![
+                    create_insert_action(first_word),
+                    BasicAction::new("key", vec![Argument::StringArgument(String::from("space"))]),
+                    create_insert_action(second_word),
+                    BasicAction::new("key", vec![Argument::StringArgument(String::from("enter"))]),
+                ],
+                None,
+            )));
+        }
+        let recommendations =
+            compute_recommendations_from_record(record.clone(), None, &AnalysisSettings::default());
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| {
+                statistics.actions
+                    == vec![
+                        BasicAction::new(
+                            "user.fire_chicken_auto_generated_command_action_insert_formatted_text",
+                            vec![
+                                Argument::CaptureArgument(TalonCapture::new("user.text", 1)),
+                                Argument::StringArgument(String::from("lower")),
+                                Argument::StringArgument(String::from(" ")),
+                            ],
+                        ),
+                        BasicAction::new(
+                            "key",
+                            vec![Argument::StringArgument(String::from("enter"))],
+                        ),
+                    ]
+            })
+            .expect("Expected the prose typed by both inserts to be replaced by a capture");
+        assert_eq!(abstract_command.number_of_times_used, 3);
+        assert_eq!(abstract_command.get_number_of_instantiations(), 3);
+        let Entry::Command(command) = &record[1] else {
+            panic!("Expected a command");
+        };
+        let chain = CommandChain::new(command.clone(), 1, 1);
+        assert_eq!(make_abstract_list_representations_for(&chain).len(), 2);
+    }
+
+    #[test]
+    fn test_repeated_blocks_of_actions_are_collapsed_and_abstracted() {
+        let create_key_action =
+            |key: &str| BasicAction::new("key", vec![Argument::StringArgument(key.to_string())]);
+        let create_command_for_lines = |number_of_lines| {
+            let mut actions = vec![create_key_action("home")];
+            for _ in 0..number_of_lines {
+                actions.push(create_key_action("down"));
+                actions.push(create_key_action("end"));
+            }
+            Command::new("go to the end of the next few lines", actions, None)
+        };
+        let mut record = Vec::new();
+        for number_of_lines in [3, 4, 5] {
+            record.push(Entry::RecordingStart(None));
+            record.push(Entry::Command(create_command_for_lines(number_of_lines)));
+        }
+        let chain = CommandChain::new(create_command_for_lines(3), 1, 1);
+        assert_eq!(
+            simplify_command_chain(&chain).get_command().get_actions(),
+            &vec![
+                create_key_action("home"),
+                create_key_action("down"),
+                create_key_action("end"),
+                create_repeat_block_action(2, 2),
+            ]
+        );
+        let recommendations =
+            compute_recommendations_from_record(record, None, &AnalysisSettings::default());
+        let mut capture = TalonCapture::new("number_small", 1);
+        capture.set_postfix(" - 1");
+        let abstract_command = recommendations
+            .iter()
+            .find(|statistics| {
+                statistics.actions
+                    == vec![
+                        create_key_action("home"),
+                        create_key_action("down"),
+                        create_key_action("end"),
+                        BasicAction::new(
+                            REPEAT_BLOCK_ACTION_NAME,
+                            vec![
+                                Argument::CaptureArgument(capture.clone()),
+                                Argument::IntArgument(2),
+                            ],
+                        ),
+                    ]
+            })
+            .expect("Expected the number of repetitions of the block to be replaced by a capture");
+        assert_eq!(abstract_command.number_of_times_used, 3);
+        assert_eq!(abstract_command.get_number_of_instantiations(), 3);
+    }
+
+    #[test]
+    fn test_gapped_commands_skip_commands_spoken_in_between() {
+        let create_key_command = |name: &str, key: &str, seconds_since_last_action| {
+            let action = BasicAction::new("key", vec![Argument::StringArgument(key.to_string())]);
+            Entry::Command(Command::new(
+                name,
+                vec![action],
+                Some(seconds_since_last_action),
+            ))
+        };
+        let mut record = Vec::new();
+        for (in_between, seconds_before_tests) in [
+            (vec!["left"], 5),
+            (vec!["up"], 5),
+            (vec!["right", "down"], 5),
+            (vec!["home"], 100),
+        ] {
+            record.push(Entry::RecordingStart(None));
+            record.push(create_key_command("save the file", "ctrl-s", 0));
+            for key in in_between {
+                record.push(create_key_command("press a key", key, 1));
+            }
+            record.push(create_key_command(
+                "run the tests",
+                "f5",
+                seconds_before_tests,
+            ));
+        }
+        let settings = AnalysisSettings {
+            gapped_maximum_skipped_commands: 1,
+            ..AnalysisSettings::default()
+        };
+        let save_then_test = vec![
+            BasicAction::new(
+                "key",
+                vec![Argument::StringArgument(String::from("ctrl-s"))],
+            ),
+            BasicAction::new("key", vec![Argument::StringArgument(String::from("f5"))]),
+        ];
+        let recommendations = compute_recommendations_from_record(record.clone(), None, &settings);
+        let gapped_command = recommendations
+            .iter()
+            .find(|statistics| statistics.actions == save_then_test)
+            .expect("Expected the commands around the skipped command to be recommended");
+        assert!(gapped_command.is_gapped);
+        assert_eq!(gapped_command.number_of_times_used, 2);
+        assert_eq!(gapped_command.number_of_words_saved, 10);
+        let recommendations =
+            compute_recommendations_from_record(record.clone(), None, &AnalysisSettings::default());
+        assert!(
+            recommendations
+                .iter()
+                .all(|statistics| !statistics.is_gapped && statistics.actions != save_then_test)
+        );
+
+        for _ in 0..3 {
+            record.push(Entry::RecordingStart(None));
+            record.push(create_key_command("save the file", "ctrl-s", 0));
+            record.push(create_key_command("run the tests", "f5", 5));
+        }
+        let recommendations = compute_recommendations_from_record(record, None, &settings);
+        let find_save_then_test = |recommendations: &[CommandStatistics]| {
+            let mut commands: Vec<(bool, usize)> = recommendations
+                .iter()
+                .filter(|statistics| statistics.actions == save_then_test)
+                .map(|statistics| (statistics.is_gapped, statistics.number_of_times_used))
+                .collect();
+            commands.sort();
+            commands
+        };
+        assert_eq!(
+            find_save_then_test(&recommendations),
+            vec![(false, 3), (true, 2)]
+        );
+        let filtered_recommendations =
+            filter_out_recommendations_redundant_smaller_commands(recommendations);
+        assert_eq!(
+            find_save_then_test(&filtered_recommendations),
+            vec![(false, 3), (true, 2)]
+        );
+    }
+
+    #[test]
+    fn test_gapped_occurrences_sharing_commands_with_contiguous_ones_are_left_out() {
+        let create_key_command = |name: &str, key: &str| {
+            let action = BasicAction::new("key", vec![Argument::StringArgument(key.to_string())]);
+            Entry::Command(Command::new(name, vec![action], Some(1)))
+        };
+        let mut record = Vec::new();
+        for _ in 0..3 {
+            record.push(Entry::RecordingStart(None));
+            record.push(create_key_command("save the file", "ctrl-s"));
+            record.push(create_key_command("press left", "left"));
+            record.push(create_key_command("save the file", "ctrl-s"));
+            record.push(create_key_command("run the tests", "f5"));
+        }
+        let settings = AnalysisSettings {
+            gapped_maximum_skipped_commands: 2,
+            ..AnalysisSettings::default()
+        };
+        let save_then_test = vec![
+            BasicAction::new(
+                "key",
+                vec![Argument::StringArgument(String::from("ctrl-s"))],
+            ),
+            BasicAction::new("key", vec![Argument::StringArgument(String::from("f5"))]),
+        ];
+        let recommendations = compute_recommendations_from_record(record, None, &settings);
+        let save_then_test_commands: Vec<(bool, usize)> = recommendations
+            .iter()
+            .filter(|statistics| statistics.actions == save_then_test)
+            .map(|statistics| (statistics.is_gapped, statistics.number_of_times_used))
+            .collect();
+        assert_eq!(save_then_test_commands, vec![(false, 3)]);
+    }
+}
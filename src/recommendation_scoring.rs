@@ -1,26 +1,23 @@
+use crate::action_interning::{ActionId, ActionInterner};
 use crate::action_records::BasicAction;
 use crate::action_utilities::*;
+use crate::analysis_settings::AnalysisSettings;
 use crate::monte_carlo_tree_search::perform_monte_carlo_tree_search;
 use crate::pool;
-use crate::recommendation_generation::{
-    CommandStatistics, compute_string_representation_of_actions,
-};
+use crate::recommendation_generation::CommandStatistics;
 use std::sync::{Arc, RwLock};
 use std::{collections::HashMap, collections::HashSet};
 
+/// Counts the recommendations including each action, indexed by the interned action.
 fn compute_number_of_commands_including_action(
-    recommendations: &Vec<CommandStatistics>,
-) -> HashMap<String, usize> {
-    let mut result = HashMap::new();
-    for recommendation in recommendations {
-        let mut unique_actions = HashSet::new();
-        for action in &recommendation.actions {
-            let action_string = action.to_json();
-            unique_actions.insert(action_string);
-        }
+    action_identifiers: &[Vec<ActionId>],
+    number_of_actions: usize,
+) -> Vec<usize> {
+    let mut result = vec![0; number_of_actions];
+    for identifiers in action_identifiers {
+        let unique_actions: HashSet<&ActionId> = identifiers.iter().collect();
         for unique_action in unique_actions {
-            let count = result.entry(unique_action).or_insert(0);
-            *count += 1;
+            result[*unique_action as usize] += 1;
         }
     }
     result
@@ -71,11 +68,12 @@ fn compute_max_nonidentical_prefix_or_suffix_similarity(
 
 fn score_recommendations_weighting_by_inverse_action_frequency(
     recommendations: &Vec<CommandStatistics>,
-    num_commands_including_action: &HashMap<String, usize>,
+    action_identifiers: &[Vec<ActionId>],
+    num_commands_including_action: &[usize],
     single_inserts: &HashSet<String>,
 ) -> f64 {
     let mut score = 0.0;
-    for recommendation in recommendations {
+    for (recommendation, identifiers) in recommendations.iter().zip(action_identifiers) {
         let actions = &recommendation.actions;
         if is_insert_only_actions(actions) && single_inserts.len() > 1 {
             let inserted_text = get_insert_text_from_insert_only_actions(actions);
@@ -85,10 +83,8 @@ fn score_recommendations_weighting_by_inverse_action_frequency(
             score += weight * recommendation.number_of_words_saved as f64;
         } else {
             let mut weight = 0.0;
-            for action in actions {
-                let representation = action.to_json();
-                weight +=
-                    1.0 / (*num_commands_including_action.get(&representation).unwrap()) as f64;
+            for identifier in identifiers {
+                weight += 1.0 / num_commands_including_action[*identifier as usize] as f64;
             }
             weight /= actions.len() as f64;
             score += weight * recommendation.number_of_words_saved as f64;
@@ -98,11 +94,17 @@ fn score_recommendations_weighting_by_inverse_action_frequency(
 }
 
 pub fn compute_heuristic_recommendation_score(recommendations: &Vec<CommandStatistics>) -> f64 {
+    let mut interner = ActionInterner::new();
+    let action_identifiers: Vec<Vec<ActionId>> = recommendations
+        .iter()
+        .map(|recommendation| interner.intern_actions(&recommendation.actions))
+        .collect();
     let num_commands_including_action =
-        compute_number_of_commands_including_action(recommendations);
+        compute_number_of_commands_including_action(&action_identifiers, interner.get_size());
     let single_inserts = compute_single_inserts_from_commands(recommendations);
     score_recommendations_weighting_by_inverse_action_frequency(
         recommendations,
+        &action_identifiers,
         &num_commands_including_action,
         &single_inserts,
     )
@@ -232,12 +234,18 @@ fn compute_string_subsequences(text: &str) -> Vec<String> {
     subsequences
 }
 
-fn append_insert_subsequences(collection: &mut Vec<String>, action: &BasicAction) {
+/// Appends the sequence of one insert action for every substring of the inserted text,
+/// skipping inserts that no recommendation uses.
+fn append_insert_subsequences(
+    collection: &mut Vec<Vec<ActionId>>,
+    action: &BasicAction,
+    interner: &ActionInterner,
+) {
     let inserted_text = get_insert_text(action);
     for s in compute_string_subsequences(inserted_text) {
-        let action = create_insert_action(&s);
-        let rep = action.to_json();
-        collection.push(rep);
+        if let Some(identifier) = interner.find(&create_insert_action(&s)) {
+            collection.push(vec![identifier]);
+        }
     }
 }
 
@@ -269,42 +277,61 @@ fn compute_ending_inserts(is_insert: bool, action: &BasicAction) -> Vec<String>
     ending_inserts
 }
 
+/// Finds the identifiers of the insert actions for the texts, using None for inserts that no recommendation uses.
+fn find_insert_identifiers(texts: &[String], interner: &ActionInterner) -> Vec<Option<ActionId>> {
+    texts
+        .iter()
+        .map(|text| interner.find(&create_insert_action(text)))
+        .collect()
+}
+
 fn append_insert_subsequences_with_multiple_actions(
-    collection: &mut Vec<String>,
-    sub_actions: &[BasicAction],
+    collection: &mut Vec<Vec<ActionId>>,
+    sub_actions: &[ActionId],
+    interner: &ActionInterner,
 ) {
     // This assumes that there is more than one action
-    let is_first_action_insert = is_insert(&sub_actions[0]);
+    let first_action = interner.get_action(sub_actions[0]);
+    let is_first_action_insert = is_insert(first_action);
     let last_index = sub_actions.len() - 1;
-    let is_last_action_insert = is_insert(&sub_actions[last_index]);
-    let mut beginning_inserts = compute_beginning_inserts(is_first_action_insert, &sub_actions[0]);
-    let mut ending_inserts =
-        compute_ending_inserts(is_last_action_insert, &sub_actions[last_index]);
+    let last_action = interner.get_action(sub_actions[last_index]);
+    let is_last_action_insert = is_insert(last_action);
+    let beginning_inserts = find_insert_identifiers(
+        &compute_beginning_inserts(is_first_action_insert, first_action),
+        interner,
+    );
+    let ending_inserts = find_insert_identifiers(
+        &compute_ending_inserts(is_last_action_insert, last_action),
+        interner,
+    );
 
     if is_first_action_insert && !is_last_action_insert {
-        let other_representation = compute_string_representation_of_actions(&sub_actions[1..]);
-        for s in &beginning_inserts {
-            let s_rep = create_insert_action(s).to_json();
-            collection.push(format!("{}{}", s_rep, other_representation));
+        for b in beginning_inserts.into_iter().flatten() {
+            let mut subsequence = vec![b];
+            subsequence.extend_from_slice(&sub_actions[1..]);
+            collection.push(subsequence);
         }
     } else if is_last_action_insert && !is_first_action_insert {
-        let other_representation =
-            compute_string_representation_of_actions(&sub_actions[..last_index]);
-        for s in &ending_inserts {
-            let s_rep = create_insert_action(s).to_json();
-            collection.push(format!("{}{}", other_representation, s_rep));
+        for e in ending_inserts.into_iter().flatten() {
+            let mut subsequence = sub_actions[..last_index].to_vec();
+            subsequence.push(e);
+            collection.push(subsequence);
         }
     } else if is_first_action_insert && is_last_action_insert {
-        beginning_inserts.push(get_insert_text(&sub_actions[0]).to_string());
-        ending_inserts.push(get_insert_text(&sub_actions[last_index]).to_string());
-        let other_representation =
-            compute_string_representation_of_actions(&sub_actions[1..last_index]);
+        let mut beginning_inserts = beginning_inserts;
+        let mut ending_inserts = ending_inserts;
+        beginning_inserts.push(Some(sub_actions[0]));
+        ending_inserts.push(Some(sub_actions[last_index]));
+        let middle = &sub_actions[1..last_index];
         for (i, b) in beginning_inserts.iter().enumerate() {
-            let b_rep = create_insert_action(b).to_json();
             for (j, e) in ending_inserts.iter().enumerate() {
-                if i != beginning_inserts.len() - 1 || j != ending_inserts.len() - 1 {
-                    let e_rep = create_insert_action(e).to_json();
-                    collection.push(format!("{}{}{}", b_rep, other_representation, e_rep));
+                if (i != beginning_inserts.len() - 1 || j != ending_inserts.len() - 1)
+                    && let (Some(b), Some(e)) = (b, e)
+                {
+                    let mut subsequence = vec![*b];
+                    subsequence.extend_from_slice(middle);
+                    subsequence.push(*e);
+                    collection.push(subsequence);
                 }
             }
         }
@@ -312,39 +339,53 @@ fn append_insert_subsequences_with_multiple_actions(
 }
 
 fn compute_action_subsequences_including_leading_and_trailing_inserts(
-    actions: &[BasicAction],
-) -> Vec<String> {
+    actions: &[ActionId],
+    interner: &ActionInterner,
+) -> Vec<Vec<ActionId>> {
     let mut subsequences = Vec::new();
     for i in 0..actions.len() {
         for j in i..actions.len() {
             let sub_actions = &actions[i..=j];
             if sub_actions.len() < actions.len() {
-                subsequences.push(compute_string_representation_of_actions(sub_actions));
+                subsequences.push(sub_actions.to_vec());
             }
-            if sub_actions.len() == 1 && is_insert(&sub_actions[0]) {
-                append_insert_subsequences(&mut subsequences, &sub_actions[0]);
+            if sub_actions.len() == 1 && is_insert(interner.get_action(sub_actions[0])) {
+                append_insert_subsequences(
+                    &mut subsequences,
+                    interner.get_action(sub_actions[0]),
+                    interner,
+                );
             } else if sub_actions.len() > 1 {
-                append_insert_subsequences_with_multiple_actions(&mut subsequences, sub_actions);
+                append_insert_subsequences_with_multiple_actions(
+                    &mut subsequences,
+                    sub_actions,
+                    interner,
+                );
             }
         }
     }
     subsequences
 }
 
+/// Identifies a recommendation by whether it is gapped and its actions,
+/// since a gapped command and an ordinary command with the same actions are counted separately.
+type RecommendationKey = (bool, Vec<ActionId>);
+
 fn find_redundant_commands_from_command(
-    sequence: String,
-    sequences: &HashMap<String, CommandStatistics>,
-) -> Vec<String> {
+    key: RecommendationKey,
+    sequences: &HashMap<RecommendationKey, CommandStatistics>,
+    interner: &ActionInterner,
+) -> Vec<RecommendationKey> {
     let mut redundant = Vec::new();
-    let command = sequences
-        .get(&sequence)
-        .expect("Command not found in sequences");
+    let command = sequences.get(&key).expect("Command not found in sequences");
+    let (is_gapped, sequence) = key;
     for sub_sequence in
-        compute_action_subsequences_including_leading_and_trailing_inserts(&command.actions)
+        compute_action_subsequences_including_leading_and_trailing_inserts(&sequence, interner)
     {
-        if let Some(existing_command) = sequences.get(&sub_sequence) {
+        let sub_key = (is_gapped, sub_sequence);
+        if let Some(existing_command) = sequences.get(&sub_key) {
             if existing_command.number_of_times_used == command.number_of_times_used {
-                redundant.push(sub_sequence);
+                redundant.push(sub_key);
             }
         }
     }
@@ -356,18 +397,25 @@ pub fn filter_out_recommendations_redundant_smaller_commands(
 ) -> Vec<CommandStatistics> {
     // For every command that is a shorter version of another command but is not used any more times: remove it
     let mut pool = pool::ThreadPool::create_with_max_threads();
-    let mut action_sequences: HashMap<String, CommandStatistics> = HashMap::new();
+    let mut interner = ActionInterner::new();
+    let mut action_sequences: HashMap<RecommendationKey, CommandStatistics> = HashMap::new();
     for command in recommendations.into_iter() {
-        let representation = compute_string_representation_of_actions(&command.actions);
-        action_sequences.insert(representation, command);
+        let identifiers = interner.intern_actions(&command.actions);
+        action_sequences.insert((command.is_gapped, identifiers), command);
     }
     let action_sequences = Arc::new(RwLock::new(action_sequences));
+    let interner = Arc::new(interner);
 
     for sequence in action_sequences.read().unwrap().keys() {
         let action_sequences_clone = Arc::clone(&action_sequences);
+        let interner_clone = Arc::clone(&interner);
         let sequence = sequence.clone();
         pool.execute(move || {
-            find_redundant_commands_from_command(sequence, &action_sequences_clone.read().unwrap())
+            find_redundant_commands_from_command(
+                sequence,
+                &action_sequences_clone.read().unwrap(),
+                &interner_clone,
+            )
         });
     }
     let results = pool.join_unordered();
@@ -386,6 +434,7 @@ pub fn find_best(
     max_number_of_recommendations: usize,
     use_tree_search: bool,
     is_verbose: bool,
+    settings: &AnalysisSettings,
 ) -> Vec<CommandStatistics> {
     if max_number_of_recommendations >= recommendations.len() {
         return recommendations.clone();
@@ -401,6 +450,7 @@ pub fn find_best(
             start,
             max_number_of_recommendations,
             is_verbose,
+            settings,
         );
         if is_verbose {
             println!(
@@ -419,6 +469,8 @@ pub fn find_best(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::action_records::Argument;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_can_find_greedy_best() {
@@ -427,33 +479,45 @@ mod tests {
                 number_of_times_used: 50,
                 number_of_words_saved: 1000,
                 instantiation_set: None,
+                contexts: BTreeMap::new(),
+                application: None,
                 actions: vec![create_insert_action("arbitrary")],
                 number_of_actions: 1,
                 total_number_of_words_dictated: 100,
+                is_gapped: false,
             },
             CommandStatistics {
                 number_of_times_used: 20,
                 number_of_words_saved: 40,
                 instantiation_set: None,
+                contexts: BTreeMap::new(),
+                application: None,
                 actions: vec![create_insert_action("text")],
                 number_of_actions: 1,
                 total_number_of_words_dictated: 20,
+                is_gapped: false,
             },
             CommandStatistics {
                 number_of_times_used: 5000,
                 number_of_words_saved: 20000,
                 instantiation_set: None,
+                contexts: BTreeMap::new(),
+                application: None,
                 actions: vec![create_insert_action("mod tests {\n]")],
                 number_of_actions: 1,
                 total_number_of_words_dictated: 400,
+                is_gapped: false,
             },
             CommandStatistics {
                 number_of_times_used: 20,
                 number_of_words_saved: 30,
                 instantiation_set: None,
+                contexts: BTreeMap::new(),
+                application: None,
                 actions: vec![create_insert_action("tarp2")],
                 number_of_actions: 1,
                 total_number_of_words_dictated: 20,
+                is_gapped: false,
             },
         ];
         let filtered =
@@ -463,4 +527,32 @@ mod tests {
         assert_eq!(best[1].actions, recommendations[0].actions);
         assert_eq!(best[0].actions, recommendations[2].actions);
     }
+
+    fn create_statistics(
+        actions: Vec<BasicAction>,
+        number_of_times_used: usize,
+    ) -> CommandStatistics {
+        let mut statistics = CommandStatistics::new(actions);
+        statistics.number_of_times_used = number_of_times_used;
+        statistics
+    }
+
+    #[test]
+    fn test_filters_out_smaller_commands_used_as_often() {
+        let enter = BasicAction::new("key", vec![Argument::StringArgument(String::from("enter"))]);
+        let recommendations = vec![
+            create_statistics(vec![create_insert_action("hello world"), enter.clone()], 3),
+            create_statistics(vec![create_insert_action("world"), enter.clone()], 3),
+            create_statistics(vec![create_insert_action("hello")], 3),
+            create_statistics(vec![create_insert_action("hello world")], 4),
+            create_statistics(vec![enter.clone()], 5),
+        ];
+        let mut remaining: Vec<usize> =
+            filter_out_recommendations_redundant_smaller_commands(recommendations)
+                .iter()
+                .map(|statistics| statistics.number_of_times_used)
+                .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![3, 4, 5]);
+    }
 }
@@ -0,0 +1,426 @@
+// Defines code for replacing the words in a record with pseudonyms so that it can be shared.
+// Every distinct word is consistently replaced with a pseudonym of the same length and case pattern,
+// so the words spoken for a command still match the text it inserted.
+
+use crate::action_records::{Argument, BasicAction, Command, Entry};
+use crate::random::RandomNumberGenerator;
+use std::collections::{HashMap, HashSet};
+
+const INSERT_ACTION_NAME: &str = "insert";
+const MAXIMUM_ATTEMPTS_TO_FIND_UNUSED_PSEUDONYM: usize = 100;
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyz";
+const DIGITS: &str = "0123456789";
+
+#[derive(Clone, Copy, PartialEq)]
+enum TokenKind {
+    Letters,
+    Digits,
+    Other,
+}
+
+fn compute_token_kind(character: char) -> TokenKind {
+    if character.is_alphabetic() {
+        TokenKind::Letters
+    } else if character.is_ascii_digit() {
+        TokenKind::Digits
+    } else {
+        TokenKind::Other
+    }
+}
+
+/// Splits text into runs of letters, runs of digits, and runs of everything else.
+fn split_into_tokens(text: &str) -> Vec<(TokenKind, String)> {
+    let mut tokens: Vec<(TokenKind, String)> = Vec::new();
+    for character in text.chars() {
+        let kind = compute_token_kind(character);
+        match tokens.last_mut() {
+            Some((last_kind, token)) if *last_kind == kind => token.push(character),
+            _ => tokens.push((kind, character.to_string())),
+        }
+    }
+    tokens
+}
+
+/// Splits words like camelCase and HTTPServer into the words they are made of.
+fn split_at_case_boundaries(word: &str) -> Vec<String> {
+    let characters: Vec<char> = word.chars().collect();
+    let mut parts = Vec::new();
+    let mut part_start = 0;
+    for index in 1..characters.len() {
+        let previous = characters[index - 1];
+        let current = characters[index];
+        let is_next_lowercase = characters
+            .get(index + 1)
+            .is_some_and(|next| next.is_lowercase());
+        let is_boundary = (previous.is_lowercase() && current.is_uppercase())
+            || (previous.is_uppercase() && current.is_uppercase() && is_next_lowercase);
+        if is_boundary {
+            parts.push(characters[part_start..index].iter().collect());
+            part_start = index;
+        }
+    }
+    parts.push(characters[part_start..].iter().collect());
+    parts
+}
+
+fn apply_case_pattern(pseudonym: &str, original: &str) -> String {
+    pseudonym
+        .chars()
+        .zip(original.chars())
+        .map(|(pseudonym_character, original_character)| {
+            if original_character.is_uppercase() {
+                pseudonym_character.to_ascii_uppercase()
+            } else {
+                pseudonym_character
+            }
+        })
+        .collect()
+}
+
+pub struct Anonymizer {
+    generator: RandomNumberGenerator,
+    vocabulary: HashSet<String>,
+    maximum_vocabulary_word_length: usize,
+    pseudonyms: HashMap<String, String>,
+    used_pseudonyms: HashSet<String>,
+    anonymized_texts: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    /// The spoken words in the record are used to split inserted text like helloworld into the words hello and world.
+    pub fn new(record: &[Entry], seed: u64) -> Self {
+        let mut vocabulary = HashSet::new();
+        for entry in record {
+            if let Entry::Command(command) = entry {
+                for (kind, token) in split_into_tokens(command.get_name()) {
+                    if kind == TokenKind::Letters {
+                        for word in split_at_case_boundaries(&token) {
+                            vocabulary.insert(word.to_lowercase());
+                        }
+                    }
+                }
+            }
+        }
+        let maximum_vocabulary_word_length = vocabulary
+            .iter()
+            .map(|word| word.chars().count())
+            .max()
+            .unwrap_or(0);
+        Anonymizer {
+            generator: RandomNumberGenerator::new(seed),
+            vocabulary,
+            maximum_vocabulary_word_length,
+            pseudonyms: HashMap::new(),
+            used_pseudonyms: HashSet::new(),
+            anonymized_texts: HashMap::new(),
+        }
+    }
+
+    fn create_random_text(&mut self, alphabet: &str, length: usize) -> String {
+        let characters: Vec<char> = alphabet.chars().collect();
+        (0..length)
+            .map(|_| characters[self.generator.next_in_range(0, characters.len())])
+            .collect()
+    }
+
+    /// Avoids giving two tokens the same pseudonym unless there are too few pseudonyms of that length to go around.
+    fn create_pseudonym(&mut self, alphabet: &str, length: usize) -> String {
+        let mut pseudonym = self.create_random_text(alphabet, length);
+        for _ in 0..MAXIMUM_ATTEMPTS_TO_FIND_UNUSED_PSEUDONYM {
+            if !self.used_pseudonyms.contains(&pseudonym) {
+                break;
+            }
+            pseudonym = self.create_random_text(alphabet, length);
+        }
+        self.used_pseudonyms.insert(pseudonym.clone());
+        pseudonym
+    }
+
+    fn compute_pseudonym(&mut self, kind: TokenKind, token: &str) -> String {
+        let key = token.to_lowercase();
+        if let Some(pseudonym) = self.pseudonyms.get(&key) {
+            return apply_case_pattern(pseudonym, token);
+        }
+        let alphabet = if kind == TokenKind::Digits {
+            DIGITS
+        } else {
+            LETTERS
+        };
+        let pseudonym = self.create_pseudonym(alphabet, token.chars().count());
+        self.pseudonyms.insert(key, pseudonym.clone());
+        apply_case_pattern(&pseudonym, token)
+    }
+
+    /// Splits a word into spoken words where possible, preferring as few unrecognized characters and then as few parts as possible.
+    /// Consecutive unrecognized characters are kept together as a single part.
+    fn segment_into_spoken_words(&self, word: &str) -> Vec<String> {
+        let characters: Vec<char> = word.chars().collect();
+        let lowercase_characters: Vec<String> = characters
+            .iter()
+            .map(|character| character.to_lowercase().collect())
+            .collect();
+        // Holds the number of unrecognized characters, number of parts, and start of the last part for each prefix.
+        let mut best: Vec<(usize, usize, usize, bool)> =
+            vec![(0, 0, 0, false); characters.len() + 1];
+        for end in 1..=characters.len() {
+            let (unknown, parts, _, _) = best[end - 1];
+            best[end] = (unknown + 1, parts + 1, end - 1, false);
+            let earliest_start = end.saturating_sub(self.maximum_vocabulary_word_length);
+            for start in earliest_start..end {
+                let candidate = lowercase_characters[start..end].concat();
+                if !self.vocabulary.contains(&candidate) {
+                    continue;
+                }
+                let (unknown, parts, _, _) = best[start];
+                if (unknown, parts + 1) < (best[end].0, best[end].1) {
+                    best[end] = (unknown, parts + 1, start, true);
+                }
+            }
+        }
+        let mut parts: Vec<(usize, usize, bool)> = Vec::new();
+        let mut end = characters.len();
+        while end > 0 {
+            let (_, _, start, is_spoken_word) = best[end];
+            parts.push((start, end, is_spoken_word));
+            end = start;
+        }
+        parts.reverse();
+        let mut segments: Vec<String> = Vec::new();
+        let mut was_previous_part_unrecognized = false;
+        for (start, end, is_spoken_word) in parts {
+            let text: String = characters[start..end].iter().collect();
+            match segments.last_mut() {
+                Some(last) if !is_spoken_word && was_previous_part_unrecognized => {
+                    last.push_str(&text)
+                }
+                _ => segments.push(text),
+            }
+            was_previous_part_unrecognized = !is_spoken_word;
+        }
+        segments
+    }
+
+    pub fn anonymize_text(&mut self, text: &str) -> String {
+        if let Some(anonymized_text) = self.anonymized_texts.get(text) {
+            return anonymized_text.clone();
+        }
+        let mut anonymized_text = String::with_capacity(text.len());
+        for (kind, token) in split_into_tokens(text) {
+            match kind {
+                TokenKind::Other => anonymized_text.push_str(&token),
+                TokenKind::Digits => {
+                    anonymized_text.push_str(&self.compute_pseudonym(kind, &token))
+                }
+                TokenKind::Letters => {
+                    for word in split_at_case_boundaries(&token) {
+                        for segment in self.segment_into_spoken_words(&word) {
+                            anonymized_text.push_str(&self.compute_pseudonym(kind, &segment));
+                        }
+                    }
+                }
+            }
+        }
+        self.anonymized_texts
+            .insert(text.to_string(), anonymized_text.clone());
+        anonymized_text
+    }
+
+    fn anonymize_action(&mut self, action: &BasicAction) -> BasicAction {
+        if action.get_name() != INSERT_ACTION_NAME {
+            return action.clone();
+        }
+        let arguments = action
+            .get_arguments()
+            .iter()
+            .map(|argument| match argument {
+                Argument::StringArgument(text) => {
+                    Argument::StringArgument(self.anonymize_text(text))
+                }
+                other => other.clone(),
+            })
+            .collect();
+        BasicAction::new(action.get_name(), arguments)
+    }
+
+    pub fn anonymize_command(&mut self, command: &Command) -> Command {
+        let actions = command
+            .get_actions()
+            .iter()
+            .map(|action| self.anonymize_action(action))
+            .collect();
+        let mut anonymized_command = Command::new(
+            &self.anonymize_text(command.get_name()),
+            actions,
+            command.get_seconds_since_last_action(),
+        );
+        anonymized_command.set_timestamp(command.get_timestamp());
+        // Window titles often contain document names and other private text, so they are anonymized like inserted text.
+        let mut context = command.get_context().clone();
+        context.window_title = context
+            .window_title
+            .map(|window_title| self.anonymize_text(&window_title));
+        anonymized_command.set_context(context);
+        anonymized_command
+    }
+}
+
+/// Replaces the spoken words and inserted text in the record while keeping everything else.
+pub fn anonymize_record(record: Vec<Entry>, seed: u64) -> Vec<Entry> {
+    let mut anonymizer = Anonymizer::new(&record, seed);
+    record
+        .into_iter()
+        .map(|entry| match entry {
+            Entry::RecordingStart(timestamp) => Entry::RecordingStart(timestamp),
+            Entry::Command(command) => Entry::Command(anonymizer.anonymize_command(&command)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_utilities::create_insert_action;
+    use crate::analysis_settings::AnalysisSettings;
+    use crate::recommendation_generation::compute_recommendations_from_record;
+    use crate::test_utilities::create_command;
+
+    fn create_key_action(key: &str) -> BasicAction {
+        BasicAction::new("key", vec![Argument::StringArgument(String::from(key))])
+    }
+
+    fn get_command(entry: &Entry) -> &Command {
+        match entry {
+            Entry::Command(command) => command,
+            Entry::RecordingStart(_) => panic!("Expected a command"),
+        }
+    }
+
+    fn get_insert_text(command: &Command, index: usize) -> &str {
+        match &command.get_actions()[index].get_arguments()[0] {
+            Argument::StringArgument(text) => text,
+            _ => panic!("Expected a string argument"),
+        }
+    }
+
+    fn compute_shape(text: &str) -> String {
+        text.chars()
+            .map(|character| match compute_token_kind(character) {
+                TokenKind::Letters if character.is_uppercase() => 'A',
+                TokenKind::Letters => 'a',
+                TokenKind::Digits => '0',
+                TokenKind::Other => character,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_words_at_case_boundaries() {
+        assert_eq!(
+            split_at_case_boundaries("helloWorld"),
+            vec!["hello", "World"]
+        );
+        assert_eq!(
+            split_at_case_boundaries("HTTPServer"),
+            vec!["HTTP", "Server"]
+        );
+        assert_eq!(split_at_case_boundaries("ALLCAPS"), vec!["ALLCAPS"]);
+    }
+
+    #[test]
+    fn test_segments_text_into_spoken_words() {
+        let record = vec![
+            create_command("hello world", vec![create_key_action("a")]),
+            create_command("hello", vec![create_key_action("b")]),
+        ];
+        let anonymizer = Anonymizer::new(&record, 0);
+        assert_eq!(
+            anonymizer.segment_into_spoken_words("xyhelloworldz"),
+            vec!["xy", "hello", "world", "z"]
+        );
+    }
+
+    #[test]
+    fn test_pseudonyms_keep_shape_and_consistency() {
+        let record = vec![create_command(
+            "snake hello world",
+            vec![
+                create_insert_action("hello_world 42"),
+                create_key_action("ctrl-s"),
+            ],
+        )];
+        let anonymized = anonymize_record(record, 7);
+        let command = get_command(&anonymized[0]);
+        let text = get_insert_text(command, 0);
+        assert_eq!(compute_shape(text), "aaaaa_aaaaa 00");
+        assert_ne!(text, "hello_world 42");
+        let name_words: Vec<&str> = command.get_name().split(' ').collect();
+        assert_eq!(text[..5], *name_words[1]);
+        assert_eq!(text[6..11], *name_words[2]);
+        assert_eq!(command.get_actions()[1], create_key_action("ctrl-s"));
+    }
+
+    #[test]
+    fn test_pseudonyms_are_case_insensitive_and_keep_case_pattern() {
+        let mut anonymizer = Anonymizer::new(&[], 3);
+        let lowercase = anonymizer.anonymize_text("word");
+        let capitalized = anonymizer.anonymize_text("Word");
+        let camel_case = anonymizer.anonymize_text("wordWORD");
+        assert_eq!(
+            capitalized,
+            format!("{}{}", lowercase[..1].to_uppercase(), &lowercase[1..])
+        );
+        assert_eq!(
+            camel_case,
+            format!("{}{}", lowercase, lowercase.to_uppercase())
+        );
+        assert_ne!(
+            anonymizer.anonymize_text("other"),
+            anonymizer.anonymize_text("words")
+        );
+    }
+
+    fn compute_recommendation_structure(record: Vec<Entry>) -> Vec<(usize, usize, u32, usize)> {
+        let mut structure: Vec<(usize, usize, u32, usize)> =
+            compute_recommendations_from_record(record, Some(4), &AnalysisSettings::default())
+                .iter()
+                .map(|statistics| {
+                    (
+                        statistics.number_of_times_used,
+                        statistics.number_of_actions,
+                        statistics.number_of_words_saved,
+                        statistics
+                            .instantiation_set
+                            .as_ref()
+                            .map_or(0, |set| set.get_size()),
+                    )
+                })
+                .collect();
+        structure.sort();
+        structure
+    }
+
+    #[test]
+    fn test_anonymized_record_produces_same_recommendation_structure() {
+        let mut record = vec![Entry::RecordingStart(None)];
+        for (name, text) in [
+            ("camel hello world", "helloWorld"),
+            ("camel good morning", "goodMorning"),
+            ("camel big dog", "bigDog"),
+            ("say hello there", "hello there"),
+        ] {
+            for _ in 0..3 {
+                record.push(create_command(name, vec![create_insert_action(text)]));
+                record.push(create_command("save it", vec![create_key_action("ctrl-s")]));
+            }
+        }
+        let anonymized = anonymize_record(record.clone(), 11);
+        assert!(anonymized != record);
+        let structure = compute_recommendation_structure(record);
+        assert!(
+            structure
+                .iter()
+                .any(|(_, _, _, instantiations)| *instantiations > 1)
+        );
+        assert_eq!(compute_recommendation_structure(anonymized), structure);
+    }
+}
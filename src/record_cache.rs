@@ -0,0 +1,553 @@
+// Defines code for caching parsed records on disk so that large histories do not need to be parsed every time.
+// A cached record is only used when the path, size, and modification time of the history file and the parse mode match.
+
+use crate::action_records::{
+    Argument, BasicAction, Command, CommandContext, Entry, ParseMode, ParsedRecord, TalonCapture,
+    read_file_record,
+};
+use crate::binary_encoding::{BinaryDecoder, BinaryEncoder, compute_stable_hash};
+use crate::data_output::compute_data_directory;
+use crate::paths::{
+    create_directory_if_nonexistent, remove_files_in_directory, write_file_atomically,
+};
+use crate::record_diagnostics::{DiagnosticKind, ParseDiagnostics};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CACHE_DIRECTORY_NAME: &str = "record_cache";
+const CACHE_FILE_EXTENSION: &str = "bin";
+const CACHE_FILE_MAGIC: &[u8; 4] = b"CGRC";
+/// Increase this whenever the encoding changes so that old cache files are ignored.
+const CACHE_FORMAT_VERSION: u32 = 2;
+
+const RECORDING_START_TAG: u8 = 0;
+const COMMAND_TAG: u8 = 1;
+
+const STRING_ARGUMENT_TAG: u8 = 0;
+const INT_ARGUMENT_TAG: u8 = 1;
+const BOOL_ARGUMENT_TAG: u8 = 2;
+const FLOAT_ARGUMENT_TAG: u8 = 3;
+const CAPTURE_ARGUMENT_TAG: u8 = 4;
+const LIST_ARGUMENT_TAG: u8 = 5;
+const DICT_ARGUMENT_TAG: u8 = 6;
+const NONE_ARGUMENT_TAG: u8 = 7;
+
+const DIAGNOSTIC_KINDS: [DiagnosticKind; 7] = [
+    DiagnosticKind::InvalidUtf8,
+    DiagnosticKind::InvalidAction,
+    DiagnosticKind::InvalidCommandName,
+    DiagnosticKind::InvalidTimeDifference,
+    DiagnosticKind::InvalidAbsoluteTime,
+    DiagnosticKind::InvalidContext,
+    DiagnosticKind::InvalidLine,
+];
+
+/// Identifies the exact history file contents and parsing that a cached record came from.
+#[derive(Clone, Debug, PartialEq)]
+struct CacheKey {
+    path: String,
+    size: u64,
+    modified_seconds: u64,
+    modified_nanoseconds: u32,
+    parse_mode: ParseMode,
+}
+
+impl CacheKey {
+    fn compute(path: &Path, parse_mode: ParseMode) -> io::Result<Self> {
+        let canonical_path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&canonical_path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(CacheKey {
+            path: canonical_path.display().to_string(),
+            size: metadata.len(),
+            modified_seconds: modified.as_secs(),
+            modified_nanoseconds: modified.subsec_nanos(),
+            parse_mode,
+        })
+    }
+
+    fn compute_cache_file_name(&self) -> String {
+        format!(
+            "{:016x}.{}",
+            compute_stable_hash(self.path.as_bytes()),
+            CACHE_FILE_EXTENSION
+        )
+    }
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.write_string(&self.path);
+        encoder.write_u64(self.size);
+        encoder.write_u64(self.modified_seconds);
+        encoder.write_u32(self.modified_nanoseconds);
+        encoder.write_u8(encode_parse_mode(self.parse_mode));
+    }
+
+    fn decode(decoder: &mut BinaryDecoder) -> Result<Self, String> {
+        Ok(CacheKey {
+            path: decoder.read_string()?,
+            size: decoder.read_u64()?,
+            modified_seconds: decoder.read_u64()?,
+            modified_nanoseconds: decoder.read_u32()?,
+            parse_mode: decode_parse_mode(decoder.read_u8()?)?,
+        })
+    }
+}
+
+fn encode_parse_mode(parse_mode: ParseMode) -> u8 {
+    match parse_mode {
+        ParseMode::Strict => 0,
+        ParseMode::Lenient => 1,
+    }
+}
+
+fn decode_parse_mode(value: u8) -> Result<ParseMode, String> {
+    match value {
+        0 => Ok(ParseMode::Strict),
+        1 => Ok(ParseMode::Lenient),
+        _ => Err(format!("Invalid parse mode {}", value)),
+    }
+}
+
+fn encode_argument(argument: &Argument, encoder: &mut BinaryEncoder) {
+    match argument {
+        Argument::StringArgument(text) => {
+            encoder.write_u8(STRING_ARGUMENT_TAG);
+            encoder.write_string(text);
+        }
+        Argument::IntArgument(value) => {
+            encoder.write_u8(INT_ARGUMENT_TAG);
+            encoder.write_i32(*value);
+        }
+        Argument::BoolArgument(value) => {
+            encoder.write_u8(BOOL_ARGUMENT_TAG);
+            encoder.write_bool(*value);
+        }
+        Argument::FloatArgument(value) => {
+            encoder.write_u8(FLOAT_ARGUMENT_TAG);
+            encoder.write_f64(*value);
+        }
+        Argument::CaptureArgument(capture) => {
+            encoder.write_u8(CAPTURE_ARGUMENT_TAG);
+            encoder.write_string(capture.get_name());
+            encoder.write_i32(capture.get_instance());
+            encoder.write_string(capture.get_postfix());
+        }
+        Argument::ListArgument(values) => {
+            encoder.write_u8(LIST_ARGUMENT_TAG);
+            encode_arguments(values, encoder);
+        }
+        Argument::DictArgument(entries) => {
+            encoder.write_u8(DICT_ARGUMENT_TAG);
+            encoder.write_length(entries.len());
+            for (key, value) in entries {
+                encoder.write_string(key);
+                encode_argument(value, encoder);
+            }
+        }
+        Argument::NoneArgument => encoder.write_u8(NONE_ARGUMENT_TAG),
+    }
+}
+
+fn encode_arguments(arguments: &[Argument], encoder: &mut BinaryEncoder) {
+    encoder.write_length(arguments.len());
+    for argument in arguments {
+        encode_argument(argument, encoder);
+    }
+}
+
+fn decode_argument(decoder: &mut BinaryDecoder) -> Result<Argument, String> {
+    let argument = match decoder.read_u8()? {
+        STRING_ARGUMENT_TAG => Argument::StringArgument(decoder.read_string()?),
+        INT_ARGUMENT_TAG => Argument::IntArgument(decoder.read_i32()?),
+        BOOL_ARGUMENT_TAG => Argument::BoolArgument(decoder.read_bool()?),
+        FLOAT_ARGUMENT_TAG => Argument::FloatArgument(decoder.read_f64()?),
+        CAPTURE_ARGUMENT_TAG => {
+            let name = decoder.read_string()?;
+            let mut capture = TalonCapture::new(&name, decoder.read_i32()?);
+            capture.set_postfix(&decoder.read_string()?);
+            Argument::CaptureArgument(capture)
+        }
+        LIST_ARGUMENT_TAG => Argument::ListArgument(decode_arguments(decoder)?),
+        DICT_ARGUMENT_TAG => {
+            let mut entries = BTreeMap::new();
+            for _ in 0..decoder.read_length()? {
+                let key = decoder.read_string()?;
+                entries.insert(key, decode_argument(decoder)?);
+            }
+            Argument::DictArgument(entries)
+        }
+        NONE_ARGUMENT_TAG => Argument::NoneArgument,
+        tag => return Err(format!("Invalid argument type {}", tag)),
+    };
+    Ok(argument)
+}
+
+fn decode_arguments(decoder: &mut BinaryDecoder) -> Result<Vec<Argument>, String> {
+    let length = decoder.read_length()?;
+    let mut arguments = Vec::with_capacity(length);
+    for _ in 0..length {
+        arguments.push(decode_argument(decoder)?);
+    }
+    Ok(arguments)
+}
+
+pub fn encode_actions(actions: &[BasicAction], encoder: &mut BinaryEncoder) {
+    encoder.write_length(actions.len());
+    for action in actions {
+        encoder.write_string(action.get_name());
+        encode_arguments(action.get_arguments(), encoder);
+    }
+}
+
+pub fn decode_actions(decoder: &mut BinaryDecoder) -> Result<Vec<BasicAction>, String> {
+    let number_of_actions = decoder.read_length()?;
+    let mut actions = Vec::with_capacity(number_of_actions);
+    for _ in 0..number_of_actions {
+        let action_name = decoder.read_string()?;
+        actions.push(BasicAction::new(&action_name, decode_arguments(decoder)?));
+    }
+    Ok(actions)
+}
+
+fn encode_names(names: &[String], encoder: &mut BinaryEncoder) {
+    encoder.write_length(names.len());
+    for name in names {
+        encoder.write_string(name);
+    }
+}
+
+fn decode_names(decoder: &mut BinaryDecoder) -> Result<Vec<String>, String> {
+    let length = decoder.read_length()?;
+    let mut names = Vec::with_capacity(length);
+    for _ in 0..length {
+        names.push(decoder.read_string()?);
+    }
+    Ok(names)
+}
+
+pub fn encode_context(context: &CommandContext, encoder: &mut BinaryEncoder) {
+    encoder.write_optional_string(context.application.as_deref());
+    encoder.write_optional_string(context.window_title.as_deref());
+    encode_names(&context.modes, encoder);
+    encode_names(&context.tags, encoder);
+}
+
+pub fn decode_context(decoder: &mut BinaryDecoder) -> Result<CommandContext, String> {
+    Ok(CommandContext {
+        application: decoder.read_optional_string()?,
+        window_title: decoder.read_optional_string()?,
+        modes: decode_names(decoder)?,
+        tags: decode_names(decoder)?,
+    })
+}
+
+fn encode_command(command: &Command, encoder: &mut BinaryEncoder) {
+    encoder.write_string(command.get_name());
+    encoder.write_optional_u32(command.get_seconds_since_last_action());
+    encoder.write_optional_u64(command.get_timestamp());
+    encode_context(command.get_context(), encoder);
+    encode_actions(command.get_actions(), encoder);
+}
+
+fn decode_command(decoder: &mut BinaryDecoder) -> Result<Command, String> {
+    let name = decoder.read_string()?;
+    let seconds_since_last_action = decoder.read_optional_u32()?;
+    let timestamp = decoder.read_optional_u64()?;
+    let context = decode_context(decoder)?;
+    let actions = decode_actions(decoder)?;
+    let mut command = Command::new(&name, actions, seconds_since_last_action);
+    command.set_timestamp(timestamp);
+    command.set_context(context);
+    Ok(command)
+}
+
+pub fn encode_entry(entry: &Entry, encoder: &mut BinaryEncoder) {
+    match entry {
+        Entry::RecordingStart(timestamp) => {
+            encoder.write_u8(RECORDING_START_TAG);
+            encoder.write_optional_u64(*timestamp);
+        }
+        Entry::Command(command) => {
+            encoder.write_u8(COMMAND_TAG);
+            encode_command(command, encoder);
+        }
+    }
+}
+
+fn encode_diagnostics(diagnostics: &ParseDiagnostics, encoder: &mut BinaryEncoder) {
+    encoder.write_length(diagnostics.get_diagnostics().len());
+    for diagnostic in diagnostics.get_diagnostics() {
+        let kind_index = DIAGNOSTIC_KINDS
+            .iter()
+            .position(|kind| *kind == diagnostic.kind)
+            .unwrap_or_default();
+        encoder.write_u64(diagnostic.line_number as u64);
+        encoder.write_u8(kind_index as u8);
+        encoder.write_string(&diagnostic.message);
+    }
+}
+
+fn decode_diagnostics(decoder: &mut BinaryDecoder) -> Result<ParseDiagnostics, String> {
+    let mut diagnostics = ParseDiagnostics::new();
+    for _ in 0..decoder.read_length()? {
+        let line_number = decoder.read_u64()? as usize;
+        let kind_index = decoder.read_u8()?;
+        let Some(kind) = DIAGNOSTIC_KINDS.get(kind_index as usize) else {
+            return Err(format!("Invalid diagnostic kind {}", kind_index));
+        };
+        diagnostics.add(line_number, *kind, &decoder.read_string()?);
+    }
+    Ok(diagnostics)
+}
+
+fn encode_parsed_record(key: &CacheKey, parsed_record: &ParsedRecord) -> Vec<u8> {
+    let mut encoder = BinaryEncoder::new();
+    for byte in CACHE_FILE_MAGIC {
+        encoder.write_u8(*byte);
+    }
+    encoder.write_u32(CACHE_FORMAT_VERSION);
+    key.encode(&mut encoder);
+    encode_diagnostics(&parsed_record.diagnostics, &mut encoder);
+    encoder.write_length(parsed_record.record.len());
+    for entry in &parsed_record.record {
+        encode_entry(entry, &mut encoder);
+    }
+    encoder.into_bytes()
+}
+
+/// Returns None when the cached record was made from a different version of the history file.
+fn decode_parsed_record(bytes: &[u8], key: &CacheKey) -> Result<Option<ParsedRecord>, String> {
+    let mut decoder = BinaryDecoder::new(bytes);
+    for expected_byte in CACHE_FILE_MAGIC {
+        if decoder.read_u8()? != *expected_byte {
+            return Err(String::from("The file is not a record cache"));
+        }
+    }
+    if decoder.read_u32()? != CACHE_FORMAT_VERSION || CacheKey::decode(&mut decoder)? != *key {
+        return Ok(None);
+    }
+    let diagnostics = decode_diagnostics(&mut decoder)?;
+    let number_of_entries = decoder.read_length()?;
+    let mut record = Vec::with_capacity(number_of_entries);
+    for _ in 0..number_of_entries {
+        let entry = match decoder.read_u8()? {
+            RECORDING_START_TAG => Entry::RecordingStart(decoder.read_optional_u64()?),
+            COMMAND_TAG => Entry::Command(decode_command(&mut decoder)?),
+            tag => return Err(format!("Invalid entry type {}", tag)),
+        };
+        record.push(entry);
+    }
+    if !decoder.is_finished() {
+        return Err(String::from("Unexpected data after the record"));
+    }
+    Ok(Some(ParsedRecord {
+        record,
+        diagnostics,
+    }))
+}
+
+fn compute_cache_directory() -> io::Result<PathBuf> {
+    let mut directory = compute_data_directory()?;
+    directory.push(CACHE_DIRECTORY_NAME);
+    Ok(directory)
+}
+
+fn read_cached_record(cache_path: &Path, key: &CacheKey) -> Option<ParsedRecord> {
+    let bytes = fs::read(cache_path).ok()?;
+    match decode_parsed_record(&bytes, key) {
+        Ok(parsed_record) => parsed_record,
+        Err(message) => {
+            println!(
+                "Ignoring unreadable cache file {}: {}",
+                cache_path.display(),
+                message
+            );
+            None
+        }
+    }
+}
+
+fn write_cached_record(cache_directory: &Path, cache_path: &Path, bytes: &[u8]) -> io::Result<()> {
+    create_directory_if_nonexistent(&cache_directory.to_path_buf())?;
+    write_file_atomically(cache_path, bytes)
+}
+
+fn read_and_cache_file_record(
+    path: &Path,
+    mode: ParseMode,
+    cache_directory: &Path,
+) -> Result<ParsedRecord, String> {
+    let key = match CacheKey::compute(path, mode) {
+        Ok(key) => key,
+        Err(_) => return read_file_record_without_cache(path, mode),
+    };
+    let cache_path = cache_directory.join(key.compute_cache_file_name());
+    if let Some(parsed_record) = read_cached_record(&cache_path, &key) {
+        println!("Using the cached parse of {}", path.display());
+        return Ok(parsed_record);
+    }
+    let parsed_record = read_file_record_without_cache(path, mode)?;
+    let bytes = encode_parsed_record(&key, &parsed_record);
+    if let Err(e) = write_cached_record(cache_directory, &cache_path, &bytes) {
+        println!("Could not cache the parse of {}: {}", path.display(), e);
+    }
+    Ok(parsed_record)
+}
+
+pub fn read_file_record_without_cache(
+    path: &Path,
+    mode: ParseMode,
+) -> Result<ParsedRecord, String> {
+    match File::open(path) {
+        Ok(file) => read_file_record(file, mode),
+        Err(e) => Err(format!("Could not open {}: {}", path.display(), e)),
+    }
+}
+
+/// Reads the record from the cache if the file has not changed since it was cached and parses and caches it otherwise.
+pub fn read_file_record_with_cache(path: &Path, mode: ParseMode) -> Result<ParsedRecord, String> {
+    match compute_cache_directory() {
+        Ok(cache_directory) => read_and_cache_file_record(path, mode, &cache_directory),
+        Err(_) => read_file_record_without_cache(path, mode),
+    }
+}
+
+/// Deletes every cached record and returns how many were deleted.
+pub fn clear_record_cache() -> io::Result<usize> {
+    remove_files_in_directory(&compute_cache_directory()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn create_test_directory(name: &str) -> PathBuf {
+        let directory =
+            env::temp_dir().join(format!("command_generator_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn create_parsed_record() -> ParsedRecord {
+        let mut capture = TalonCapture::new("number_small", 1);
+        capture.set_postfix(" - 1");
+        let mut dictionary = BTreeMap::new();
+        dictionary.insert(String::from("key"), Argument::NoneArgument);
+        let actions = vec![
+            BasicAction::new(
+                "user.action",
+                vec![
+                    Argument::StringArgument(String::from("text\n")),
+                    Argument::IntArgument(-3),
+                    Argument::BoolArgument(false),
+                    Argument::FloatArgument(2.5),
+                    Argument::CaptureArgument(capture),
+                    Argument::ListArgument(vec![Argument::DictArgument(dictionary)]),
+                ],
+            ),
+            BasicAction::new("key", vec![]),
+        ];
+        let mut command = Command::new("do thing", actions, Some(4));
+        command.set_timestamp(Some(1700000004));
+        command.set_context(CommandContext {
+            application: Some(String::from("Firefox")),
+            window_title: None,
+            modes: vec![String::from("command")],
+            tags: vec![String::from("browser"), String::from("user.tabs")],
+        });
+        let mut diagnostics = ParseDiagnostics::new();
+        diagnostics.add(9, DiagnosticKind::InvalidLine, "bad line");
+        ParsedRecord {
+            record: vec![
+                Entry::RecordingStart(Some(1700000000)),
+                Entry::Command(command),
+                Entry::RecordingStart(None),
+                Entry::Command(Command::new("other", vec![], None)),
+            ],
+            diagnostics,
+        }
+    }
+
+    fn create_key() -> CacheKey {
+        CacheKey {
+            path: String::from("/histories/history.txt"),
+            size: 100,
+            modified_seconds: 1700000000,
+            modified_nanoseconds: 5,
+            parse_mode: ParseMode::Lenient,
+        }
+    }
+
+    #[test]
+    fn test_parsed_record_survives_round_trip() {
+        let parsed_record = create_parsed_record();
+        let bytes = encode_parsed_record(&create_key(), &parsed_record);
+        let decoded = decode_parsed_record(&bytes, &create_key())
+            .unwrap()
+            .unwrap();
+        assert!(decoded.record == parsed_record.record);
+        assert_eq!(decoded.diagnostics, parsed_record.diagnostics);
+    }
+
+    #[test]
+    fn test_changed_file_or_mode_is_not_used() {
+        let bytes = encode_parsed_record(&create_key(), &create_parsed_record());
+        let mut modified_key = create_key();
+        modified_key.modified_nanoseconds += 1;
+        assert!(
+            decode_parsed_record(&bytes, &modified_key)
+                .unwrap()
+                .is_none()
+        );
+        let mut strict_key = create_key();
+        strict_key.parse_mode = ParseMode::Strict;
+        assert!(decode_parsed_record(&bytes, &strict_key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupted_cache_is_an_error() {
+        let bytes = encode_parsed_record(&create_key(), &create_parsed_record());
+        assert!(decode_parsed_record(&bytes[..bytes.len() - 3], &create_key()).is_err());
+        assert!(decode_parsed_record(b"not a cache", &create_key()).is_err());
+    }
+
+    #[test]
+    fn test_cache_is_refreshed_when_file_changes() {
+        let directory = create_test_directory("record_cache");
+        let history_path = directory.join("history.txt");
+        let cache_directory = directory.join("cache");
+        fs::write(
+            &history_path,
+            "START\nCommand: first\n{\"name\": \"key\", \"arguments\": []}\n",
+        )
+        .unwrap();
+        let parsed_record =
+            read_and_cache_file_record(&history_path, ParseMode::Lenient, &cache_directory)
+                .unwrap();
+        assert_eq!(parsed_record.record.len(), 2);
+        assert_eq!(fs::read_dir(&cache_directory).unwrap().count(), 1);
+        let cached_record =
+            read_and_cache_file_record(&history_path, ParseMode::Lenient, &cache_directory)
+                .unwrap();
+        assert!(cached_record.record == parsed_record.record);
+        fs::write(
+            &history_path,
+            "START\nCommand: first and longer\n{\"name\": \"key\", \"arguments\": []}\nSTART\n",
+        )
+        .unwrap();
+        let updated_record =
+            read_and_cache_file_record(&history_path, ParseMode::Lenient, &cache_directory)
+                .unwrap();
+        assert_eq!(updated_record.record.len(), 3);
+        assert_eq!(fs::read_dir(&cache_directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
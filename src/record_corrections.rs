@@ -0,0 +1,347 @@
+// Defines code for removing commands from a record that were corrected soon after being spoken,
+// so that mistakes and the commands that fixed them are not recommended as patterns.
+
+use crate::action_records::{Argument, BasicAction, Command, Entry};
+use crate::action_utilities::{get_insert_text, is_insert};
+
+const UNDO_ACTION_NAME: &str = "edit.undo";
+/// Actions that remove the text dictated by the previous command, like those used by "scratch that".
+const CLEAR_LAST_PHRASE_ACTION_NAMES: [&str; 2] =
+    ["user.clear_last_phrase", "user.clear_last_utterance"];
+const DELETE_ACTION_NAME: &str = "edit.delete";
+const BACKSPACE_KEY: &str = "backspace";
+
+/// What a command that corrects the commands before it undoes.
+#[derive(Debug, PartialEq)]
+enum Correction {
+    /// Undoes the given number of commands before it.
+    Commands(usize),
+    /// Deletes the given number of characters.
+    Characters(usize),
+}
+
+/// The number of backspaces pressed by a key action argument like "backspace backspace:3",
+/// or None if it presses any other key.
+fn count_backspaces_in_keys(keys: &str) -> Option<usize> {
+    keys.split_whitespace()
+        .map(|key| match key.split_once(':') {
+            None if key == BACKSPACE_KEY => Some(1),
+            Some((BACKSPACE_KEY, count)) => count.parse().ok(),
+            _ => None,
+        })
+        .sum()
+}
+
+/// The number of characters the action deletes, or None if it does anything else.
+fn count_deleted_characters(action: &BasicAction) -> Option<usize> {
+    match (action.get_name(), action.get_arguments().as_slice()) {
+        (DELETE_ACTION_NAME, []) => Some(1),
+        ("key", [Argument::StringArgument(keys)]) => count_backspaces_in_keys(keys),
+        _ => None,
+    }
+}
+
+fn get_repeat_count(action: &BasicAction) -> Option<usize> {
+    match (action.get_name(), action.get_arguments().as_slice()) {
+        ("repeat", [Argument::IntArgument(repeat_count)]) if *repeat_count >= 0 => {
+            Some(*repeat_count as usize)
+        }
+        _ => None,
+    }
+}
+
+/// Finds what the command corrects if every one of its actions is a correction of the same kind.
+/// A repeat action repeats the correction made by the action before it.
+fn compute_correction(command: &Command) -> Option<Correction> {
+    let actions = command.get_actions();
+    if actions.is_empty() {
+        return None;
+    }
+    let is_undo = |action: &BasicAction| {
+        action.get_name() == UNDO_ACTION_NAME && action.get_arguments().is_empty()
+    };
+    if let [action] = actions.as_slice()
+        && CLEAR_LAST_PHRASE_ACTION_NAMES.contains(&action.get_name())
+    {
+        return Some(Correction::Commands(1));
+    }
+    let count_action: fn(&BasicAction) -> Option<usize> = if is_undo(&actions[0]) {
+        |action| (action.get_name() == UNDO_ACTION_NAME).then_some(1)
+    } else {
+        count_deleted_characters
+    };
+    let mut total = 0;
+    let mut previous_count = 0;
+    for action in actions {
+        if let Some(repeat_count) = get_repeat_count(action) {
+            total += repeat_count * previous_count;
+        } else {
+            previous_count = count_action(action)?;
+            total += previous_count;
+        }
+    }
+    if is_undo(&actions[0]) {
+        Some(Correction::Commands(total))
+    } else {
+        Some(Correction::Characters(total))
+    }
+}
+
+/// The number of characters typed by the command if it only inserts text.
+fn count_inserted_characters(command: &Command) -> Option<usize> {
+    let actions = command.get_actions();
+    if actions.is_empty() || !actions.iter().all(is_insert) {
+        return None;
+    }
+    Some(
+        actions
+            .iter()
+            .map(|action| get_insert_text(action).chars().count())
+            .sum(),
+    )
+}
+
+/// Corrections spoken more than the correction window after any of the commands they would remove are assumed to fix something else.
+/// The time since a corrected command adds up the times between it and every command spoken after it.
+fn is_spoken_soon_after_corrected_commands(
+    command: &Command,
+    corrected_commands: &[Entry],
+    correction_window_seconds: u32,
+) -> bool {
+    let mut seconds_since_corrected_command: u32 = 0;
+    let mut next_command = command;
+    for entry in corrected_commands.iter().rev() {
+        let Entry::Command(corrected_command) = entry else {
+            return false;
+        };
+        seconds_since_corrected_command = seconds_since_corrected_command
+            .saturating_add(next_command.get_seconds_since_last_action().unwrap_or(0));
+        if seconds_since_corrected_command > correction_window_seconds {
+            return false;
+        }
+        next_command = corrected_command;
+    }
+    true
+}
+
+/// The number of commands at the end of the corrected record that the correction removes,
+/// or None if it cannot be matched to the commands before it in the same session.
+fn compute_number_of_corrected_commands(
+    correction: &Correction,
+    corrected_record: &[Entry],
+) -> Option<usize> {
+    let number_of_commands = match correction {
+        Correction::Commands(number_of_commands) => *number_of_commands,
+        Correction::Characters(number_of_characters) => match corrected_record.last() {
+            Some(Entry::Command(command))
+                if count_inserted_characters(command) == Some(*number_of_characters) =>
+            {
+                1
+            }
+            _ => return None,
+        },
+    };
+    let is_in_session = number_of_commands > 0
+        && number_of_commands <= corrected_record.len()
+        && corrected_record[corrected_record.len() - number_of_commands..]
+            .iter()
+            .all(|entry| matches!(entry, Entry::Command(_)));
+    is_in_session.then_some(number_of_commands)
+}
+
+/// The commands before and after removed commands were not spoken one after the other,
+/// so a recording start is placed between them to keep them from being merged into a chain.
+fn separate_commands_around_removals(
+    record: Vec<Entry>,
+    is_followed_by_removal: Vec<bool>,
+) -> Vec<Entry> {
+    let mut separated_record = Vec::with_capacity(record.len());
+    let mut should_separate = false;
+    for (entry, is_entry_followed_by_removal) in record.into_iter().zip(is_followed_by_removal) {
+        let is_command = matches!(entry, Entry::Command(_));
+        if should_separate && is_command {
+            separated_record.push(Entry::RecordingStart(None));
+        }
+        should_separate = is_command && is_entry_followed_by_removal;
+        separated_record.push(entry);
+    }
+    separated_record
+}
+
+/// Removes commands that were undone within the correction window along with the commands that undid them.
+/// Undo actions remove as many commands before them as they undo, "scratch that" style actions remove the previous command,
+/// and backspaces remove the previous command if it only inserted text with exactly that many characters.
+/// Commands removed by an earlier correction cannot be corrected again, so repeated corrections walk further back.
+/// A correction window of 0 keeps every command.
+/// Returns the corrected record and the number of corrections removed.
+pub fn remove_corrections(
+    record: Vec<Entry>,
+    correction_window_seconds: u32,
+) -> (Vec<Entry>, usize) {
+    if correction_window_seconds == 0 {
+        return (record, 0);
+    }
+    let mut corrected_record: Vec<Entry> = Vec::with_capacity(record.len());
+    // Whether commands were removed right after the entry at the same index of the corrected record.
+    let mut is_followed_by_removal: Vec<bool> = Vec::with_capacity(record.len());
+    let mut number_of_corrections = 0;
+    for entry in record {
+        if let Entry::Command(command) = &entry
+            && let Some(correction) = compute_correction(command)
+            && let Some(number_of_corrected_commands) =
+                compute_number_of_corrected_commands(&correction, &corrected_record)
+            && is_spoken_soon_after_corrected_commands(
+                command,
+                &corrected_record[corrected_record.len() - number_of_corrected_commands..],
+                correction_window_seconds,
+            )
+        {
+            let corrected_length = corrected_record.len() - number_of_corrected_commands;
+            corrected_record.truncate(corrected_length);
+            is_followed_by_removal.truncate(corrected_length);
+            if let Some(is_last_entry_followed_by_removal) = is_followed_by_removal.last_mut() {
+                *is_last_entry_followed_by_removal = true;
+            }
+            number_of_corrections += 1;
+        } else {
+            corrected_record.push(entry);
+            is_followed_by_removal.push(false);
+        }
+    }
+    (
+        separate_commands_around_removals(corrected_record, is_followed_by_removal),
+        number_of_corrections,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_utilities::create_insert_action;
+    use crate::test_utilities::{compute_names, create_command, create_timed_command};
+
+    const CORRECTION_WINDOW_SECONDS: u32 = 30;
+
+    fn create_key_command(name: &str, keys: &str) -> Entry {
+        let action = BasicAction::new("key", vec![Argument::StringArgument(keys.to_string())]);
+        create_command(name, vec![action])
+    }
+
+    fn create_undo_command() -> Entry {
+        create_command("nope", vec![BasicAction::new(UNDO_ACTION_NAME, vec![])])
+    }
+
+    #[test]
+    fn test_removes_undone_and_deleted_commands() {
+        let record = vec![
+            Entry::RecordingStart(None),
+            create_command("say hello", vec![create_insert_action("hello")]),
+            create_command("say world", vec![create_insert_action("world")]),
+            create_undo_command(),
+            create_command("say there", vec![create_insert_action("there")]),
+            create_command(
+                "scratch that",
+                vec![BasicAction::new("user.clear_last_phrase", vec![])],
+            ),
+            create_command("say hi", vec![create_insert_action("hi")]),
+            create_command(
+                "press delete twice",
+                vec![
+                    BasicAction::new(
+                        "key",
+                        vec![Argument::StringArgument("backspace".to_string())],
+                    ),
+                    BasicAction::new("repeat", vec![Argument::IntArgument(1)]),
+                ],
+            ),
+            create_command("say hey", vec![create_insert_action("hey")]),
+            create_key_command("press delete", "backspace:2"),
+            create_key_command("press down", "down"),
+            Entry::RecordingStart(None),
+            create_undo_command(),
+        ];
+        let (corrected_record, number_of_corrections) =
+            remove_corrections(record.clone(), CORRECTION_WINDOW_SECONDS);
+        assert_eq!(number_of_corrections, 3);
+        assert_eq!(
+            compute_names(&corrected_record),
+            vec![
+                "START",
+                "say hello",
+                "START",
+                "say hey",
+                "press delete",
+                "press down",
+                "START",
+                "nope"
+            ]
+        );
+        let (uncorrected_record, number_of_corrections) = remove_corrections(record.clone(), 0);
+        assert_eq!(number_of_corrections, 0);
+        assert_eq!(compute_names(&uncorrected_record), compute_names(&record));
+    }
+
+    #[test]
+    fn test_repeated_undos_remove_several_commands() {
+        let late_undo = create_timed_command(
+            "nope",
+            vec![BasicAction::new(UNDO_ACTION_NAME, vec![])],
+            Some(CORRECTION_WINDOW_SECONDS + 1),
+        );
+        let record = vec![
+            create_key_command("press up", "up"),
+            create_key_command("press down", "down"),
+            create_key_command("press left", "left"),
+            create_command(
+                "nope twice",
+                vec![
+                    BasicAction::new(UNDO_ACTION_NAME, vec![]),
+                    BasicAction::new("repeat", vec![Argument::IntArgument(1)]),
+                ],
+            ),
+            create_key_command("press right", "right"),
+            late_undo,
+        ];
+        let (record, number_of_corrections) = remove_corrections(record, CORRECTION_WINDOW_SECONDS);
+        assert_eq!(number_of_corrections, 1);
+        assert_eq!(
+            compute_names(&record),
+            vec!["press up", "START", "press right", "nope"]
+        );
+    }
+
+    #[test]
+    fn test_undos_only_remove_commands_within_the_window() {
+        let half_window = CORRECTION_WINDOW_SECONDS / 2 + 1;
+        let create_timed_key_command = |name: &str, key: &str| {
+            let action = BasicAction::new("key", vec![Argument::StringArgument(key.to_string())]);
+            create_timed_command(name, vec![action], Some(half_window))
+        };
+        let undo_twice = create_timed_command(
+            "nope twice",
+            vec![
+                BasicAction::new(UNDO_ACTION_NAME, vec![]),
+                BasicAction::new("repeat", vec![Argument::IntArgument(1)]),
+            ],
+            Some(half_window),
+        );
+        let record = vec![
+            create_timed_key_command("press up", "up"),
+            create_timed_key_command("press down", "down"),
+            undo_twice,
+            create_timed_key_command("press left", "left"),
+            create_timed_key_command("press right", "right"),
+            create_timed_command(
+                "nope",
+                vec![BasicAction::new(UNDO_ACTION_NAME, vec![])],
+                Some(half_window),
+            ),
+        ];
+        let (record, number_of_corrections) = remove_corrections(record, CORRECTION_WINDOW_SECONDS);
+        assert_eq!(number_of_corrections, 1);
+        assert_eq!(
+            compute_names(&record),
+            vec!["press up", "press down", "nope twice", "press left"]
+        );
+    }
+}
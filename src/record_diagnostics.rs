@@ -0,0 +1,131 @@
+// Defines code for collecting and summarizing the problems found while parsing a record.
+
+use std::collections::BTreeMap;
+
+const MAXIMUM_NUMBER_OF_EXAMPLES: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticKind {
+    InvalidUtf8,
+    InvalidAction,
+    InvalidCommandName,
+    InvalidTimeDifference,
+    InvalidAbsoluteTime,
+    InvalidContext,
+    InvalidLine,
+}
+
+impl DiagnosticKind {
+    pub fn compute_description(&self) -> &'static str {
+        match self {
+            DiagnosticKind::InvalidUtf8 => "invalid UTF-8",
+            DiagnosticKind::InvalidAction => "invalid action",
+            DiagnosticKind::InvalidCommandName => "invalid command name",
+            DiagnosticKind::InvalidTimeDifference => "invalid time difference",
+            DiagnosticKind::InvalidAbsoluteTime => "invalid absolute time",
+            DiagnosticKind::InvalidContext => "invalid context",
+            DiagnosticKind::InvalidLine => "invalid line",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseDiagnostic {
+    pub line_number: usize,
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseDiagnostics {
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl ParseDiagnostics {
+    pub fn new() -> Self {
+        ParseDiagnostics {
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, line_number: usize, kind: DiagnosticKind, message: &str) {
+        self.diagnostics.push(ParseDiagnostic {
+            line_number,
+            kind,
+            message: String::from(message),
+        });
+    }
+
+    pub fn get_diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn compute_counts_by_kind(&self) -> BTreeMap<DiagnosticKind, usize> {
+        let mut counts = BTreeMap::new();
+        for diagnostic in &self.diagnostics {
+            *counts.entry(diagnostic.kind).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Describes how many problems of each kind were found along with the first few problems.
+    pub fn compute_summary(&self, source_description: &str) -> String {
+        let mut lines = vec![format!(
+            "Found {} problems while parsing {}:",
+            self.diagnostics.len(),
+            source_description
+        )];
+        for (kind, count) in self.compute_counts_by_kind() {
+            lines.push(format!("\t{}: {}", kind.compute_description(), count));
+        }
+        let number_of_examples = self.diagnostics.len().min(MAXIMUM_NUMBER_OF_EXAMPLES);
+        lines.push(format!("First {} problems:", number_of_examples));
+        for diagnostic in self.diagnostics.iter().take(number_of_examples) {
+            lines.push(format!(
+                "\tLine {} ({}): {}",
+                diagnostic.line_number,
+                diagnostic.kind.compute_description(),
+                diagnostic.message
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_diagnostics_by_kind() {
+        let mut diagnostics = ParseDiagnostics::new();
+        diagnostics.add(3, DiagnosticKind::InvalidAction, "first");
+        diagnostics.add(7, DiagnosticKind::InvalidUtf8, "second");
+        diagnostics.add(9, DiagnosticKind::InvalidAction, "third");
+        let counts = diagnostics.compute_counts_by_kind();
+        assert_eq!(counts.get(&DiagnosticKind::InvalidAction), Some(&2));
+        assert_eq!(counts.get(&DiagnosticKind::InvalidUtf8), Some(&1));
+        assert_eq!(counts.get(&DiagnosticKind::InvalidCommandName), None);
+    }
+
+    #[test]
+    fn test_summary_lists_only_first_examples() {
+        let mut diagnostics = ParseDiagnostics::new();
+        for line_number in 1..=MAXIMUM_NUMBER_OF_EXAMPLES + 2 {
+            diagnostics.add(
+                line_number,
+                DiagnosticKind::InvalidTimeDifference,
+                "bad time",
+            );
+        }
+        let summary = diagnostics.compute_summary("history.txt");
+        assert!(summary.starts_with("Found 7 problems while parsing history.txt:"));
+        assert!(summary.contains("\tinvalid time difference: 7"));
+        assert!(summary.contains("\tLine 5 (invalid time difference): bad time"));
+        assert!(!summary.contains("Line 6"));
+    }
+}
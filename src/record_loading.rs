@@ -0,0 +1,416 @@
+// Defines code for finding command history files and combining them into a single record.
+
+use crate::action_records::{Entry, ParseMode, ParsedRecord, read_record_with_mode};
+use crate::record_cache::{read_file_record_with_cache, read_file_record_without_cache};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+pub const STANDARD_INPUT_SPECIFICATION: &str = "-";
+
+/// Where part of the command record is read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordSource {
+    File(PathBuf),
+    StandardInput,
+}
+
+impl RecordSource {
+    pub fn compute_description(&self) -> String {
+        match self {
+            RecordSource::File(path) => path.display().to_string(),
+            RecordSource::StandardInput => String::from("standard input"),
+        }
+    }
+}
+
+fn is_wildcard_character(character: char) -> bool {
+    character == '*' || character == '?'
+}
+
+fn is_glob_pattern(text: &str) -> bool {
+    text.chars().any(is_wildcard_character)
+}
+
+/// Matches text against a pattern where * matches any sequence of characters and ? matches a single character.
+pub fn does_glob_pattern_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let mut pattern_index = 0;
+    let mut text_index = 0;
+    let mut last_star_pattern_index: Option<usize> = None;
+    let mut text_index_after_last_star = 0;
+    while text_index < text.len() {
+        if pattern_index < pattern.len()
+            && (pattern[pattern_index] == '?' || pattern[pattern_index] == text[text_index])
+        {
+            pattern_index += 1;
+            text_index += 1;
+        } else if pattern_index < pattern.len() && pattern[pattern_index] == '*' {
+            last_star_pattern_index = Some(pattern_index);
+            text_index_after_last_star = text_index;
+            pattern_index += 1;
+        } else if let Some(star_index) = last_star_pattern_index {
+            pattern_index = star_index + 1;
+            text_index_after_last_star += 1;
+            text_index = text_index_after_last_star;
+        } else {
+            return false;
+        }
+    }
+    pattern[pattern_index..]
+        .iter()
+        .all(|&character| character == '*')
+}
+
+fn is_hidden_file_name(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+fn compute_directory_to_list(directory: &Path) -> PathBuf {
+    if directory.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        directory.to_path_buf()
+    }
+}
+
+fn list_directory_entries_matching(directory: &Path, pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let mut matches = Vec::new();
+    for entry in fs::read_dir(compute_directory_to_list(directory))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_hidden_file_name(&name) && does_glob_pattern_match(pattern, &name) {
+            matches.push(directory.join(name));
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+fn list_files_in_directory(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let entries = list_directory_entries_matching(directory, "*")?;
+    Ok(entries.into_iter().filter(|path| path.is_file()).collect())
+}
+
+/// Expands wildcards in every component of the path, so patterns like histories/*/record_*.txt are supported.
+fn expand_glob_pattern(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let mut candidates = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let component_text = component.as_os_str().to_string_lossy().to_string();
+        let is_normal_component = matches!(component, Component::Normal(_));
+        let mut next_candidates = Vec::new();
+        for candidate in &candidates {
+            if is_normal_component && is_glob_pattern(&component_text) {
+                if candidate.as_os_str().is_empty() || candidate.is_dir() {
+                    next_candidates
+                        .extend(list_directory_entries_matching(candidate, &component_text)?);
+                }
+            } else {
+                next_candidates.push(candidate.join(&component_text));
+            }
+        }
+        candidates = next_candidates;
+    }
+    let mut files = Vec::new();
+    for candidate in candidates {
+        if candidate.is_dir() {
+            files.extend(list_files_in_directory(&candidate)?);
+        } else if candidate.is_file() {
+            files.push(candidate);
+        }
+    }
+    Ok(files)
+}
+
+/// Turns a path, directory, glob pattern, or - for standard input into the record sources it refers to.
+pub fn expand_record_specification(specification: &str) -> Result<Vec<RecordSource>, String> {
+    if specification == STANDARD_INPUT_SPECIFICATION {
+        return Ok(vec![RecordSource::StandardInput]);
+    }
+    let path = Path::new(specification);
+    let files = if is_glob_pattern(specification) {
+        expand_glob_pattern(specification)
+    } else if path.is_dir() {
+        list_files_in_directory(path)
+    } else if path.is_file() {
+        Ok(vec![path.to_path_buf()])
+    } else {
+        return Err(format!("No file or directory exists at {}", specification));
+    };
+    match files {
+        Ok(files) if files.is_empty() => Err(format!("No files found for {}", specification)),
+        Ok(files) => Ok(files.into_iter().map(RecordSource::File).collect()),
+        Err(e) => Err(format!("Could not search {}: {}", specification, e)),
+    }
+}
+
+/// Describes every source on its own line.
+pub fn compute_sources_description(sources: &[RecordSource]) -> String {
+    sources
+        .iter()
+        .map(|source| source.compute_description())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Standard input is never cached because it cannot be read again to check whether the cache is current.
+fn read_record_from_source(
+    source: &RecordSource,
+    mode: ParseMode,
+    should_use_cache: bool,
+) -> Result<ParsedRecord, String> {
+    match source {
+        RecordSource::File(path) if should_use_cache => read_file_record_with_cache(path, mode),
+        RecordSource::File(path) => read_file_record_without_cache(path, mode),
+        RecordSource::StandardInput => read_record_with_mode(io::stdin().lock(), mode),
+    }
+}
+
+/// Splits a record into sessions, each with the time it started at if known.
+fn split_into_sessions(record: &[Entry]) -> Vec<(Option<u64>, &[Entry])> {
+    let mut sessions = Vec::new();
+    let mut start_timestamp = None;
+    let mut session_start_index = 0;
+    for (index, entry) in record.iter().enumerate() {
+        if let Entry::RecordingStart(timestamp) = entry {
+            if index > session_start_index {
+                sessions.push((start_timestamp, &record[session_start_index..index]));
+            }
+            start_timestamp = *timestamp;
+            session_start_index = index + 1;
+        }
+    }
+    if record.len() > session_start_index {
+        sessions.push((start_timestamp, &record[session_start_index..]));
+    }
+    sessions
+}
+
+fn compute_session_hash(session: &[Entry]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for entry in session {
+        if let Entry::Command(command) = entry {
+            command.to_string().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Keeps track of the sessions from every record so that sessions duplicated across records can be found.
+struct SessionIndex<'a> {
+    sessions_by_hash: HashMap<u64, Vec<(usize, &'a [Entry])>>,
+}
+
+impl<'a> SessionIndex<'a> {
+    fn new() -> Self {
+        SessionIndex {
+            sessions_by_hash: HashMap::new(),
+        }
+    }
+
+    fn is_duplicate_from_earlier_record(
+        &self,
+        session: &[Entry],
+        hash: u64,
+        record_index: usize,
+    ) -> bool {
+        match self.sessions_by_hash.get(&hash) {
+            Some(sessions) => sessions.iter().any(|(other_record_index, other_session)| {
+                *other_record_index < record_index && *other_session == session
+            }),
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, session: &'a [Entry], hash: u64, record_index: usize) {
+        self.sessions_by_hash
+            .entry(hash)
+            .or_default()
+            .push((record_index, session));
+    }
+}
+
+/// Joins records while keeping commands from different records out of the same session.
+/// Sessions that exactly duplicate a session from an earlier record are left out.
+/// Returns the joined record and the number of duplicate sessions removed.
+pub fn join_records(records: &[Vec<Entry>]) -> (Vec<Entry>, usize) {
+    let mut joined = Vec::new();
+    let mut index = SessionIndex::new();
+    let mut number_of_duplicates = 0;
+    for (record_index, record) in records.iter().enumerate() {
+        for (start_timestamp, session) in split_into_sessions(record) {
+            let hash = compute_session_hash(session);
+            if index.is_duplicate_from_earlier_record(session, hash, record_index) {
+                number_of_duplicates += 1;
+                continue;
+            }
+            index.insert(session, hash, record_index);
+            joined.push(Entry::RecordingStart(start_timestamp));
+            joined.extend_from_slice(session);
+        }
+    }
+    (joined, number_of_duplicates)
+}
+
+/// Reads every source into its own record and combines them into one record.
+/// Problems skipped while parsing are summarized for each source.
+pub fn load_record(
+    sources: &[RecordSource],
+    mode: ParseMode,
+    should_use_cache: bool,
+) -> Result<Vec<Entry>, String> {
+    let mut records = Vec::with_capacity(sources.len());
+    for source in sources {
+        if sources.len() > 1 {
+            println!("Reading {}", source.compute_description());
+        }
+        match read_record_from_source(source, mode, should_use_cache) {
+            Ok(parsed_record) => {
+                if !parsed_record.diagnostics.is_empty() {
+                    println!(
+                        "{}",
+                        parsed_record
+                            .diagnostics
+                            .compute_summary(&source.compute_description())
+                    );
+                }
+                records.push(parsed_record.record);
+            }
+            Err(message) => {
+                return Err(format!(
+                    "Error reading {}:\n\t{}",
+                    source.compute_description(),
+                    message
+                ));
+            }
+        }
+    }
+    if records.len() == 1 {
+        return Ok(records.pop().unwrap());
+    }
+    let (record, number_of_duplicates) = join_records(&records);
+    if number_of_duplicates > 0 {
+        println!("Removed {} duplicate sessions", number_of_duplicates);
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utilities::{compute_names, create_key_command};
+
+    #[test]
+    fn test_glob_matches_star() {
+        assert!(does_glob_pattern_match("*.txt", "history.txt"));
+        assert!(does_glob_pattern_match("record*", "record"));
+        assert!(!does_glob_pattern_match("*.txt", "history.txt.bak"));
+    }
+
+    #[test]
+    fn test_glob_matches_question_mark() {
+        assert!(does_glob_pattern_match("week?.txt", "week1.txt"));
+        assert!(!does_glob_pattern_match("week?.txt", "week10.txt"));
+    }
+
+    #[test]
+    fn test_glob_matches_multiple_stars() {
+        assert!(does_glob_pattern_match(
+            "*-*-history",
+            "laptop-2024-history"
+        ));
+        assert!(!does_glob_pattern_match("*-*-history", "laptop-history"));
+    }
+
+    #[test]
+    fn test_joining_separates_records_with_recording_start() {
+        let records = vec![
+            vec![create_key_command("first", Some(1))],
+            vec![create_key_command("second", Some(1))],
+        ];
+        let (joined, number_of_duplicates) = join_records(&records);
+        assert_eq!(number_of_duplicates, 0);
+        assert_eq!(
+            compute_names(&joined),
+            vec!["START", "first", "START", "second"]
+        );
+    }
+
+    #[test]
+    fn test_joining_removes_session_duplicated_in_later_record() {
+        let records = vec![
+            vec![
+                Entry::RecordingStart(None),
+                create_key_command("shared", Some(1)),
+                create_key_command("session", Some(1)),
+                Entry::RecordingStart(None),
+                create_key_command("only first", Some(1)),
+            ],
+            vec![
+                Entry::RecordingStart(None),
+                create_key_command("shared", Some(1)),
+                create_key_command("session", Some(1)),
+                Entry::RecordingStart(None),
+                create_key_command("only second", Some(1)),
+            ],
+        ];
+        let (joined, number_of_duplicates) = join_records(&records);
+        assert_eq!(number_of_duplicates, 1);
+        assert_eq!(
+            compute_names(&joined),
+            vec![
+                "START",
+                "shared",
+                "session",
+                "START",
+                "only first",
+                "START",
+                "only second"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_joining_keeps_repeated_session_within_one_record() {
+        let records = vec![
+            vec![
+                Entry::RecordingStart(None),
+                create_key_command("repeated", Some(1)),
+                Entry::RecordingStart(None),
+                create_key_command("repeated", Some(1)),
+            ],
+            vec![create_key_command("other", Some(1))],
+        ];
+        let (joined, number_of_duplicates) = join_records(&records);
+        assert_eq!(number_of_duplicates, 0);
+        assert_eq!(joined.len(), 6);
+    }
+
+    #[test]
+    fn test_joining_keeps_session_start_times() {
+        let records = vec![
+            vec![
+                Entry::RecordingStart(Some(10)),
+                create_key_command("first", Some(1)),
+            ],
+            vec![
+                create_key_command("untimed", Some(1)),
+                Entry::RecordingStart(Some(20)),
+                create_key_command("second", Some(1)),
+            ],
+        ];
+        let (joined, _) = join_records(&records);
+        let start_times: Vec<Option<u64>> = joined
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::RecordingStart(timestamp) => Some(*timestamp),
+                Entry::Command(_) => None,
+            })
+            .collect();
+        assert_eq!(start_times, vec![Some(10), None, Some(20)]);
+    }
+}
@@ -0,0 +1,409 @@
+// Defines code for summarizing a record before recommendations are generated from it.
+
+use crate::action_records::Entry;
+use crate::action_utilities::{get_insert_text, is_insert};
+use crate::json::escape_json_string;
+use crate::recommendation_generation::FIVE_MINUTES_IN_SECONDS;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const NUMBER_OF_TOP_ITEMS_TO_PRINT: usize = 10;
+const UNKNOWN_TIME_DIFFERENCE_LABEL: &str = "unknown";
+/// Each bucket holds time differences up to and including its bound in seconds.
+const TIME_DIFFERENCE_BUCKETS: [(&str, u32); 6] = [
+    ("0", 0),
+    ("1", 1),
+    ("2-5", 5),
+    ("6-30", 30),
+    ("31-60", 60),
+    ("61-300", FIVE_MINUTES_IN_SECONDS),
+];
+const LONGEST_TIME_DIFFERENCE_LABEL: &str = "over 300";
+
+pub struct SessionLengthSummary {
+    pub minimum: usize,
+    pub median: usize,
+    pub mean: f64,
+    pub maximum: usize,
+}
+
+pub struct RecordStatistics {
+    pub number_of_commands: usize,
+    pub command_counts: Vec<(String, usize)>,
+    pub action_counts: Vec<(String, usize)>,
+    pub insert_text_counts: Vec<(String, usize)>,
+    pub session_lengths: Vec<usize>,
+    pub time_difference_counts: Vec<(String, usize)>,
+    /// The most commands in a chain considered, or None if chains are not limited.
+    pub max_chain_size: Option<usize>,
+    pub time_gap_seconds: u32,
+    pub number_of_chain_starts_cut_by_time_gap: usize,
+    pub number_of_chains_removed_by_time_gap: usize,
+}
+
+/// Orders the counts from most to least common, breaking ties alphabetically.
+fn sort_counts(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut sorted_counts: Vec<(String, usize)> = counts.into_iter().collect();
+    sorted_counts.sort_by(|(first_name, first_count), (second_name, second_count)| {
+        second_count
+            .cmp(first_count)
+            .then_with(|| first_name.cmp(second_name))
+    });
+    sorted_counts
+}
+
+fn compute_session_lengths(record: &[Entry]) -> Vec<usize> {
+    let mut session_lengths = Vec::new();
+    let mut current_length = 0;
+    for entry in record {
+        match entry {
+            Entry::RecordingStart(_) => {
+                if current_length > 0 {
+                    session_lengths.push(current_length);
+                }
+                current_length = 0;
+            }
+            Entry::Command(_) => current_length += 1,
+        }
+    }
+    if current_length > 0 {
+        session_lengths.push(current_length);
+    }
+    session_lengths
+}
+
+fn compute_time_difference_label(seconds_since_last_action: Option<u32>) -> &'static str {
+    let Some(seconds) = seconds_since_last_action else {
+        return UNKNOWN_TIME_DIFFERENCE_LABEL;
+    };
+    TIME_DIFFERENCE_BUCKETS
+        .iter()
+        .find(|(_, bound)| seconds <= *bound)
+        .map_or(LONGEST_TIME_DIFFERENCE_LABEL, |(label, _)| label)
+}
+
+fn compute_time_difference_counts(record: &[Entry]) -> Vec<(String, usize)> {
+    let mut labels: Vec<&str> = TIME_DIFFERENCE_BUCKETS
+        .iter()
+        .map(|(label, _)| *label)
+        .collect();
+    labels.push(LONGEST_TIME_DIFFERENCE_LABEL);
+    labels.push(UNKNOWN_TIME_DIFFERENCE_LABEL);
+    let mut counts: Vec<(String, usize)> = labels
+        .into_iter()
+        .map(|label| (label.to_string(), 0))
+        .collect();
+    for entry in record {
+        if let Entry::Command(command) = entry {
+            let label = compute_time_difference_label(command.get_seconds_since_last_action());
+            if let Some((_, count)) = counts.iter_mut().find(|(name, _)| name == label) {
+                *count += 1;
+            }
+        }
+    }
+    counts
+}
+
+fn is_time_gap_at_index(record: &[Entry], index: usize, time_gap_seconds: u32) -> bool {
+    match &record[index] {
+        Entry::Command(command) => command
+            .get_seconds_since_last_action()
+            .is_some_and(|seconds| seconds > time_gap_seconds),
+        Entry::RecordingStart(_) => false,
+    }
+}
+
+/// Computes how many chain starting points are cut short by the time gap rule and how many chains that removes.
+/// Chains never cross a recording start, so only the chains that would otherwise be considered are counted.
+fn compute_chains_cut_by_time_gap(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    time_gap_seconds: u32,
+) -> (usize, usize) {
+    let mut number_of_starts_cut = 0;
+    let mut number_of_chains_removed = 0;
+    for start in 0..record.len() {
+        if matches!(record[start], Entry::RecordingStart(_)) {
+            continue;
+        }
+        let chain_target =
+            max_chain_size.map_or(record.len(), |size| record.len().min(start + size));
+        let session_chain_size = (start..chain_target)
+            .take_while(|index| !matches!(record[*index], Entry::RecordingStart(_)))
+            .count();
+        let chain_size = (start + 1..start + session_chain_size)
+            .find(|index| is_time_gap_at_index(record, *index, time_gap_seconds))
+            .map_or(session_chain_size, |index| index - start);
+        if chain_size < session_chain_size {
+            number_of_starts_cut += 1;
+            number_of_chains_removed += session_chain_size - chain_size;
+        }
+    }
+    (number_of_starts_cut, number_of_chains_removed)
+}
+
+pub fn compute_record_statistics(
+    record: &[Entry],
+    max_chain_size: Option<usize>,
+    time_gap_seconds: u32,
+) -> RecordStatistics {
+    let mut command_counts: HashMap<String, usize> = HashMap::new();
+    let mut action_counts: HashMap<String, usize> = HashMap::new();
+    let mut insert_text_counts: HashMap<String, usize> = HashMap::new();
+    let mut number_of_commands = 0;
+    for entry in record {
+        if let Entry::Command(command) = entry {
+            number_of_commands += 1;
+            *command_counts
+                .entry(command.get_name().to_string())
+                .or_insert(0) += 1;
+            for action in command.get_actions() {
+                *action_counts
+                    .entry(action.get_name().to_string())
+                    .or_insert(0) += 1;
+                if is_insert(action) {
+                    *insert_text_counts
+                        .entry(get_insert_text(action).clone())
+                        .or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    let (number_of_chain_starts_cut_by_time_gap, number_of_chains_removed_by_time_gap) =
+        compute_chains_cut_by_time_gap(record, max_chain_size, time_gap_seconds);
+    RecordStatistics {
+        number_of_commands,
+        command_counts: sort_counts(command_counts),
+        action_counts: sort_counts(action_counts),
+        insert_text_counts: sort_counts(insert_text_counts),
+        session_lengths: compute_session_lengths(record),
+        time_difference_counts: compute_time_difference_counts(record),
+        max_chain_size,
+        time_gap_seconds,
+        number_of_chain_starts_cut_by_time_gap,
+        number_of_chains_removed_by_time_gap,
+    }
+}
+
+fn compute_counts_report(title: &str, counts: &[(String, usize)]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{} ({} distinct, showing up to {}):",
+        title,
+        counts.len(),
+        NUMBER_OF_TOP_ITEMS_TO_PRINT
+    )];
+    for (name, count) in counts.iter().take(NUMBER_OF_TOP_ITEMS_TO_PRINT) {
+        lines.push(format!("\t{}: {}", count, escape_json_string(name)));
+    }
+    lines
+}
+
+fn compute_counts_json(counts: &[(String, usize)]) -> String {
+    let counts_json: Vec<String> = counts
+        .iter()
+        .map(|(name, count)| format!("[{}, {}]", escape_json_string(name), count))
+        .collect();
+    format!("[{}]", counts_json.join(", "))
+}
+
+impl RecordStatistics {
+    pub fn compute_session_length_summary(&self) -> Option<SessionLengthSummary> {
+        if self.session_lengths.is_empty() {
+            return None;
+        }
+        let mut sorted_lengths = self.session_lengths.clone();
+        sorted_lengths.sort();
+        let total: usize = sorted_lengths.iter().sum();
+        Some(SessionLengthSummary {
+            minimum: sorted_lengths[0],
+            median: sorted_lengths[sorted_lengths.len() / 2],
+            mean: total as f64 / sorted_lengths.len() as f64,
+            maximum: sorted_lengths[sorted_lengths.len() - 1],
+        })
+    }
+
+    pub fn compute_report(&self) -> String {
+        let mut lines = vec![format!("Commands: {}", self.number_of_commands)];
+        lines.push(format!("Sessions: {}", self.session_lengths.len()));
+        if let Some(summary) = self.compute_session_length_summary() {
+            lines.push(format!(
+                "Commands per session: minimum {}, median {}, mean {:.1}, maximum {}",
+                summary.minimum, summary.median, summary.mean, summary.maximum
+            ));
+        }
+        lines.push(String::from("Seconds since the last command:"));
+        for (label, count) in &self.time_difference_counts {
+            lines.push(format!("\t{}: {}", label, count));
+        }
+        let chain_size_description = self
+            .max_chain_size
+            .map_or(String::new(), |size| format!(" of up to {} commands", size));
+        lines.push(format!(
+            "The {} second time gap rule cuts short the chains starting at {} commands, removing {} chains{}",
+            self.time_gap_seconds,
+            self.number_of_chain_starts_cut_by_time_gap,
+            self.number_of_chains_removed_by_time_gap,
+            chain_size_description
+        ));
+        lines.extend(compute_counts_report("Commands", &self.command_counts));
+        lines.extend(compute_counts_report("Actions", &self.action_counts));
+        lines.extend(compute_counts_report(
+            "Inserted text",
+            &self.insert_text_counts,
+        ));
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> String {
+        let session_lengths: Vec<String> = self
+            .session_lengths
+            .iter()
+            .map(|length| length.to_string())
+            .collect();
+        let time_difference_counts: Vec<String> = self
+            .time_difference_counts
+            .iter()
+            .map(|(label, count)| format!("{}: {}", escape_json_string(label), count))
+            .collect();
+        let fields = [
+            format!("\"number_of_commands\": {}", self.number_of_commands),
+            format!("\"session_lengths\": [{}]", session_lengths.join(", ")),
+            format!(
+                "\"seconds_since_last_command\": {{{}}}",
+                time_difference_counts.join(", ")
+            ),
+            format!(
+                "\"time_gap_seconds\": {}, \"max_chain_size\": {}",
+                self.time_gap_seconds,
+                self.max_chain_size
+                    .map_or(String::from("null"), |size| size.to_string())
+            ),
+            format!(
+                "\"chain_starts_cut_by_time_gap\": {}",
+                self.number_of_chain_starts_cut_by_time_gap
+            ),
+            format!(
+                "\"chains_removed_by_time_gap\": {}",
+                self.number_of_chains_removed_by_time_gap
+            ),
+            format!(
+                "\"command_counts\": {}",
+                compute_counts_json(&self.command_counts)
+            ),
+            format!(
+                "\"action_counts\": {}",
+                compute_counts_json(&self.action_counts)
+            ),
+            format!(
+                "\"insert_text_counts\": {}",
+                compute_counts_json(&self.insert_text_counts)
+            ),
+        ];
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_records::{Argument, BasicAction};
+    use crate::action_utilities::create_insert_action;
+    use crate::json::{JsonValue, parse_json};
+    use crate::test_utilities::create_timed_command;
+
+    fn create_key_command(seconds: Option<u32>) -> Entry {
+        let action = BasicAction::new("key", vec![Argument::StringArgument(String::from("a"))]);
+        create_timed_command("press air", vec![action], seconds)
+    }
+
+    fn create_record() -> Vec<Entry> {
+        vec![
+            create_timed_command("word hello", vec![create_insert_action("hello")], None),
+            Entry::RecordingStart(None),
+            create_key_command(Some(0)),
+            create_timed_command("word hello", vec![create_insert_action("hello")], Some(3)),
+            create_key_command(Some(400)),
+            create_key_command(Some(20)),
+            Entry::RecordingStart(None),
+            Entry::RecordingStart(None),
+            create_timed_command(
+                "word world",
+                vec![create_insert_action("world")],
+                Some(1000),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_counts_commands_actions_and_inserts() {
+        let statistics =
+            compute_record_statistics(&create_record(), Some(3), FIVE_MINUTES_IN_SECONDS);
+        assert_eq!(statistics.number_of_commands, 6);
+        assert_eq!(
+            statistics.command_counts,
+            vec![
+                (String::from("press air"), 3),
+                (String::from("word hello"), 2),
+                (String::from("word world"), 1)
+            ]
+        );
+        assert_eq!(
+            statistics.action_counts,
+            vec![(String::from("insert"), 3), (String::from("key"), 3)]
+        );
+        assert_eq!(statistics.insert_text_counts[0], (String::from("hello"), 2));
+    }
+
+    #[test]
+    fn test_computes_sessions_and_time_differences() {
+        let statistics =
+            compute_record_statistics(&create_record(), Some(3), FIVE_MINUTES_IN_SECONDS);
+        assert_eq!(statistics.session_lengths, vec![1, 4, 1]);
+        let summary = statistics.compute_session_length_summary().unwrap();
+        assert_eq!(
+            (summary.minimum, summary.median, summary.maximum),
+            (1, 1, 4)
+        );
+        let count_of = |label: &str| {
+            statistics
+                .time_difference_counts
+                .iter()
+                .find(|(name, _)| name == label)
+                .unwrap()
+                .1
+        };
+        assert_eq!(count_of("0"), 1);
+        assert_eq!(count_of("2-5"), 1);
+        assert_eq!(count_of("6-30"), 1);
+        assert_eq!(count_of("over 300"), 2);
+        assert_eq!(count_of("unknown"), 1);
+    }
+
+    #[test]
+    fn test_counts_chains_cut_by_time_gap() {
+        let statistics =
+            compute_record_statistics(&create_record(), Some(3), FIVE_MINUTES_IN_SECONDS);
+        // The gap before the fifth entry cuts the chains starting at the third and fourth entries.
+        // The gap before the last command does not matter because a recording start comes first.
+        assert_eq!(statistics.number_of_chain_starts_cut_by_time_gap, 2);
+        assert_eq!(statistics.number_of_chains_removed_by_time_gap, 3);
+    }
+
+    #[test]
+    fn test_statistics_json_is_valid() {
+        let statistics =
+            compute_record_statistics(&create_record(), Some(3), FIVE_MINUTES_IN_SECONDS);
+        let json = parse_json(&statistics.to_json()).unwrap();
+        let JsonValue::Object(fields) = json else {
+            panic!("Expected a dictionary");
+        };
+        assert!(fields.iter().any(|(key, value)| key == "number_of_commands"
+            && *value == JsonValue::Number(String::from("6"))));
+    }
+}
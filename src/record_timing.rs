@@ -0,0 +1,322 @@
+// Defines code for reconstructing when commands were spoken and trimming a record to a time range.
+
+use crate::action_records::Entry;
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
+const SECONDS_PER_WEEK: u64 = 7 * SECONDS_PER_DAY;
+
+/// Computes the unix time in seconds of every entry in the record.
+/// Commands without an absolute time are placed by adding their time difference to the previous known time.
+/// Entries remain unknown until an absolute time is found in their session.
+pub fn compute_entry_times(record: &[Entry]) -> Vec<Option<u64>> {
+    let mut times = Vec::with_capacity(record.len());
+    let mut current_time: Option<u64> = None;
+    for entry in record {
+        current_time = match entry {
+            Entry::RecordingStart(timestamp) => *timestamp,
+            Entry::Command(command) => match command.get_timestamp() {
+                Some(timestamp) => Some(timestamp),
+                None => current_time
+                    .map(|time| time + command.get_seconds_since_last_action().unwrap_or(0) as u64),
+            },
+        };
+        times.push(current_time);
+    }
+    times
+}
+
+/// Holds the optional inclusive start and exclusive end of a range of unix times in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeRange {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl TimeRange {
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn contains(&self, time: u64) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time < until)
+    }
+}
+
+/// Keeps only the commands spoken within the range. Commands with an unknown time are removed.
+/// Where commands were removed, a recording start is inserted so that chains do not join commands
+/// that were not spoken consecutively. Kept commands are given their reconstructed absolute time.
+/// Returns the trimmed record and the number of removed commands whose time was unknown.
+pub fn trim_record_to_time_range(record: Vec<Entry>, range: &TimeRange) -> (Vec<Entry>, usize) {
+    let times = compute_entry_times(&record);
+    let mut trimmed = Vec::new();
+    let mut number_of_commands_with_unknown_time = 0;
+    let mut was_previous_command_removed = false;
+    for (entry, time) in record.into_iter().zip(times) {
+        match entry {
+            Entry::RecordingStart(_) => {
+                trimmed.push(Entry::RecordingStart(time));
+                was_previous_command_removed = false;
+            }
+            Entry::Command(mut command) => match time {
+                Some(time) if range.contains(time) => {
+                    if was_previous_command_removed
+                        && matches!(trimmed.last(), Some(Entry::Command(_)))
+                    {
+                        trimmed.push(Entry::RecordingStart(None));
+                    }
+                    was_previous_command_removed = false;
+                    command.set_timestamp(Some(time));
+                    trimmed.push(Entry::Command(command));
+                }
+                Some(_) => was_previous_command_removed = true,
+                None => {
+                    number_of_commands_with_unknown_time += 1;
+                    was_previous_command_removed = true;
+                }
+            },
+        }
+    }
+    remove_empty_sessions(&mut trimmed);
+    (trimmed, number_of_commands_with_unknown_time)
+}
+
+/// Removes recording starts that are immediately followed by another recording start or the end of the record.
+fn remove_empty_sessions(record: &mut Vec<Entry>) {
+    let mut result: Vec<Entry> = Vec::with_capacity(record.len());
+    for entry in record.drain(..) {
+        if let (Some(Entry::RecordingStart(_)), Entry::RecordingStart(_)) = (result.last(), &entry)
+        {
+            result.pop();
+        }
+        result.push(entry);
+    }
+    if let Some(Entry::RecordingStart(_)) = result.last() {
+        result.pop();
+    }
+    *record = result;
+}
+
+fn parse_date_component(text: &str, name: &str) -> Result<u64, String> {
+    match text.parse::<u64>() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(format!("Invalid {} in date: {}", name, text)),
+    }
+}
+
+fn is_leap_year(year: u64) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+fn compute_number_of_days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn compute_number_of_days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let days_in_previous_years: u64 = (1970..year)
+        .map(|year| if is_leap_year(year) { 366 } else { 365 })
+        .sum();
+    let days_in_previous_months: u64 = (1..month)
+        .map(|month| compute_number_of_days_in_month(year, month))
+        .sum();
+    days_in_previous_years + days_in_previous_months + day - 1
+}
+
+/// Converts a YYYY-MM-DD date into the unix time in seconds of midnight UTC at its start.
+fn parse_date(text: &str) -> Result<u64, String> {
+    let components: Vec<&str> = text.split('-').collect();
+    if components.len() != 3 {
+        return Err(format!("Dates must have the form YYYY-MM-DD: {}", text));
+    }
+    let year = parse_date_component(components[0], "year")?;
+    let month = parse_date_component(components[1], "month")?;
+    let day = parse_date_component(components[2], "day")?;
+    if year < 1970 {
+        return Err(format!("Dates before 1970 are not supported: {}", text));
+    }
+    if !(1..=12).contains(&month) {
+        return Err(format!("Invalid month in date: {}", text));
+    }
+    if day < 1 || day > compute_number_of_days_in_month(year, month) {
+        return Err(format!("Invalid day in date: {}", text));
+    }
+    Ok(compute_number_of_days_since_epoch(year, month, day) * SECONDS_PER_DAY)
+}
+
+fn compute_seconds_per_unit(unit: char) -> Option<u64> {
+    match unit {
+        'h' => Some(SECONDS_PER_HOUR),
+        'd' => Some(SECONDS_PER_DAY),
+        'w' => Some(SECONDS_PER_WEEK),
+        _ => None,
+    }
+}
+
+/// Converts a duration like 30d, 12h, or 2w into the unix time that long before the current time.
+fn parse_relative_time(text: &str, current_time: u64) -> Result<u64, String> {
+    let unit = text.chars().last().unwrap_or_default();
+    let Some(seconds_per_unit) = compute_seconds_per_unit(unit) else {
+        return Err(format!(
+            "Durations must end in h, d, or w for hours, days, or weeks: {}",
+            text
+        ));
+    };
+    let amount_text = &text[..text.len() - unit.len_utf8()];
+    match amount_text.parse::<u64>() {
+        Ok(amount) => Ok(current_time.saturating_sub(amount.saturating_mul(seconds_per_unit))),
+        Err(_) => Err(format!("Invalid duration: {}", text)),
+    }
+}
+
+/// Converts unix seconds, a YYYY-MM-DD date, or a duration before the current time like 30d into unix seconds.
+/// When the time marks the end of a range, a date refers to the end of that day.
+pub fn parse_time_specification(
+    text: &str,
+    current_time: u64,
+    is_end_of_range: bool,
+) -> Result<u64, String> {
+    let text = text.trim();
+    if let Ok(seconds) = text.parse::<u64>() {
+        Ok(seconds)
+    } else if text.contains('-') {
+        let start_of_day = parse_date(text)?;
+        if is_end_of_range {
+            Ok(start_of_day + SECONDS_PER_DAY)
+        } else {
+            Ok(start_of_day)
+        }
+    } else {
+        parse_relative_time(text, current_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_records::{BasicAction, Command};
+    use crate::test_utilities::{compute_names, create_key_command};
+
+    fn create_timestamped_command(name: &str, timestamp: u64) -> Entry {
+        let action = BasicAction::new("key", vec![]);
+        let mut command = Command::new(name, vec![action], None);
+        command.set_timestamp(Some(timestamp));
+        Entry::Command(command)
+    }
+
+    #[test]
+    fn test_reconstructs_times_from_session_start() {
+        let record = vec![
+            create_key_command("before any time", Some(5)),
+            Entry::RecordingStart(Some(1000)),
+            create_key_command("first", Some(3)),
+            create_key_command("second", None),
+            create_key_command("third", Some(10)),
+            create_timestamped_command("fourth", 2000),
+            create_key_command("fifth", Some(1)),
+            Entry::RecordingStart(None),
+            create_key_command("unknown", Some(4)),
+        ];
+        assert_eq!(
+            compute_entry_times(&record),
+            vec![
+                None,
+                Some(1000),
+                Some(1003),
+                Some(1003),
+                Some(1013),
+                Some(2000),
+                Some(2001),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trimming_separates_commands_around_removed_commands() {
+        let record = vec![
+            Entry::RecordingStart(Some(100)),
+            create_key_command("early", Some(0)),
+            create_key_command("kept", Some(100)),
+            create_key_command("removed", Some(1000)),
+            create_key_command("also kept", Some(1)),
+            Entry::RecordingStart(None),
+            create_key_command("unknown", Some(1)),
+        ];
+        let range = TimeRange {
+            since: Some(150),
+            until: Some(1200),
+        };
+        let (trimmed, number_of_unknown) = trim_record_to_time_range(record, &range);
+        assert_eq!(number_of_unknown, 1);
+        assert_eq!(compute_names(&trimmed), vec!["START", "kept"]);
+        let range = TimeRange {
+            since: Some(150),
+            until: None,
+        };
+        let record = vec![
+            Entry::RecordingStart(Some(100)),
+            create_key_command("early", Some(0)),
+            create_key_command("kept", Some(100)),
+            create_timestamped_command("gap", 50),
+            create_key_command("also kept", Some(200)),
+        ];
+        let (trimmed, _) = trim_record_to_time_range(record, &range);
+        assert_eq!(
+            compute_names(&trimmed),
+            vec!["START", "kept", "START", "also kept"]
+        );
+        match &trimmed[3] {
+            Entry::Command(command) => assert_eq!(command.get_timestamp(), Some(250)),
+            Entry::RecordingStart(_) => panic!("Expected a command"),
+        }
+    }
+
+    #[test]
+    fn test_parses_unix_seconds() {
+        assert_eq!(
+            parse_time_specification("1700000000", 0, false),
+            Ok(1700000000)
+        );
+    }
+
+    #[test]
+    fn test_parses_dates() {
+        assert_eq!(parse_time_specification("1970-01-01", 0, false), Ok(0));
+        assert_eq!(
+            parse_time_specification("2024-03-01", 0, false),
+            Ok(1709251200)
+        );
+        assert_eq!(
+            parse_time_specification("2024-02-29", 0, true),
+            Ok(1709251200)
+        );
+        assert!(parse_time_specification("2023-02-29", 0, false).is_err());
+        assert!(parse_time_specification("2024-13-01", 0, false).is_err());
+        assert!(parse_time_specification("2024-1", 0, false).is_err());
+    }
+
+    #[test]
+    fn test_parses_relative_times() {
+        let now = 100 * SECONDS_PER_DAY;
+        assert_eq!(
+            parse_time_specification("30d", now, false),
+            Ok(70 * SECONDS_PER_DAY)
+        );
+        assert_eq!(
+            parse_time_specification("2w", now, false),
+            Ok(86 * SECONDS_PER_DAY)
+        );
+        assert_eq!(
+            parse_time_specification("12h", now, false),
+            Ok(now - 12 * SECONDS_PER_HOUR)
+        );
+        assert!(parse_time_specification("30x", now, false).is_err());
+        assert!(parse_time_specification("d", now, false).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+// Defines code for writing a record back out in the format read by the record parser.
+
+use crate::action_records::{
+    ABSOLUTE_TIME_PREFIX, APPLICATION_PREFIX, COMMAND_NAME_PREFIX, Command, CommandContext, Entry,
+    MODES_PREFIX, RECORDING_START_MESSAGE, TAGS_PREFIX, TIME_DIFFERENCE_PREFIX,
+    WINDOW_TITLE_PREFIX,
+};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+fn write_absolute_time<W: Write>(writer: &mut W, timestamp: Option<u64>) -> io::Result<()> {
+    if let Some(timestamp) = timestamp {
+        writeln!(writer, "{}{}", ABSOLUTE_TIME_PREFIX, timestamp)?;
+    }
+    Ok(())
+}
+
+fn write_context<W: Write>(writer: &mut W, context: &CommandContext) -> io::Result<()> {
+    if let Some(application) = &context.application {
+        writeln!(writer, "{} {}", APPLICATION_PREFIX, application)?;
+    }
+    if let Some(window_title) = &context.window_title {
+        writeln!(writer, "{} {}", WINDOW_TITLE_PREFIX, window_title)?;
+    }
+    if !context.modes.is_empty() {
+        writeln!(writer, "{} {}", MODES_PREFIX, context.modes.join(" "))?;
+    }
+    if !context.tags.is_empty() {
+        writeln!(writer, "{} {}", TAGS_PREFIX, context.tags.join(" "))?;
+    }
+    Ok(())
+}
+
+/// The time difference goes after the actions because the parser gives a command the last time difference in its block.
+fn write_command<W: Write>(writer: &mut W, command: &Command) -> io::Result<()> {
+    writeln!(writer, "{}{}", COMMAND_NAME_PREFIX, command.get_name())?;
+    write_absolute_time(writer, command.get_timestamp())?;
+    write_context(writer, command.get_context())?;
+    for action in command.get_actions() {
+        if !action.is_representable_in_json() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The command {} has an infinite or NaN number, which cannot be written as JSON",
+                    command.get_name()
+                ),
+            ));
+        }
+        writeln!(writer, "{}", action.to_json())?;
+    }
+    if let Some(seconds) = command.get_seconds_since_last_action() {
+        writeln!(writer, "{}{}", TIME_DIFFERENCE_PREFIX, seconds)?;
+    }
+    Ok(())
+}
+
+pub fn write_record<W: Write>(record: &[Entry], writer: &mut W) -> io::Result<()> {
+    for entry in record {
+        match entry {
+            Entry::RecordingStart(timestamp) => {
+                writeln!(writer, "{}", RECORDING_START_MESSAGE)?;
+                write_absolute_time(writer, *timestamp)?;
+            }
+            Entry::Command(command) => write_command(writer, command)?,
+        }
+    }
+    Ok(())
+}
+
+pub fn write_record_to_file(record: &[Entry], path: &Path) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut buffered_writer = io::BufWriter::new(file);
+    write_record(record, &mut buffered_writer)?;
+    buffered_writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action_records::{
+        Argument, BasicAction, ParseMode, TalonCapture, read_record_with_mode,
+    };
+    use crate::random::RandomNumberGenerator;
+    use std::collections::BTreeMap;
+
+    const NUMBER_OF_RANDOM_RECORDS: u64 = 50;
+    const MAXIMUM_ARGUMENT_DEPTH: usize = 3;
+    const TEXT_CHARACTERS: [char; 16] = [
+        'a', 'Z', '7', ' ', '"', '\\', '\n', '\t', '{', ']', ',', ':', '\u{1}', 'é', '😀', '/',
+    ];
+
+    fn compute_record_text(record: &[Entry]) -> String {
+        let mut bytes = Vec::new();
+        write_record(record, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn read_record_text(text: &str) -> Vec<Entry> {
+        match read_record_with_mode(text.as_bytes(), ParseMode::Strict) {
+            Ok(parsed_record) => parsed_record.record,
+            Err(message) => panic!("Error parsing written record:\n{}\n{}", message, text),
+        }
+    }
+
+    fn create_random_text(generator: &mut RandomNumberGenerator) -> String {
+        let length = generator.next_in_range(0, 8);
+        (0..length)
+            .map(|_| TEXT_CHARACTERS[generator.next_in_range(0, TEXT_CHARACTERS.len())])
+            .collect()
+    }
+
+    fn create_random_command_name(generator: &mut RandomNumberGenerator) -> String {
+        let number_of_words = generator.next_in_range(1, 4);
+        (0..number_of_words)
+            .map(|_| format!("word{}", generator.next_in_range(0, 100)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn create_random_argument(generator: &mut RandomNumberGenerator, depth: usize) -> Argument {
+        let number_of_kinds = if depth < MAXIMUM_ARGUMENT_DEPTH { 9 } else { 7 };
+        match generator.next_in_range(0, number_of_kinds) {
+            0 => Argument::StringArgument(create_random_text(generator)),
+            1 => Argument::IntArgument(generator.next() as i32 - (1 << 30)),
+            2 => Argument::BoolArgument(generator.next_in_range(0, 2) == 0),
+            3 => Argument::FloatArgument(generator.next() as f64 / 64.0 - 1000.0),
+            4 => Argument::CaptureArgument(TalonCapture::new(
+                "user.text",
+                generator.next_in_range(0, 5) as i32,
+            )),
+            5 => Argument::NoneArgument,
+            6 => Argument::FloatArgument(generator.next_in_range(0, 10) as f64),
+            7 => Argument::ListArgument(create_random_arguments(generator, depth + 1)),
+            _ => {
+                let mut entries = BTreeMap::new();
+                for _ in 0..generator.next_in_range(0, 3) {
+                    entries.insert(
+                        create_random_text(generator),
+                        create_random_argument(generator, depth + 1),
+                    );
+                }
+                Argument::DictArgument(entries)
+            }
+        }
+    }
+
+    fn create_random_arguments(
+        generator: &mut RandomNumberGenerator,
+        depth: usize,
+    ) -> Vec<Argument> {
+        (0..generator.next_in_range(0, 4))
+            .map(|_| create_random_argument(generator, depth))
+            .collect()
+    }
+
+    fn create_random_optional_number(generator: &mut RandomNumberGenerator) -> Option<u32> {
+        if generator.next_in_range(0, 2) == 0 {
+            Some(generator.next_in_range(0, 1000) as u32)
+        } else {
+            None
+        }
+    }
+
+    fn create_random_context_names(generator: &mut RandomNumberGenerator) -> Vec<String> {
+        let mut names: Vec<String> = (0..generator.next_in_range(0, 3))
+            .map(|_| format!("user.name_{}", generator.next_in_range(0, 5)))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn create_random_context(generator: &mut RandomNumberGenerator) -> CommandContext {
+        let application = if generator.next_in_range(0, 2) == 0 {
+            Some(create_random_command_name(generator))
+        } else {
+            None
+        };
+        let window_title = if generator.next_in_range(0, 2) == 0 {
+            Some(create_random_command_name(generator))
+        } else {
+            None
+        };
+        CommandContext {
+            application,
+            window_title,
+            modes: create_random_context_names(generator),
+            tags: create_random_context_names(generator),
+        }
+    }
+
+    fn create_random_command(generator: &mut RandomNumberGenerator) -> Command {
+        let actions = (0..generator.next_in_range(1, 4))
+            .map(|_| {
+                let name = format!("user.action_{}", generator.next_in_range(0, 10));
+                BasicAction::new(&name, create_random_arguments(generator, 0))
+            })
+            .collect();
+        let seconds_since_last_action = create_random_optional_number(generator);
+        let mut command = Command::new(
+            &create_random_command_name(generator),
+            actions,
+            seconds_since_last_action,
+        );
+        let timestamp = create_random_optional_number(generator);
+        command.set_timestamp(timestamp.map(|seconds| 1_700_000_000 + seconds as u64));
+        command.set_context(create_random_context(generator));
+        command
+    }
+
+    fn create_random_record(seed: u64) -> Vec<Entry> {
+        let mut generator = RandomNumberGenerator::new(seed);
+        let mut record = Vec::new();
+        for _ in 0..generator.next_in_range(0, 20) {
+            if generator.next_in_range(0, 4) == 0 {
+                let timestamp = create_random_optional_number(&mut generator);
+                record.push(Entry::RecordingStart(timestamp.map(u64::from)));
+            } else {
+                record.push(Entry::Command(create_random_command(&mut generator)));
+            }
+        }
+        record
+    }
+
+    #[test]
+    fn test_writes_expected_lines() {
+        let mut command = Command::new(
+            "press enter",
+            vec![BasicAction::new(
+                "key",
+                vec![Argument::StringArgument(String::from("enter"))],
+            )],
+            Some(3),
+        );
+        command.set_timestamp(Some(1700000003));
+        let record = vec![
+            Entry::RecordingStart(Some(1700000000)),
+            Entry::Command(command),
+        ];
+        assert_eq!(
+            compute_record_text(&record),
+            "START\n@1700000000\nCommand: press enter\n@1700000003\n{\"name\": \"key\", \"arguments\": [\"enter\"]}\nT3\n"
+        );
+    }
+
+    #[test]
+    fn test_refuses_to_write_numbers_json_cannot_express() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let action = BasicAction::new(
+                "mouse_scroll",
+                vec![Argument::ListArgument(vec![Argument::FloatArgument(value)])],
+            );
+            let record = vec![Entry::Command(Command::new("scroll", vec![action], None))];
+            let error = write_record(&record, &mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_random_records_survive_round_trip() {
+        for seed in 0..NUMBER_OF_RANDOM_RECORDS {
+            let record = create_random_record(seed);
+            let text = compute_record_text(&record);
+            let loaded = read_record_text(&text);
+            assert!(
+                loaded == record,
+                "Seed {} did not round trip:\n{}",
+                seed,
+                text
+            );
+            assert_eq!(compute_record_text(&loaded), text);
+        }
+    }
+
+    #[test]
+    fn test_rewriting_parsed_history_is_stable() {
+        let text = "START\nCommand: word hello\n{\"name\": \"insert\", \"arguments\": [\"hello\"]}\nT3\nT5\nCommand: scroll\n{\"arguments\": [1.0, -2], \"name\": \"mouse_scroll\"}\n";
+        let written = compute_record_text(&read_record_text(text));
+        assert_eq!(compute_record_text(&read_record_text(&written)), written);
+        assert!(written.contains("[1.0, -2]"));
+    }
+}
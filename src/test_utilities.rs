@@ -0,0 +1,36 @@
+// Defines helpers shared by the tests of several modules for building records and checking what is left in them.
+
+use crate::action_records::{BasicAction, Command, Entry};
+
+/// A command spoken a second after the previous one.
+pub fn create_command(name: &str, actions: Vec<BasicAction>) -> Entry {
+    create_timed_command(name, actions, Some(1))
+}
+
+pub fn create_timed_command(
+    name: &str,
+    actions: Vec<BasicAction>,
+    seconds_since_last_action: Option<u32>,
+) -> Entry {
+    Entry::Command(Command::new(name, actions, seconds_since_last_action))
+}
+
+/// A command for tests that only care about which commands are in a record and when they were spoken.
+pub fn create_key_command(name: &str, seconds_since_last_action: Option<u32>) -> Entry {
+    create_timed_command(
+        name,
+        vec![BasicAction::new("key", vec![])],
+        seconds_since_last_action,
+    )
+}
+
+/// The names of the commands in the record, with START for every recording start.
+pub fn compute_names(record: &[Entry]) -> Vec<String> {
+    record
+        .iter()
+        .map(|entry| match entry {
+            Entry::RecordingStart(_) => String::from("START"),
+            Entry::Command(command) => command.get_name().to_string(),
+        })
+        .collect()
+}